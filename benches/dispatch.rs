@@ -0,0 +1,51 @@
+//! Benchmarks for handling key events, from the key message to
+//! the event having reached the compositor. The compositor is
+//! a mock (see `kway::mock`), so these don't need a display.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use kway::{
+    keyboard::{KeyMessage, Keyboard},
+    layout::{KeyDef, Layout},
+    mock,
+};
+
+/// Scan codes of the basic keys on the base layers.
+fn base_keys(layout: &Layout) -> Vec<u16> {
+    layout
+        .left
+        .iter()
+        .take(1)
+        .chain(layout.right.iter().take(1))
+        .flat_map(|layer| layer.rows().flatten())
+        .filter_map(|key| match key {
+            KeyDef::Basic(key) => Some(key.key.code()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn dispatch(c: &mut Criterion) {
+    let layout = Layout::default();
+    let keys = base_keys(&layout);
+    let mut keys = keys.iter().cycle();
+    let mut keyboard = Keyboard::with_connection(layout, mock::connect());
+
+    c.bench_function("key press and release", |b| {
+        b.iter(|| {
+            let code = *keys.next().unwrap();
+            keyboard.handle(KeyMessage::ButtonPress(code));
+            keyboard.handle(KeyMessage::ButtonRelease(code));
+        })
+    });
+
+    let shift = evdev::Key::KEY_LEFTSHIFT.code();
+    c.bench_function("modifier press and release", |b| {
+        b.iter(|| {
+            keyboard.handle(KeyMessage::ModPress(shift));
+            keyboard.handle(KeyMessage::ModRelease(shift));
+        })
+    });
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,42 @@
+//! Benchmarks for loading the layout and rendering its layers.
+//! Rendering builds the key widgets, so it needs a display,
+//! and is skipped without one.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use kway::{
+    config::Config,
+    layout::{KeyContext, Layout},
+    ui::{UIMessage, keyboard::KEY_SIZE},
+};
+use relm4::gtk;
+
+fn parse(c: &mut Criterion) {
+    c.bench_function("layout parse", |b| {
+        b.iter(|| Layout::try_from_str(Layout::DEFAULT))
+    });
+}
+
+fn render(c: &mut Criterion) {
+    if let Err(err) = gtk::init() {
+        eprintln!("Skipping layer rendering: {}", err);
+        return;
+    }
+    let layout = Layout::default();
+    let config = Config::default();
+    let context = KeyContext::default();
+    let (sender, _receiver) = relm4::channel::<UIMessage>();
+
+    let mut group = c.benchmark_group("layer render");
+    let sides = [("left", &layout.left), ("right", &layout.right)];
+    for (side, layers) in sides {
+        for (i, layer) in layers.iter().enumerate() {
+            group.bench_function(format!("{} {}", side, i), |b| {
+                b.iter(|| layer.render(KEY_SIZE, None, &config, &context, &sender))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, render);
+criterion_main!(benches);
@@ -1,9 +1,11 @@
 use relm4::RelmApp;
 
 use crate::{
+    config::Config,
     keyboard::Keyboard,
     layout::Layout,
     pointer::Pointer,
+    state::State,
     ui::{UIMessage, UIModel},
 };
 
@@ -11,9 +13,11 @@ pub struct App {
     ui: RelmApp<UIMessage>,
     keyboard: Keyboard,
     pointer: Pointer,
+    config: Config,
+    state: State,
 }
 impl App {
-    pub fn new(layout: Layout) -> Self {
+    pub fn new(layout: Layout, config: Config, state: State) -> Self {
         let ui = RelmApp::<UIMessage>::new("kway");
         let styles = include_str!("../assets/style.css");
         relm4::set_global_css_with_priority(styles, relm4::gtk::STYLE_PROVIDER_PRIORITY_USER);
@@ -22,10 +26,13 @@ impl App {
             ui,
             pointer: Pointer::new(),
             keyboard: Keyboard::new(layout),
+            config,
+            state,
         }
     }
 
     pub fn run(self) {
-        self.ui.run::<UIModel>((self.keyboard, self.pointer));
+        self.ui
+            .run::<UIModel>((self.keyboard, self.pointer, self.config, self.state));
     }
 }
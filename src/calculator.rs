@@ -0,0 +1,225 @@
+//! A small arithmetic evaluator, so the result of an
+//! expression typed on a calculator layer can be previewed.
+//!
+//! Supports `+ - * / % ^` (also `× ÷ −`),
+//! parentheses, unary minus, and decimals.
+
+/// Characters that can be part of an expression.
+fn is_expression_char(c: char) -> bool {
+    c.is_ascii_digit() || "+-*/%^().,×÷− ".contains(c)
+}
+
+/// The expression at the end of the text, if there is one
+/// worth evaluating, i.e. one with an operator in it.
+/// It runs to the end of the text so it can be replaced.
+pub fn trailing_expression(text: &str) -> Option<&str> {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_expression_char(*c))
+        .last()
+        .map(|(i, _)| i)?;
+    let expr = text[start..].trim_start();
+    let has_operator = expr
+        .trim_start_matches(['-', '−'])
+        .contains(|c: char| "+-*/%^×÷−".contains(c));
+    (has_operator && expr.contains(|c: char| c.is_ascii_digit())).then_some(expr)
+}
+
+/// Evaluate an expression, if it's valid and finite.
+pub fn evaluate(expr: &str) -> Option<f64> {
+    let tokens = tokenize(expr)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let value = parser.expression()?;
+    (parser.pos == parser.tokens.len() && value.is_finite()).then_some(value)
+}
+
+/// Format a result without trailing zeros or float noise.
+pub fn format(value: f64) -> String {
+    let rounded = format!("{:.10}", value);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".into()
+    } else {
+        trimmed.into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Op(char),
+    Open,
+    Close,
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            ' ' => continue,
+            '0'..='9' | '.' | ',' => {
+                // Commas are taken as decimal separators.
+                let mut number = String::from(if c == ',' { '.' } else { c });
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '0'..='9' | '.' => number.push(c),
+                        ',' => number.push('.'),
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                Token::Number(number.parse().ok()?)
+            }
+            '×' => Token::Op('*'),
+            '÷' => Token::Op('/'),
+            '−' => Token::Op('-'),
+            '+' | '-' | '*' | '/' | '%' | '^' => Token::Op(c),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            _ => return None,
+        };
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+/// Recursive descent, lowest precedence first:
+/// sums, then products, then unary minus, then powers.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        while let Some(Token::Op(op @ ('*' | '/' | '%'))) = self.peek() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Some(value)
+    }
+
+    /// So that `-2^2` is `-4`.
+    fn unary(&mut self) -> Option<f64> {
+        match self.peek()? {
+            Token::Op('-') => {
+                self.pos += 1;
+                Some(-self.unary()?)
+            }
+            Token::Op('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// Powers are right-associative, so `2^3^2` is `2^9`.
+    fn power(&mut self) -> Option<f64> {
+        let base = self.atom()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            return Some(base.powf(exponent));
+        }
+        Some(base)
+    }
+
+    fn atom(&mut self) -> Option<f64> {
+        match self.advance()? {
+            Token::Number(value) => Some(value),
+            Token::Open => {
+                let value = self.expression()?;
+                match self.advance()? {
+                    Token::Close => Some(value),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("2+3*4"), Some(14.));
+        assert_eq!(evaluate("(2+3)*4"), Some(20.));
+        assert_eq!(evaluate("10-4-3"), Some(3.));
+        assert_eq!(evaluate("7%4*2"), Some(6.));
+    }
+
+    #[test]
+    fn powers_and_unary_minus() {
+        assert_eq!(evaluate("2^3^2"), Some(512.));
+        assert_eq!(evaluate("-2^2"), Some(-4.));
+        assert_eq!(evaluate("2^-1"), Some(0.5));
+        assert_eq!(evaluate("--3"), Some(3.));
+    }
+
+    #[test]
+    fn other_symbols() {
+        assert_eq!(evaluate("6×7"), Some(42.));
+        assert_eq!(evaluate("9÷2"), Some(4.5));
+        assert_eq!(evaluate("5−8"), Some(-3.));
+        assert_eq!(evaluate("1,5*2"), Some(3.));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(evaluate(""), None);
+        assert_eq!(evaluate("2+"), None);
+        assert_eq!(evaluate("(2+3"), None);
+        assert_eq!(evaluate("2+3)"), None);
+        assert_eq!(evaluate("1/0"), None);
+        assert_eq!(evaluate("1..2"), None);
+        assert_eq!(evaluate("2x3"), None);
+    }
+
+    #[test]
+    fn trailing() {
+        assert_eq!(trailing_expression("total: 12*7"), Some("12*7"));
+        assert_eq!(trailing_expression("a (1+2)/3"), Some("(1+2)/3"));
+        assert_eq!(trailing_expression("call 555 1234"), None);
+        assert_eq!(trailing_expression("-5"), None);
+        assert_eq!(trailing_expression("x + y"), None);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format(84.), "84");
+        assert_eq!(format(0.1 + 0.2), "0.3");
+        assert_eq!(format(-0.), "0");
+        assert_eq!(format(1. / 3.), "0.3333333333");
+    }
+}
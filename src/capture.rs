@@ -0,0 +1,254 @@
+//! Take screenshots and record the screen through `xdg-desktop-portal`,
+//! so the desktop handles permissions and picking an area, window, or
+//! output. Screenshots are saved by the portal; recordings are made
+//! by a recorder command reading the portal's PipeWire stream.
+
+use std::{
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::CommandExt,
+    },
+    process::{self, Child, Stdio},
+};
+
+use relm4::{
+    Sender,
+    gtk::{
+        gio::{
+            self,
+            prelude::{DBusProxyExt, UnixFDListExtManual},
+        },
+        glib::{self, variant::ToVariant},
+    },
+};
+use serde::Deserialize;
+use tracing::{debug, error};
+
+use crate::{
+    config::Recorder,
+    portal::{self, request},
+    ui::UIMessage,
+};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum CaptureAction {
+    /// Screenshot everything.
+    Screenshot,
+
+    /// Screenshot an area, picked with the portal's dialog.
+    Area,
+
+    /// Start or stop recording the screen.
+    Record,
+}
+const SCREENSHOT: &str = "org.freedesktop.portal.Screenshot";
+const SCREENCAST: &str = "org.freedesktop.portal.ScreenCast";
+const SESSION: &str = "org.freedesktop.portal.Session";
+
+/// Source types to offer for recording: monitors and windows.
+const SOURCE_TYPES: u32 = 1 | 2;
+
+/// The file descriptor the recorder gets the PipeWire remote on.
+const RECORDER_FD: RawFd = 3;
+
+/// Take a screenshot, of an area picked by the user if
+/// `interactive`, sending [`UIMessage::Captured`] with
+/// where it was saved (or nothing if it was cancelled).
+pub fn screenshot(interactive: bool, sender: Sender<UIMessage>) -> Result<(), glib::Error> {
+    let portal = portal::proxy(SCREENSHOT)?;
+    let options = glib::VariantDict::new(None);
+    options.insert_value("modal", &true.to_variant());
+    options.insert_value("interactive", &interactive.to_variant());
+    request(
+        &portal,
+        "Screenshot",
+        vec!["".to_variant()],
+        options,
+        move |results| {
+            let uri = results
+                .and_then(|results| results.lookup_value("uri", None))
+                .and_then(|uri| uri.str().map(str::to_string));
+            debug!("[Capture] Screenshot: {:?}", uri);
+            sender.emit(UIMessage::Captured(uri));
+        },
+    )
+}
+
+/// A recording underway.
+#[derive(Debug)]
+pub struct Recording {
+    session: String,
+    recorder: Child,
+}
+impl Recording {
+    /// Stop recording, letting the recorder finish up.
+    pub fn stop(mut self) {
+        // SAFETY: Sending a signal has no memory safety concerns;
+        // at worst the process has already exited.
+        unsafe { libc::kill(self.recorder.id() as libc::pid_t, libc::SIGINT) };
+        std::thread::spawn(move || self.recorder.wait());
+
+        let session = gio::DBusProxy::for_bus_sync(
+            gio::BusType::Session,
+            gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+            None,
+            portal::PORTAL,
+            &self.session,
+            SESSION,
+            gio::Cancellable::NONE,
+        );
+        let closed = session.and_then(|session| {
+            session.call_sync(
+                "Close",
+                None,
+                gio::DBusCallFlags::NONE,
+                -1,
+                gio::Cancellable::NONE,
+            )
+        });
+        if let Err(err) = closed {
+            error!("Failed to close the screencast session: {}", err);
+        }
+    }
+}
+
+/// Start recording: the user picks what to record, then the
+/// recorder is started on its stream. Sends [`UIMessage::Recording`]
+/// once it's underway, or [`UIMessage::CommandFailed`] if it couldn't be.
+pub fn record(recorder: &Recorder, sender: Sender<UIMessage>) -> Result<(), glib::Error> {
+    let portal = portal::proxy(SCREENCAST)?;
+    let options = glib::VariantDict::new(None);
+    options.insert_value("session_handle_token", &"kway_screencast".to_variant());
+    let portal_cb = portal.clone();
+    let recorder = recorder.clone();
+    request(&portal, "CreateSession", vec![], options, move |results| {
+        let Some(session) = results
+            .and_then(|results| results.lookup_value("session_handle", None))
+            .and_then(|handle| handle.str().map(str::to_string))
+        else {
+            sender.emit(failed(&recorder, "no screencast session".into()));
+            return;
+        };
+        if let Err(err) = select_sources(portal_cb, session, recorder.clone(), sender.clone()) {
+            sender.emit(failed(&recorder, err.to_string()));
+        }
+    })
+}
+
+fn select_sources(
+    portal: gio::DBusProxy,
+    session: String,
+    recorder: Recorder,
+    sender: Sender<UIMessage>,
+) -> Result<(), glib::Error> {
+    let options = glib::VariantDict::new(None);
+    options.insert_value("types", &SOURCE_TYPES.to_variant());
+    let args = vec![portal::object_path(&session)?];
+    let portal_cb = portal.clone();
+    request(&portal, "SelectSources", args, options, move |results| {
+        if results.is_none() {
+            sender.emit(UIMessage::Toast("Recording cancelled".into()));
+            return;
+        }
+        if let Err(err) = start(portal_cb, session, recorder.clone(), sender.clone()) {
+            sender.emit(failed(&recorder, err.to_string()));
+        }
+    })
+}
+
+fn start(
+    portal: gio::DBusProxy,
+    session: String,
+    recorder: Recorder,
+    sender: Sender<UIMessage>,
+) -> Result<(), glib::Error> {
+    let args = vec![portal::object_path(&session)?, "".to_variant()];
+    let portal_cb = portal.clone();
+    let options = glib::VariantDict::new(None);
+    request(&portal, "Start", args, options, move |results| {
+        // Streams are `a(ua{sv})`: PipeWire node ids with properties.
+        let node = results
+            .and_then(|results| results.lookup_value("streams", None))
+            .filter(|streams| streams.n_children() > 0)
+            .and_then(|streams| streams.child_value(0).try_child_value(0))
+            .and_then(|node| node.get::<u32>());
+        let Some(node) = node else {
+            sender.emit(UIMessage::Toast("Recording cancelled".into()));
+            return;
+        };
+        match spawn_recorder(&portal_cb, &session, node, &recorder) {
+            Ok(child) => sender.emit(UIMessage::Recording(Recording {
+                session: session.clone(),
+                recorder: child,
+            })),
+            Err(err) => sender.emit(failed(&recorder, err.to_string())),
+        }
+    })
+}
+
+/// Start the recorder with the PipeWire remote on [`RECORDER_FD`]
+/// and `{node}` in its arguments replaced with the stream's node.
+fn spawn_recorder(
+    portal: &gio::DBusProxy,
+    session: &str,
+    node: u32,
+    recorder: &Recorder,
+) -> Result<Child, glib::Error> {
+    let args = glib::Variant::tuple_from_iter([
+        portal::object_path(session)?,
+        glib::VariantDict::new(None).end(),
+    ]);
+    let (reply, fds) = portal.call_with_unix_fd_list_sync(
+        "OpenPipeWireRemote",
+        Some(&args),
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::UnixFDList::NONE,
+        gio::Cancellable::NONE,
+    )?;
+    let index = reply
+        .try_child_value(0)
+        .and_then(|handle| handle.get::<glib::variant::Handle>())
+        .map(|handle| handle.0)
+        .unwrap_or_default();
+    let fd = fds
+        .ok_or_else(|| glib::Error::new(gio::IOErrorEnum::NotFound, "no PipeWire remote"))?
+        .get(index)?;
+    // SAFETY: The list hands out a duplicate that's ours to close.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let args = recorder
+        .args
+        .iter()
+        .map(|arg| arg.replace("{node}", &node.to_string()));
+    let mut command = process::Command::new(&recorder.cmd);
+    command.args(args).stdin(Stdio::null());
+    // Our copy is closed once it's been passed on.
+    let remote = fd.as_raw_fd();
+    // SAFETY: Only async-signal-safe calls are made between
+    // forking and exec, and the fd is open until spawning.
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 onto the same fd leaves it close-on-exec,
+            // so that has to be cleared instead.
+            let result = if remote == RECORDER_FD {
+                libc::fcntl(remote, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(remote, RECORDER_FD)
+            };
+            if result < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let child = command
+        .spawn()
+        .map_err(|err| glib::Error::new(gio::IOErrorEnum::Failed, &err.to_string()));
+    drop(fd);
+    child
+}
+
+fn failed(recorder: &Recorder, reason: String) -> UIMessage {
+    UIMessage::CommandFailed(recorder.cmd.clone(), reason)
+}
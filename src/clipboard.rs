@@ -0,0 +1,245 @@
+//! Watch the clipboard's text for paste keys,
+//! via the wlr data control protocol.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    os::fd::AsFd,
+};
+
+use relm4::Sender;
+use tracing::{debug, error};
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle,
+    backend::ObjectId,
+    event_created_child,
+    protocol::{wl_registry, wl_seat::WlSeat},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+};
+
+use crate::ui::UIMessage;
+
+/// Text types, most preferred first.
+const TEXT_TYPES: &[&str] = &[
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "STRING",
+    "TEXT",
+];
+
+/// Set by password managers on offers that shouldn't be shown.
+const PASSWORD_HINT: &str = "x-kde-passwordManagerHint";
+
+/// Clipboards larger than this (bytes) aren't offered for pasting.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+struct State {
+    seat: Option<WlSeat>,
+    manager: Option<ZwlrDataControlManagerV1>,
+
+    /// The types each offer has, as they're announced.
+    offers: HashMap<ObjectId, Vec<String>>,
+    sender: Sender<UIMessage>,
+}
+
+/// Send [`UIMessage::Clipboard`] whenever the clipboard changes,
+/// from a background thread. Does nothing if the compositor
+/// doesn't support the protocol.
+pub fn watch(sender: Sender<UIMessage>) {
+    std::thread::spawn(move || {
+        let conn = match Connection::connect_to_env() {
+            Ok(conn) => conn,
+            Err(err) => {
+                error!("Failed to connect to Wayland: {}", err);
+                return;
+            }
+        };
+        let mut event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+        conn.display().get_registry(&qh, ());
+
+        let mut state = State {
+            seat: None,
+            manager: None,
+            offers: HashMap::default(),
+            sender,
+        };
+        if let Err(err) = event_queue.roundtrip(&mut state) {
+            error!("Failed to get Wayland globals: {}", err);
+            return;
+        }
+        let (Some(manager), Some(seat)) = (&state.manager, &state.seat) else {
+            debug!("[Clipboard] Data control isn't supported");
+            return;
+        };
+        manager.get_data_device(seat, &qh, ());
+
+        loop {
+            if let Err(err) = event_queue.blocking_dispatch(&mut state) {
+                error!("Failed to watch the clipboard: {}", err);
+                return;
+            }
+        }
+    });
+}
+
+/// Read the text sent through the pipe, unless there's too much.
+fn read_text(mut reader: impl Read) -> io::Result<Option<String>> {
+    let mut bytes = vec![];
+    reader.by_ref().take(MAX_SIZE + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_SIZE {
+        debug!("[Clipboard] Too large to offer");
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<State>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            if interface == ZwlrDataControlManagerV1::interface().name {
+                let manager =
+                    registry.bind::<ZwlrDataControlManagerV1, _, _>(name, version.min(2), qh, ());
+                state.manager = Some(manager);
+            } else if interface == WlSeat::interface().name && state.seat.is_none() {
+                let seat = registry.bind::<WlSeat, _, _>(name, version.min(1), qh, ());
+                state.seat = Some(seat);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrDataControlManagerV1,
+        _: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        conn: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        use zwlr_data_control_device_v1::Event;
+
+        match event {
+            Event::DataOffer { id } => {
+                state.offers.insert(id.id(), vec![]);
+            }
+            Event::Selection { id } => {
+                let Some(offer) = id else {
+                    debug!("[Clipboard] Cleared");
+                    state.sender.emit(UIMessage::Clipboard(None));
+                    return;
+                };
+                let types = state.offers.remove(&offer.id()).unwrap_or_default();
+                if types.iter().any(|t| t == PASSWORD_HINT) {
+                    debug!("[Clipboard] Skipping a password");
+                    state.sender.emit(UIMessage::Clipboard(None));
+                    offer.destroy();
+                    return;
+                }
+                let text_type = TEXT_TYPES
+                    .iter()
+                    .find(|text_type| types.iter().any(|t| t == *text_type));
+                let Some(text_type) = text_type else {
+                    debug!("[Clipboard] Not text: {:?}", types);
+                    state.sender.emit(UIMessage::Clipboard(None));
+                    offer.destroy();
+                    return;
+                };
+                let (reader, writer) = match io::pipe() {
+                    Ok(pipe) => pipe,
+                    Err(err) => {
+                        error!("Failed to create a pipe for the clipboard: {}", err);
+                        return;
+                    }
+                };
+                offer.receive(text_type.to_string(), writer.as_fd());
+                // Our end has to be closed for reading to finish.
+                drop(writer);
+                if let Err(err) = conn.flush() {
+                    error!("Failed to request the clipboard: {}", err);
+                }
+                offer.destroy();
+
+                // The other client may be slow to send it.
+                let sender = state.sender.clone();
+                std::thread::spawn(move || match read_text(reader) {
+                    Ok(text) => sender.emit(UIMessage::Clipboard(text)),
+                    Err(err) => error!("Failed to read the clipboard: {}", err),
+                });
+            }
+            // Only the clipboard itself is pasted.
+            Event::PrimarySelection { id: Some(offer) } => {
+                state.offers.remove(&offer.id());
+                offer.destroy();
+            }
+            Event::Finished => {
+                debug!("[Clipboard] Data control device finished");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwlrDataControlOfferV1,
+        event: zwlr_data_control_offer_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event else {
+            return;
+        };
+        if let Some(types) = state.offers.get_mut(&offer.id()) {
+            types.push(mime_type);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Completions for URLs and file paths, for the suggestion bar.
+//! URLs come from user-supplied lists, which can be plain text
+//! (one per line), a Chromium-style `Bookmarks` file, or a
+//! Firefox (`places.sqlite`) or Chromium (`History`) history.
+
+use std::{
+    collections::HashSet,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use rusqlite::{Connection, OpenFlags};
+use tracing::error;
+
+use crate::session::SurroundingText;
+
+/// Shortest text that's completed from the lists,
+/// since shorter prefixes match too much to be useful.
+const MIN_PREFIX: usize = 2;
+
+/// History databases are SQLite, which start with this.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// The most visited URLs, from Firefox's and Chromium's history.
+const FIREFOX_HISTORY: &str =
+    "SELECT url FROM moz_places WHERE hidden = 0 ORDER BY frecency DESC LIMIT ?1";
+const CHROMIUM_HISTORY: &str =
+    "SELECT url FROM urls WHERE hidden = 0 ORDER BY visit_count DESC LIMIT ?1";
+
+/// How many URLs are taken from a history.
+const MAX_HISTORY: u32 = 5000;
+
+pub struct Completer {
+    entries: Vec<String>,
+}
+impl Completer {
+    /// Load completions from the files, skipping
+    /// (and logging) any that can't be read.
+    pub fn load(paths: &[PathBuf]) -> Self {
+        let mut seen = HashSet::new();
+        let entries = paths
+            .iter()
+            .flat_map(|path| match load_file(path) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    error!("Failed to load completions from {:?}: {}", path, err);
+                    vec![]
+                }
+            })
+            .filter(|entry| seen.insert(entry.clone()))
+            .collect();
+        Self { entries }
+    }
+
+    /// Completions for the text being typed from the lists,
+    /// best first. Paths are completed with [`complete_path`].
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<String> {
+        if prefix.len() < MIN_PREFIX {
+            return vec![];
+        }
+        self.entries
+            .iter()
+            .filter(|entry| {
+                entry.as_str() != prefix
+                    && (entry.starts_with(prefix) || strip_scheme(entry).starts_with(prefix))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The word before the cursor, i.e. what's being typed.
+pub fn current_word(text: &SurroundingText) -> Option<&str> {
+    let before = text.text.get(..text.cursor as usize)?;
+    let word = before.rsplit(char::is_whitespace).next()?;
+    (!word.is_empty()).then_some(word)
+}
+
+/// Whether the text being typed is a path (starting with `/` or `~/`),
+/// which is completed from the filesystem rather than the lists.
+pub fn is_path(prefix: &str) -> bool {
+    prefix.starts_with('/') || prefix.starts_with("~/")
+}
+
+fn load_file(path: &Path) -> Result<Vec<String>> {
+    let mut header = [0; SQLITE_HEADER.len()];
+    let is_sqlite = fs_err::File::open(path)?
+        .read_exact(&mut header)
+        .is_ok_and(|()| header == SQLITE_HEADER);
+    if is_sqlite {
+        history(path)
+    } else {
+        Ok(parse(&fs_err::read_to_string(path)?))
+    }
+}
+
+/// The most visited URLs from a browser's history. It's read
+/// from a copy, as browsers keep it locked while they run.
+fn history(path: &Path) -> Result<Vec<String>> {
+    let copy = tempfile::NamedTempFile::new()?;
+    fs_err::copy(path, copy.path())?;
+    let db = Connection::open_with_flags(copy.path(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let is_firefox = db
+        .prepare("SELECT 1 FROM sqlite_master WHERE name = 'moz_places'")?
+        .exists([])?;
+    let query = if is_firefox {
+        FIREFOX_HISTORY
+    } else {
+        CHROMIUM_HISTORY
+    };
+    let urls = db
+        .prepare(query)?
+        .query_map([MAX_HISTORY], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    Ok(urls)
+}
+
+fn parse(text: &str) -> Vec<String> {
+    if text.trim_start().starts_with('{') {
+        // JSON is also YAML, so the bookmarks
+        // can be read without another parser.
+        match serde_yaml::from_str(text) {
+            Ok(value) => {
+                let mut urls = vec![];
+                bookmark_urls(&value, &mut urls);
+                urls
+            }
+            Err(err) => {
+                error!("Failed to parse bookmarks: {}", err);
+                vec![]
+            }
+        }
+    } else {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Collect the `url` fields anywhere in a bookmarks tree.
+fn bookmark_urls(value: &serde_yaml::Value, urls: &mut Vec<String>) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value.as_str()) {
+                    (Some("url"), Some(url)) => urls.push(url.to_string()),
+                    _ => bookmark_urls(value, urls),
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(items) => {
+            for item in items {
+                bookmark_urls(item, urls);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// So that e.g. "git" matches "https://www.github.com".
+fn strip_scheme(url: &str) -> &str {
+    let url = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    url.strip_prefix("www.").unwrap_or(url)
+}
+
+/// Entries of the path's directory that start with its last part.
+/// Directories end with `/` so they can be completed into.
+/// This reads the directory, so it should be called in another thread.
+pub fn complete_path(prefix: &str, limit: usize) -> Vec<String> {
+    let Some(split) = prefix.rfind('/') else {
+        return vec![];
+    };
+    let (dir, name) = prefix.split_at(split + 1);
+    let path = match dir.strip_prefix("~/") {
+        Some(rest) => match std::env::var_os("HOME") {
+            Some(home) => Path::new(&home).join(rest),
+            None => return vec![],
+        },
+        None => PathBuf::from(dir),
+    };
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return vec![];
+    };
+
+    let mut paths: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            // Only show hidden files if asked for.
+            if !file_name.starts_with(name) || (name.is_empty() && file_name.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            Some(format!(
+                "{}{}{}",
+                dir,
+                file_name,
+                if is_dir { "/" } else { "" }
+            ))
+        })
+        .collect();
+    paths.sort();
+    paths.truncate(limit);
+    paths
+}
@@ -0,0 +1,164 @@
+//! Trigger compositor actions, e.g. opening the overview
+//! or managing windows.
+//!
+//! If the compositor has an IPC tool it's used directly,
+//! otherwise we fall back to the (GNOME) default shortcut
+//! where there is one.
+
+use serde::Deserialize;
+
+use crate::layout::Modifier;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum CompositorAction {
+    /// Open the overview (e.g. GNOME's Activities).
+    Overview,
+
+    /// Switch to the next workspace.
+    NextWorkspace,
+
+    /// Switch to the previous workspace.
+    PrevWorkspace,
+
+    /// Switch to the numbered workspace.
+    Workspace(u32),
+
+    /// Focus the neighboring window.
+    FocusWindow(WindowDirection),
+
+    /// Move the focused window.
+    MoveWindow(WindowDirection),
+
+    /// Toggle fullscreen for the focused window.
+    Fullscreen,
+}
+impl CompositorAction {
+    /// The shortcut to send if the compositor
+    /// can't be controlled directly.
+    pub fn shortcut(&self) -> Option<(Vec<Modifier>, evdev::Key)> {
+        match self {
+            Self::Overview => Some((vec![], evdev::Key::KEY_LEFTMETA)),
+            Self::NextWorkspace => Some((vec![Modifier::Meta], evdev::Key::KEY_PAGEDOWN)),
+            Self::PrevWorkspace => Some((vec![Modifier::Meta], evdev::Key::KEY_PAGEUP)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum WindowDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+impl WindowDirection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compositor {
+    Sway,
+    Hyprland,
+    Kde,
+    Other,
+}
+impl Compositor {
+    /// Detect the running compositor from the environment.
+    pub fn detect() -> Self {
+        if std::env::var_os("SWAYSOCK").is_some() {
+            Self::Sway
+        } else if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            Self::Hyprland
+        } else if std::env::var_os("KDE_FULL_SESSION").is_some() {
+            Self::Kde
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Whether the compositor is controlled over IPC,
+    /// in which case GNOME's shortcuts don't apply.
+    pub fn has_ipc(&self) -> bool {
+        matches!(self, Self::Sway | Self::Hyprland)
+    }
+
+    /// The IPC command that performs the action,
+    /// if the compositor supports it.
+    pub fn command(&self, action: CompositorAction) -> Option<(String, Vec<String>)> {
+        let (cmd, args) = match self {
+            Self::Sway => ("swaymsg", sway_args(action)?),
+            Self::Hyprland => ("hyprctl", hyprland_args(action)?),
+            Self::Kde | Self::Other => return None,
+        };
+        Some((cmd.to_string(), args))
+    }
+
+    /// IPC commands that blur what's behind
+    /// the layer surfaces with the given namespace.
+    /// Sway itself doesn't blur, but SwayFX does.
+    pub fn blur_commands(&self, namespace: &str) -> Vec<(String, Vec<String>)> {
+        match self {
+            Self::Sway => vec![(
+                "swaymsg".into(),
+                vec![
+                    "layer_effects".into(),
+                    namespace.into(),
+                    // Quoted, as sway would otherwise take
+                    // the `;` as the end of the command.
+                    "'blur enable; blur_ignore_transparent enable'".into(),
+                ],
+            )],
+            Self::Hyprland => ["blur", "ignorezero"]
+                .into_iter()
+                .map(|rule| {
+                    let args = vec![
+                        "keyword".into(),
+                        "layerrule".into(),
+                        format!("{},{}", rule, namespace),
+                    ];
+                    ("hyprctl".into(), args)
+                })
+                .collect(),
+            // KWin is asked through its blur protocol instead.
+            Self::Kde | Self::Other => vec![],
+        }
+    }
+}
+
+fn sway_args(action: CompositorAction) -> Option<Vec<String>> {
+    let args = match action {
+        CompositorAction::Overview => return None,
+        CompositorAction::NextWorkspace => vec!["workspace".into(), "next_on_output".into()],
+        CompositorAction::PrevWorkspace => vec!["workspace".into(), "prev_on_output".into()],
+        CompositorAction::Workspace(n) => {
+            vec!["workspace".into(), "number".into(), n.to_string()]
+        }
+        CompositorAction::FocusWindow(dir) => vec!["focus".into(), dir.name().into()],
+        CompositorAction::MoveWindow(dir) => vec!["move".into(), dir.name().into()],
+        CompositorAction::Fullscreen => vec!["fullscreen".into(), "toggle".into()],
+    };
+    Some(args)
+}
+
+fn hyprland_args(action: CompositorAction) -> Option<Vec<String>> {
+    // Hyprland abbreviates directions to their first letter.
+    let dir = |dir: WindowDirection| dir.name()[..1].to_string();
+    let dispatch = match action {
+        CompositorAction::Overview => return None,
+        CompositorAction::NextWorkspace => vec!["workspace".into(), "e+1".into()],
+        CompositorAction::PrevWorkspace => vec!["workspace".into(), "e-1".into()],
+        CompositorAction::Workspace(n) => vec!["workspace".into(), n.to_string()],
+        CompositorAction::FocusWindow(d) => vec!["movefocus".into(), dir(d)],
+        CompositorAction::MoveWindow(d) => vec!["movewindow".into(), dir(d)],
+        CompositorAction::Fullscreen => vec!["fullscreen".into()],
+    };
+    Some(std::iter::once("dispatch".into()).chain(dispatch).collect())
+}
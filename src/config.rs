@@ -0,0 +1,889 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{handwriting::RecognizerDef, layout::CommandInsert, transliterate::Transliteration};
+
+/// Runtime settings, which (unlike the layout)
+/// can be changed from the keyboard itself.
+/// Those changes are saved with the [`crate::state::State`],
+/// so that the config file is only ever written by hand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The color theme.
+    pub theme: Theme,
+
+    /// Scale applied to key sizes.
+    pub scale: f32,
+
+    /// Key label font size (px), independent of the key size.
+    /// If not set the theme's size is used.
+    pub label_size: Option<u32>,
+
+    /// Key label font family, e.g. "Noto Sans".
+    pub label_font: Option<String>,
+
+    /// Key label font weight, from 100 (thin) to 900 (black).
+    pub label_weight: Option<u16>,
+
+    /// If set, keys are sized to this physical size (in mm),
+    /// so that they're consistent across outputs.
+    /// The `scale` is applied on top of this.
+    pub key_size_mm: Option<f32>,
+
+    /// Opacity of the keyboard background, from 0 to 1.
+    /// If not set the theme's background is used.
+    pub background_opacity: Option<f32>,
+
+    /// Ask the compositor to blur what's behind the keyboard.
+    pub blur: bool,
+
+    /// The largest share of the screen height a layer can take.
+    /// Taller layers are split into pages.
+    pub max_height: f32,
+
+    /// How long (ms) a key must be pressed
+    /// to trigger hold-and-repeat.
+    /// Keys can override this in the layout.
+    pub hold_term: u64,
+
+    /// Require leaving a dead zone around a key
+    /// before a swipe registers, to reduce accidental swipes.
+    pub swipe_dead_zone: Option<DeadZone>,
+
+    /// If set, sliding this far (px) off a key
+    /// and releasing cancels the press.
+    pub cancel_distance: Option<f64>,
+
+    /// Show what a swipe will do while swiping, firing it on
+    /// release; returning to the center takes it back.
+    pub swipe_preview: bool,
+
+    /// Release anything held by a key (e.g. a swipe-hold)
+    /// when the keyboard is hidden, rather than leaving it
+    /// held until the key is touched again.
+    pub release_on_hide: bool,
+
+    /// How often (Hz) pointer movement is sent.
+    /// Movement in between is summed.
+    pub pointer_rate: u32,
+
+    /// How far scroll swipes scroll per pixel swiped, in
+    /// high-resolution wheel units (120 is one wheel click).
+    pub scroll_speed: f64,
+
+    /// Natural scrolling: content follows the swipe,
+    /// as on a touchscreen, instead of the wheel direction.
+    pub natural_scroll: bool,
+
+    /// Lock scroll swipes to the axis they started on,
+    /// so that diagonal drift doesn't scroll sideways.
+    pub scroll_axis_lock: bool,
+
+    /// Briefly show a crosshair at the cursor
+    /// while moving it from the mouse layer.
+    pub crosshair: bool,
+
+    /// Stylus mode: smaller keys, and
+    /// pen presses don't trigger holds.
+    pub stylus: bool,
+
+    /// In stylus mode, highlight keys while hovered.
+    pub hover_highlight: bool,
+
+    /// Vibrate on key presses, through feedbackd.
+    pub haptics: bool,
+
+    /// Correct a misspelled word when Space is typed after it,
+    /// from the active language's dictionary.
+    pub autocorrect: bool,
+
+    /// Show the left layers on the right and
+    /// vice versa, e.g. for left-handed use.
+    pub swap_halves: bool,
+
+    /// Mirror layers horizontally, so
+    /// each row's keys are reversed.
+    pub mirror: bool,
+
+    /// Dwell typing: if set, resting a mouse or pen on a key
+    /// for this long (ms) activates it.
+    pub dwell: Option<u64>,
+
+    /// What to do while the session is locked.
+    pub on_lock: LockBehavior,
+
+    /// Which layer-shell layer the keyboard is shown on.
+    pub layer: ShellLayer,
+
+    /// What the button that shows the keyboard looks like.
+    pub trigger: TriggerStyle,
+
+    /// When keys fire: as soon as they're touched
+    /// or (the default) when they're released.
+    /// Keys with swipe actions always fire on release.
+    pub activate: Activation,
+
+    /// Without a keymap from the compositor (or `--keymap`),
+    /// guess it from the locale rather than using US.
+    pub locale_keymap: bool,
+
+    /// Reserve the keyboard's height across the whole
+    /// bottom edge while it's shown, so that tiling compositors
+    /// resize windows consistently. Only the halves are drawn.
+    pub reserve_space: bool,
+
+    /// Prefix of the layer-shell namespaces, which compositor
+    /// rules can match. The windows are `<namespace>-left`,
+    /// `<namespace>-right`, and `<namespace>-trigger`.
+    pub namespace: String,
+
+    /// Whether the keyboard can take keyboard focus.
+    pub focus: FocusMode,
+
+    /// Hide the trigger and keyboard while
+    /// a fullscreen window is focused.
+    pub hide_on_fullscreen: bool,
+
+    /// Hide the keyboard when the window that was focused
+    /// as it was shown closes or is minimized, so that it
+    /// doesn't linger once the app being typed in is gone.
+    pub hide_on_close: bool,
+
+    /// Whether to show the keyboard when a text field is focused.
+    pub show_on_focus: ShowOnFocus,
+
+    /// The PIN pad, shown in place of the right half.
+    pub pin_pad: PinPad,
+
+    /// When to save power, by turning off
+    /// animations and word predictions.
+    pub power_saving: PowerSaving,
+
+    /// App ids that still show the trigger when fullscreen.
+    pub fullscreen_allow: Vec<String>,
+
+    /// If set, bind a global shortcut (via the desktop portal)
+    /// to show or hide the keyboard, suggesting this trigger
+    /// (e.g. `LOGO+k`). The desktop may ask to confirm it.
+    pub shortcut: Option<String>,
+
+    /// If set, hide the trigger while typing on a physical
+    /// keyboard, until none has been typed on for this long (ms).
+    pub hide_trigger_typing: Option<u64>,
+
+    /// Hardware keys (e.g. a phone's volume keys) to grab
+    /// for showing the keyboard or the mouse layer.
+    pub hardware_keys: Vec<HardwareKey>,
+
+    /// Files of URLs to complete from, either one per line
+    /// or a Chromium-style `Bookmarks` file.
+    pub completions: Vec<PathBuf>,
+
+    /// Path to the table of characters for Zhuyin syllables
+    /// (see [`crate::zhuyin`] for the format).
+    pub zhuyin_table: Option<PathBuf>,
+
+    /// Also show candidates (e.g. for Zhuyin or handwriting)
+    /// next to the text cursor, like desktop input methods.
+    pub candidate_popup: bool,
+
+    /// The recognizer for the handwriting panel
+    /// (see [`crate::handwriting`] for the protocol).
+    pub handwriting: Option<RecognizerDef>,
+
+    /// A helper that scans a barcode or QR code (e.g. with
+    /// the camera) and prints its text, for the `Scan` key.
+    pub scanner: Option<Scanner>,
+
+    /// A command that records the screen for `Record` keys,
+    /// reading the PipeWire stream passed on fd 3, with
+    /// `{node}` in its arguments replaced with the stream's node.
+    pub recorder: Option<Recorder>,
+
+    /// Allow clients to subscribe to events (e.g. layer changes)
+    /// over the IPC socket, e.g. for status bars.
+    pub events: bool,
+
+    /// Include which keys are pressed in events.
+    /// Off by default as it reveals what's typed.
+    pub event_keys: bool,
+
+    /// Languages to cycle through with the `Language` swipe action.
+    pub languages: Vec<Language>,
+
+    /// The active language, by name.
+    /// If unset, the first language is active.
+    pub language: Option<String>,
+
+    /// The active profile, if any, whose settings apply over
+    /// the rest of the config. It's not saved with the config.
+    #[serde(skip)]
+    pub profile: Option<String>,
+
+    /// Named bundles of settings,
+    /// e.g. for switching between a phone and docked setup.
+    pub profiles: HashMap<String, Profile>,
+
+    /// The settings the active profile replaced, which are
+    /// put back when switching profiles and when saving.
+    #[serde(skip)]
+    replaced: Profile,
+
+    /// The settings as the config file has them,
+    /// to tell which were changed from the keyboard.
+    #[serde(skip)]
+    file_settings: Settings,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            scale: 1.,
+            label_size: None,
+            label_font: None,
+            label_weight: None,
+            key_size_mm: None,
+            background_opacity: None,
+            blur: false,
+            max_height: 0.5,
+            hold_term: 500,
+            swipe_dead_zone: None,
+            cancel_distance: None,
+            swipe_preview: false,
+            release_on_hide: true,
+            pointer_rate: 125,
+            scroll_speed: 4.,
+            natural_scroll: false,
+            scroll_axis_lock: false,
+            crosshair: false,
+            stylus: false,
+            hover_highlight: true,
+            haptics: false,
+            autocorrect: false,
+            swap_halves: false,
+            mirror: false,
+            dwell: None,
+            on_lock: LockBehavior::Hide,
+            layer: ShellLayer::Overlay,
+            trigger: TriggerStyle::Corner,
+            activate: Activation::Release,
+            locale_keymap: false,
+            reserve_space: false,
+            namespace: "kway".into(),
+            focus: FocusMode::None,
+            hide_on_fullscreen: false,
+            hide_on_close: false,
+            show_on_focus: ShowOnFocus::Never,
+            pin_pad: PinPad::default(),
+            power_saving: PowerSaving::default(),
+            hide_trigger_typing: None,
+            hardware_keys: vec![],
+            shortcut: None,
+            fullscreen_allow: vec![],
+            completions: vec![],
+            zhuyin_table: None,
+            candidate_popup: false,
+            handwriting: None,
+            scanner: None,
+            recorder: None,
+            events: false,
+            event_keys: false,
+            languages: vec![],
+            language: None,
+            profile: None,
+            profiles: HashMap::default(),
+            replaced: Profile::default(),
+            file_settings: Settings::default(),
+        }
+    }
+}
+impl Config {
+    /// The smallest and largest allowed scales.
+    pub const SCALE_RANGE: (f32, f32) = (0.5, 2.);
+
+    /// How much the scale changes per step.
+    pub const SCALE_STEP: f32 = 0.1;
+
+    /// Additional scale applied in stylus mode,
+    /// since pens are more precise than fingers.
+    pub const STYLUS_SCALE: f32 = 0.75;
+
+    /// `$XDG_CONFIG_HOME/kway/config.yml`,
+    /// falling back to `~/.config/kway/config.yml`.
+    fn default_path() -> PathBuf {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_else(std::env::temp_dir);
+        dir.join("kway").join("config.yml")
+    }
+
+    /// Load the config at the provided path, or the default path.
+    /// If the file doesn't exist the default config is used.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = path.map_or_else(Self::default_path, Path::to_path_buf);
+        let mut config: Config = match fs_err::read_to_string(&path) {
+            Ok(s) => {
+                serde_yaml::from_str(&s).with_context(|| format!("Invalid config {:?}", path))?
+            }
+            Err(_) => Config::default(),
+        };
+        config.file_settings = config.settings();
+        Ok(config)
+    }
+
+    /// The settings that can be changed from the
+    /// keyboard, without the active profile's.
+    fn settings(&self) -> Settings {
+        let replaced = &self.replaced;
+        Settings {
+            theme: Some(replaced.theme.unwrap_or(self.theme)),
+            scale: Some(replaced.scale.unwrap_or(self.scale)),
+            stylus: Some(replaced.stylus.unwrap_or(self.stylus)),
+            haptics: Some(replaced.haptics.unwrap_or(self.haptics)),
+            autocorrect: Some(replaced.autocorrect.unwrap_or(self.autocorrect)),
+            swap_halves: Some(self.swap_halves),
+            mirror: Some(self.mirror),
+        }
+    }
+
+    /// The settings changed from the keyboard since the config
+    /// was loaded, to be saved with the state. Changes to
+    /// settings the active profile overrides aren't included.
+    pub fn changed_settings(&self) -> Settings {
+        fn changed<T: PartialEq>(now: Option<T>, file: Option<T>) -> Option<T> {
+            if now != file { now } else { None }
+        }
+        let (now, file) = (self.settings(), self.file_settings.clone());
+        Settings {
+            theme: changed(now.theme, file.theme),
+            scale: changed(now.scale, file.scale),
+            stylus: changed(now.stylus, file.stylus),
+            haptics: changed(now.haptics, file.haptics),
+            autocorrect: changed(now.autocorrect, file.autocorrect),
+            swap_halves: changed(now.swap_halves, file.swap_halves),
+            mirror: changed(now.mirror, file.mirror),
+        }
+    }
+
+    /// Apply settings saved from [`Self::changed_settings`].
+    /// This has to happen before any profile is used.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        if let Some(theme) = settings.theme {
+            self.theme = theme;
+        }
+        if let Some(scale) = settings.scale {
+            self.scale = scale;
+        }
+        if let Some(stylus) = settings.stylus {
+            self.stylus = stylus;
+        }
+        if let Some(haptics) = settings.haptics {
+            self.haptics = haptics;
+        }
+        if let Some(autocorrect) = settings.autocorrect {
+            self.autocorrect = autocorrect;
+        }
+        if let Some(swap_halves) = settings.swap_halves {
+            self.swap_halves = swap_halves;
+        }
+        if let Some(mirror) = settings.mirror {
+            self.mirror = mirror;
+        }
+    }
+
+    /// Switch to the named profile, applying its settings over
+    /// the rest of the config (rather than the last profile's).
+    /// Returns the profile if it exists.
+    pub fn use_profile(&mut self, name: &str) -> Option<Profile> {
+        let profile = self.profiles.get(name)?.clone();
+        self.restore_replaced();
+        if let Some(theme) = profile.theme {
+            self.replaced.theme = Some(std::mem::replace(&mut self.theme, theme));
+        }
+        if let Some(scale) = profile.scale {
+            self.replaced.scale = Some(std::mem::replace(&mut self.scale, scale));
+        }
+        if let Some(stylus) = profile.stylus {
+            self.replaced.stylus = Some(std::mem::replace(&mut self.stylus, stylus));
+        }
+        if let Some(haptics) = profile.haptics {
+            self.replaced.haptics = Some(std::mem::replace(&mut self.haptics, haptics));
+        }
+        if let Some(autocorrect) = profile.autocorrect {
+            self.replaced.autocorrect = Some(std::mem::replace(&mut self.autocorrect, autocorrect));
+        }
+        if let Some(hold_term) = profile.hold_term {
+            self.replaced.hold_term = Some(std::mem::replace(&mut self.hold_term, hold_term));
+        }
+        if let Some(natural_scroll) = profile.natural_scroll {
+            self.replaced.natural_scroll =
+                Some(std::mem::replace(&mut self.natural_scroll, natural_scroll));
+        }
+        self.profile = Some(name.to_string());
+        Some(profile)
+    }
+
+    /// Set the theme. While a profile is active this only lasts
+    /// until switching profiles, as the profile's settings do,
+    /// so it isn't saved as the theme to use without the profile.
+    pub fn set_theme(&mut self, theme: Theme) {
+        if self.profile.is_some() && self.replaced.theme.is_none() {
+            self.replaced.theme = Some(self.theme);
+        }
+        self.theme = theme;
+    }
+
+    /// Put back the settings the active profile replaced.
+    fn restore_replaced(&mut self) {
+        let replaced = std::mem::take(&mut self.replaced);
+        if let Some(theme) = replaced.theme {
+            self.theme = theme;
+        }
+        if let Some(scale) = replaced.scale {
+            self.scale = scale;
+        }
+        if let Some(stylus) = replaced.stylus {
+            self.stylus = stylus;
+        }
+        if let Some(haptics) = replaced.haptics {
+            self.haptics = haptics;
+        }
+        if let Some(autocorrect) = replaced.autocorrect {
+            self.autocorrect = autocorrect;
+        }
+        if let Some(hold_term) = replaced.hold_term {
+            self.hold_term = hold_term;
+        }
+        if let Some(natural_scroll) = replaced.natural_scroll {
+            self.natural_scroll = natural_scroll;
+        }
+    }
+
+    /// The names of the defined profiles, sorted.
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The user's own CSS, kept next to the config.
+    pub fn style_path(&self) -> PathBuf {
+        self.path.with_file_name("style.css")
+    }
+
+    /// The layout for the active profile, if it specifies one.
+    pub fn profile_layout(&self) -> Option<&Path> {
+        let name = self.profile.as_ref()?;
+        self.profiles.get(name)?.layout.as_deref()
+    }
+
+    pub fn active_language(&self) -> Option<&Language> {
+        match &self.language {
+            Some(name) => self.languages.iter().find(|lang| &lang.name == name),
+            None => self.languages.first(),
+        }
+    }
+
+    /// Switch to the named language, if it's configured.
+    pub fn use_language(&mut self, name: &str) -> Option<&Language> {
+        let language = self.languages.iter().find(|lang| lang.name == name)?;
+        self.language = Some(language.name.clone());
+        Some(language)
+    }
+
+    /// Switch to the next language, wrapping around.
+    pub fn next_language(&mut self) -> Option<&Language> {
+        self.step_language(1)
+    }
+
+    /// Switch to the previous language, wrapping around.
+    pub fn previous_language(&mut self) -> Option<&Language> {
+        let len = self.languages.len().max(1);
+        self.step_language(len - 1)
+    }
+
+    /// Switch to the language this many after the active one.
+    fn step_language(&mut self, step: usize) -> Option<&Language> {
+        let current = self
+            .active_language()
+            .and_then(|active| {
+                self.languages
+                    .iter()
+                    .position(|lang| lang.name == active.name)
+            })
+            .unwrap_or(0);
+        let next = self
+            .languages
+            .get((current + step) % self.languages.len().max(1))?;
+        self.language = Some(next.name.clone());
+        Some(next)
+    }
+
+    /// The scale to actually apply to keys.
+    pub fn key_scale(&self) -> f32 {
+        if self.stylus {
+            self.scale * Self::STYLUS_SCALE
+        } else {
+            self.scale
+        }
+    }
+
+    pub fn scale_by(&mut self, steps: i8) {
+        let (min, max) = Self::SCALE_RANGE;
+        let scale = self.scale + steps as f32 * Self::SCALE_STEP;
+        self.scale = scale.clamp(min, max);
+    }
+}
+
+/// Which layer-shell layer the keyboard is shown on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShellLayer {
+    /// Above everything, including fullscreen windows
+    /// and, on some compositors, the lock screen.
+    #[default]
+    Overlay,
+
+    /// Above windows, but below fullscreen windows
+    /// and overlays like the lock screen.
+    Top,
+}
+
+/// Whether the keyboard's surfaces can take keyboard focus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FocusMode {
+    /// Never take focus, so keys always
+    /// type into the focused window.
+    #[default]
+    None,
+
+    /// Take focus when interacted with,
+    /// if the compositor allows it. `Exclusive`
+    /// is taken as this, as holding focus would
+    /// leave keys nothing else to type into.
+    #[serde(alias = "Exclusive")]
+    OnDemand,
+}
+
+/// What to do while the session is locked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LockBehavior {
+    /// Hide the keyboard until unlocked.
+    #[default]
+    Hide,
+
+    /// Keep the keyboard, but disable keys that
+    /// run commands, launch apps, or type secrets.
+    Restrict,
+
+    /// Do nothing.
+    Ignore,
+}
+
+/// When keys fire.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    /// On release, so a press can still be
+    /// canceled by sliding off the key.
+    #[default]
+    Release,
+
+    /// As soon as the key is touched, for immediate feedback.
+    /// Only for keys without swipe actions: those always fire
+    /// on release, since until then a swipe can't be ruled out.
+    Press,
+}
+
+/// Whether focusing a text field shows the keyboard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ShowOnFocus {
+    /// Only show it from the trigger.
+    #[default]
+    Never,
+
+    /// Whenever a text field is focused.
+    Always,
+
+    /// Only if the text field was touched (or tapped with a
+    /// stylus), and hide it if it was clicked with a mouse or
+    /// touchpad. This watches input devices directly, so
+    /// it needs to be in the `input` group.
+    Touch,
+}
+
+/// What the button that shows the keyboard looks like.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TriggerStyle {
+    /// A small button in the bottom right corner, with
+    /// the layout's trigger swipe actions.
+    #[default]
+    Corner,
+
+    /// A thin pill centered along the bottom edge,
+    /// between where the halves go. Tap or swipe
+    /// it up to show the keyboard.
+    Pill,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum DeadZone {
+    /// The key's bounding box.
+    Key,
+
+    /// A radius (px) around the key's center.
+    Radius(f64),
+}
+
+/// Settings that can be changed from the keyboard
+/// (e.g. its settings layer), where set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: Option<Theme>,
+    pub scale: Option<f32>,
+    pub stylus: Option<bool>,
+    pub haptics: Option<bool>,
+    pub autocorrect: Option<bool>,
+    pub swap_halves: Option<bool>,
+    pub mirror: Option<bool>,
+}
+
+/// A profile overrides the layout and settings.
+/// Anything not specified is taken from the config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Path to a layout file.
+    pub layout: Option<PathBuf>,
+    pub theme: Option<Theme>,
+    pub scale: Option<f32>,
+    pub stylus: Option<bool>,
+    pub haptics: Option<bool>,
+    pub autocorrect: Option<bool>,
+    pub hold_term: Option<u64>,
+    pub natural_scroll: Option<bool>,
+}
+
+/// A helper to scan codes with, whose output is inserted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scanner {
+    pub cmd: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// Give up on scanning after this long (ms).
+    /// Longer than for other commands, as it
+    /// takes a while to line up the camera.
+    #[serde(default = "scan_timeout")]
+    pub timeout: u64,
+}
+impl Scanner {
+    pub fn command(&self) -> CommandInsert {
+        CommandInsert {
+            insert: self.cmd.clone(),
+            args: self.args.clone(),
+            label: "Scan".into(),
+            timeout: self.timeout,
+            env: HashMap::new(),
+            cwd: None,
+        }
+    }
+}
+
+/// A command to record the screen with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recorder {
+    pub cmd: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PinPad {
+    /// Show it when a PIN field is focused.
+    pub auto: bool,
+
+    /// Also show it for password fields,
+    /// e.g. where passwords are PINs.
+    pub passwords: bool,
+
+    /// Shuffle the digits each time it's shown, so that
+    /// where presses land doesn't give the PIN away.
+    pub scramble: bool,
+}
+impl Default for PinPad {
+    fn default() -> Self {
+        Self {
+            auto: true,
+            passwords: false,
+            scramble: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareKey {
+    pub key: evdev::Key,
+    pub action: HardwareAction,
+
+    /// Only act on a long press, leaving shorter
+    /// presses to do what they normally would.
+    #[serde(default)]
+    pub hold: bool,
+
+    /// The name of the device to grab the key on (as `evtest`
+    /// lists it). Without one, any device with the key is grabbed
+    /// except for full keyboards, which are left alone.
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HardwareAction {
+    ToggleKeyboard,
+    ToggleMouseLayer,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerSaving {
+    /// At or below this battery percentage,
+    /// while discharging. `None` to never.
+    pub low_battery: Option<u8>,
+
+    /// While the power-saver profile is active.
+    pub power_saver: bool,
+}
+impl Default for PowerSaving {
+    fn default() -> Self {
+        Self {
+            low_battery: Some(20),
+            power_saver: true,
+        }
+    }
+}
+impl PowerSaving {
+    pub fn enabled(&self) -> bool {
+        self.low_battery.is_some() || self.power_saver
+    }
+}
+
+fn scan_timeout() -> u64 {
+    60_000
+}
+
+/// A language, with its own word predictions and, optionally, layout.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Language {
+    /// Shown in the suggestion bar.
+    pub name: String,
+
+    /// Path to a word list to predict words from,
+    /// one per line, most common first.
+    pub dictionary: Option<PathBuf>,
+
+    /// Path to a layout file to switch to.
+    pub layout: Option<PathBuf>,
+
+    /// Transliterate Latin keys into another script,
+    /// with a bundled table (`Greek` or `Cyrillic`)
+    /// or the path to one.
+    pub transliterate: Option<Transliteration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    Dark,
+    Light,
+    HighContrast,
+
+    /// High contrast with extra-large labels.
+    LargePrint,
+}
+impl Theme {
+    /// Cycle to the next theme.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Dark => Self::Light,
+            Self::Light => Self::HighContrast,
+            Self::HighContrast => Self::LargePrint,
+            Self::LargePrint => Self::Dark,
+        }
+    }
+
+    /// Parse a theme name, e.g. `high-contrast`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace(['-', '_'], "").as_str() {
+            "dark" => Some(Self::Dark),
+            "light" => Some(Self::Light),
+            "highcontrast" => Some(Self::HighContrast),
+            "largeprint" => Some(Self::LargePrint),
+            _ => None,
+        }
+    }
+
+    /// The (RGB) colour of the keyboard background.
+    pub fn background(&self) -> (u8, u8, u8) {
+        match self {
+            Self::Light => (255, 255, 255),
+            _ => (0, 0, 0),
+        }
+    }
+
+    /// CSS applied on top of the base styles.
+    pub fn css(&self) -> &'static str {
+        match self {
+            Self::Dark => "",
+            Self::Light => include_str!("../assets/themes/light.css"),
+            Self::HighContrast => include_str!("../assets/themes/high-contrast.css"),
+            Self::LargePrint => concat!(
+                include_str!("../assets/themes/high-contrast.css"),
+                include_str!("../assets/themes/large-print.css"),
+            ),
+        }
+    }
+}
+
+/// A setting that can be changed from the settings layer.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Setting {
+    /// Cycle through themes.
+    Theme,
+
+    /// Increase the key scale.
+    ScaleUp,
+
+    /// Decrease the key scale.
+    ScaleDown,
+
+    /// Toggle stylus mode.
+    Stylus,
+
+    /// Toggle haptic feedback.
+    Haptics,
+
+    /// Toggle autocorrect.
+    Autocorrect,
+
+    /// Toggle swapping the halves.
+    SwapHalves,
+
+    /// Toggle mirroring layers.
+    Mirror,
+
+    /// Open the app launcher.
+    Launcher,
+
+    /// Close the settings layer.
+    Close,
+}
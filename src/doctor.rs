@@ -0,0 +1,135 @@
+//! Check what kway needs from the system, run with `kway doctor`,
+//! so that missing protocols or permissions are easy to spot.
+
+use std::{collections::HashMap, path::Path};
+
+use wayland_client::{Connection, Dispatch, QueueHandle, protocol::wl_registry};
+
+use crate::compositor::Compositor;
+
+/// A protocol kway uses, and what's lost without it.
+struct Protocol {
+    interface: &'static str,
+    name: &'static str,
+    required: bool,
+    missing: &'static str,
+}
+
+const PROTOCOLS: &[Protocol] = &[
+    Protocol {
+        interface: "zwp_virtual_keyboard_manager_v1",
+        name: "virtual keyboard",
+        required: true,
+        missing: "Keys can't be typed. Your compositor needs to support virtual-keyboard-unstable-v1.",
+    },
+    Protocol {
+        interface: "zwlr_layer_shell_v1",
+        name: "layer shell",
+        required: true,
+        missing: "The keyboard can't be placed above other windows. Your compositor needs to support wlr-layer-shell.",
+    },
+    Protocol {
+        interface: "zwp_input_method_manager_v2",
+        name: "input method v2",
+        required: false,
+        missing: "Suggestions, autocorrect, and text keys are disabled; keys still type.",
+    },
+    Protocol {
+        interface: "zwlr_virtual_pointer_manager_v1",
+        name: "virtual pointer",
+        required: false,
+        missing: "Not used by kway (the pointer goes through uinput), but other tools may need it.",
+    },
+    Protocol {
+        interface: "zwlr_foreign_toplevel_manager_v1",
+        name: "foreign toplevel",
+        required: false,
+        missing: "The keyboard can't hide for fullscreen windows.",
+    },
+];
+
+#[derive(Default)]
+struct Globals {
+    /// Interface names and their versions.
+    interfaces: HashMap<String, u32>,
+}
+impl Dispatch<wl_registry::WlRegistry, ()> for Globals {
+    fn event(
+        state: &mut Self,
+        _: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Globals>,
+    ) {
+        if let wl_registry::Event::Global {
+            interface, version, ..
+        } = event
+        {
+            state.interfaces.insert(interface, version);
+        }
+    }
+}
+
+/// Run the checks, printing the results.
+/// Returns whether everything required is available.
+pub fn run() -> bool {
+    let mut ok = true;
+
+    println!("Compositor: {:?}", Compositor::detect());
+    match Connection::connect_to_env() {
+        Ok(conn) => {
+            let mut event_queue = conn.new_event_queue();
+            let qh = event_queue.handle();
+            conn.display().get_registry(&qh, ());
+            let mut globals = Globals::default();
+            if let Err(err) = event_queue.roundtrip(&mut globals) {
+                println!("✗ Failed to list Wayland protocols: {}", err);
+                return false;
+            }
+
+            for protocol in PROTOCOLS {
+                match globals.interfaces.get(protocol.interface) {
+                    Some(version) => println!("✓ {} (v{})", protocol.name, version),
+                    None => {
+                        ok &= !protocol.required;
+                        let mark = if protocol.required { '✗' } else { '!' };
+                        println!("{} {} is missing", mark, protocol.name);
+                        println!("    {}", protocol.missing);
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            ok = false;
+            println!("✗ Couldn't connect to Wayland: {}", err);
+            println!("    Make sure kway is run from within a Wayland session.");
+        }
+    }
+
+    ok &= check_uinput();
+    ok
+}
+
+/// Pointer control needs write access to `/dev/uinput`.
+fn check_uinput() -> bool {
+    let path = Path::new("/dev/uinput");
+    if !path.exists() {
+        println!("✗ {} doesn't exist", path.display());
+        println!("    Load the module with `sudo modprobe uinput`.");
+        return false;
+    }
+    match std::fs::OpenOptions::new().write(true).open(path) {
+        Ok(_) => {
+            println!("✓ {} is writable", path.display());
+            true
+        }
+        Err(err) => {
+            println!("✗ {} isn't writable: {}", path.display(), err);
+            println!(
+                "    Add yourself to the `input` group and add the udev rules (see the readme), then log in again."
+            );
+            false
+        }
+    }
+}
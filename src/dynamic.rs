@@ -0,0 +1,46 @@
+//! Dynamic keys show the output of a script, refreshed
+//! periodically, e.g. a key showing the current volume
+//! that turns it up or down when swiped.
+//!
+//! The script prints a JSON object with the key's label
+//! and, optionally, a command (the program followed by
+//! its arguments) for tapping and for each swipe:
+//!
+//! ```json
+//! {
+//!   "label": "🔊 40%",
+//!   "tap": ["pactl", "set-sink-mute", "@DEFAULT_SINK@", "toggle"],
+//!   "up": ["pactl", "set-sink-volume", "@DEFAULT_SINK@", "+5%"],
+//!   "down": ["pactl", "set-sink-volume", "@DEFAULT_SINK@", "-5%"]
+//! }
+//! ```
+//!
+//! The key is refreshed right after one of its commands runs,
+//! so that the label keeps up.
+
+use serde::Deserialize;
+
+use crate::layout::Command;
+
+/// What a dynamic key currently shows and does.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub label: String,
+    pub tap: Vec<String>,
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+}
+impl State {
+    pub fn parse(output: &str) -> Result<Self, String> {
+        serde_yaml::from_str(output).map_err(|err| err.to_string())
+    }
+
+    /// The command for an action, unless it has none.
+    pub fn command(action: &[String]) -> Option<Command> {
+        let (cmd, args) = action.split_first()?;
+        Some(Command::new(cmd.clone(), args.to_vec()))
+    }
+}
@@ -0,0 +1,238 @@
+//! A stream of events for external consumers, e.g. status bars
+//! showing the active layer. Clients send `subscribe` over the
+//! IPC socket and then receive one JSON object per line:
+//!
+//! - `{"event":"visible","visible":true}`
+//! - `{"event":"layer","left":"Left layer 0","right":"Settings"}`
+//! - `{"event":"modifiers","modifiers":["Ctrl"]}`
+//! - `{"event":"language","language":"de"}`
+//! - `{"event":"key","pressed":true,"key":"KEY_A"}`
+//!
+//! A `status` event with the full [`Status`] is sent on subscribing.
+//!
+//! Which keys are pressed is only included with `event_keys`,
+//! as otherwise anything typed could be read back.
+
+use std::{
+    io::{ErrorKind, Write},
+    os::unix::net::UnixStream,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::layout::Modifier;
+
+/// The reply to `subscribe` when events are turned off.
+pub const EVENTS_OFF: &str = "events are off (set `events: true`)";
+
+/// The keyboard's state, as shown by status bars.
+#[derive(Debug, Default, Serialize)]
+pub struct Status {
+    pub visible: bool,
+    pub left: String,
+    pub right: String,
+    pub modifiers: Vec<String>,
+    pub language: String,
+}
+impl Status {
+    pub fn to_json(&self) -> String {
+        Message::Status(self).to_json()
+    }
+
+    /// Update from an event, which may only have some of the fields.
+    pub fn update(&mut self, event: StatusUpdate) {
+        if let Some(visible) = event.visible {
+            self.visible = visible;
+        }
+        if let Some(left) = event.left {
+            self.left = left;
+        }
+        if let Some(right) = event.right {
+            self.right = right;
+        }
+        if let Some(language) = event.language {
+            self.language = language;
+        }
+        if let Some(modifiers) = event.modifiers {
+            self.modifiers = modifiers;
+        }
+    }
+}
+
+/// The status fields an event has, if any.
+#[derive(Debug, Default, Deserialize)]
+pub struct StatusUpdate {
+    pub visible: Option<bool>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub modifiers: Option<Vec<String>>,
+    pub language: Option<String>,
+}
+
+pub enum Event<'a> {
+    Status(&'a Status),
+    Visible(bool),
+    Layer(&'a str, &'a str),
+    Modifiers(&'a [Modifier]),
+    Language(&'a str),
+    Key(evdev::Key, bool),
+}
+impl Event<'_> {
+    fn to_json(&self, with_keys: bool) -> String {
+        match *self {
+            Self::Status(status) => Message::Status(status),
+            Self::Visible(visible) => Message::Visible { visible },
+            Self::Layer(left, right) => Message::Layer { left, right },
+            Self::Modifiers(modifiers) => Message::Modifiers {
+                modifiers: modifiers
+                    .iter()
+                    .map(|modifier| format!("{:?}", modifier))
+                    .collect(),
+            },
+            Self::Language(language) => Message::Language { language },
+            Self::Key(key, pressed) => Message::Key {
+                pressed,
+                key: with_keys.then(|| format!("{:?}", key)),
+            },
+        }
+        .to_json()
+    }
+}
+
+/// An event as it's sent to subscribers.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Message<'a> {
+    Status(&'a Status),
+    Visible {
+        visible: bool,
+    },
+    Layer {
+        left: &'a str,
+        right: &'a str,
+    },
+    Modifiers {
+        modifiers: Vec<String>,
+    },
+    Language {
+        language: &'a str,
+    },
+    Key {
+        pressed: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+    },
+}
+impl Message<'_> {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Events are valid JSON")
+    }
+}
+
+#[derive(Default)]
+pub struct Broadcaster {
+    subscribers: Vec<UnixStream>,
+}
+impl Broadcaster {
+    pub fn subscribe(&mut self, stream: UnixStream) {
+        // Never block the UI on a subscriber.
+        if stream.set_nonblocking(true).is_ok() {
+            self.subscribers.push(stream);
+        }
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Send an event to every subscriber, dropping those
+    /// that have gone away or aren't keeping up.
+    pub fn send(&mut self, event: Event, with_keys: bool) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let line = format!("{}\n", event.to_json(with_keys));
+        self.subscribers
+            .retain_mut(|stream| match stream.write_all(line.as_bytes()) {
+                Ok(()) => true,
+                Err(err) => {
+                    if err.kind() == ErrorKind::WouldBlock {
+                        debug!("Dropping a subscriber that isn't keeping up");
+                    }
+                    false
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev::Key;
+
+    use super::*;
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            Event::Visible(true).to_json(false),
+            r#"{"event":"visible","visible":true}"#
+        );
+        assert_eq!(
+            Event::Layer("Left \"0\"", "Settings").to_json(false),
+            r#"{"event":"layer","left":"Left \"0\"","right":"Settings"}"#
+        );
+        assert_eq!(
+            Event::Modifiers(&[Modifier::Ctrl, Modifier::Shift]).to_json(false),
+            r#"{"event":"modifiers","modifiers":["Ctrl","Shift"]}"#
+        );
+        assert_eq!(
+            Event::Language("de").to_json(false),
+            r#"{"event":"language","language":"de"}"#
+        );
+    }
+
+    #[test]
+    fn keys_only_with_event_keys() {
+        assert_eq!(
+            Event::Key(Key::KEY_A, true).to_json(true),
+            r#"{"event":"key","pressed":true,"key":"KEY_A"}"#
+        );
+        assert_eq!(
+            Event::Key(Key::KEY_A, false).to_json(false),
+            r#"{"event":"key","pressed":false}"#
+        );
+    }
+
+    #[test]
+    fn status_round_trips() {
+        let status = Status {
+            visible: true,
+            left: "Left layer 0".into(),
+            right: "Right layer 1".into(),
+            modifiers: vec!["Ctrl".into()],
+            language: "de".into(),
+        };
+        let json = Event::Status(&status).to_json(false);
+        assert!(json.starts_with(r#"{"event":"status","visible":true,"#));
+
+        let mut parsed = Status::default();
+        parsed.update(serde_json::from_str(&json).unwrap());
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", status));
+    }
+
+    #[test]
+    fn events_update_only_their_fields() {
+        let mut status = Status {
+            visible: true,
+            language: "de".into(),
+            ..Default::default()
+        };
+        let event = Event::Layer("Left", "Right").to_json(false);
+        status.update(serde_json::from_str(&event).unwrap());
+        assert!(status.visible);
+        assert_eq!(status.left, "Left");
+        assert_eq!(status.right, "Right");
+        assert_eq!(status.language, "de");
+    }
+}
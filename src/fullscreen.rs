@@ -0,0 +1,240 @@
+//! Watch for a fullscreen window being focused (e.g. a video or game),
+//! for the focused window closing or being minimized, and for
+//! which app is focused, via the wlr foreign toplevel protocol.
+
+use std::collections::HashMap;
+
+use relm4::Sender;
+use tracing::{debug, error};
+use wayland_client::{
+    Connection, Dispatch, Proxy, QueueHandle, backend::ObjectId, event_created_child,
+    protocol::wl_registry,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use crate::ui::UIMessage;
+
+#[derive(Default)]
+struct Toplevel {
+    app_id: String,
+    activated: bool,
+    fullscreen: bool,
+    minimized: bool,
+}
+
+struct State {
+    manager: Option<ZwlrForeignToplevelManagerV1>,
+    toplevels: HashMap<ObjectId, Toplevel>,
+
+    /// Pending state, applied on `done`.
+    pending: HashMap<ObjectId, Toplevel>,
+
+    /// The app id of the focused fullscreen window, if any.
+    fullscreen: Option<String>,
+
+    /// The focused window. When no window is activated,
+    /// this is the last one that was, until it's gone.
+    focused: Option<ObjectId>,
+
+    /// The app id of the activated window, if any.
+    app_id: Option<String>,
+    sender: Sender<UIMessage>,
+}
+impl State {
+    /// Changes are sent as a batch ending with `done`,
+    /// so they're collected here and then applied together.
+    fn pending(&mut self, id: &ObjectId) -> &mut Toplevel {
+        let current = self.toplevels.get(id);
+        self.pending.entry(id.clone()).or_insert_with(|| Toplevel {
+            app_id: current.map(|t| t.app_id.clone()).unwrap_or_default(),
+            activated: current.is_some_and(|t| t.activated),
+            fullscreen: current.is_some_and(|t| t.fullscreen),
+            minimized: current.is_some_and(|t| t.minimized),
+        })
+    }
+
+    fn update(&mut self) {
+        // Focus may move on before the window's gone,
+        // e.g. when minimizing, so a departure is
+        // noticed for the last focused window.
+        let gone = self.focused.as_ref().is_some_and(|id| {
+            self.toplevels
+                .get(id)
+                .is_none_or(|toplevel| toplevel.minimized)
+        });
+        if gone {
+            debug!("[Fullscreen] Focused window closed or minimized");
+            if let Some(id) = self.focused.take() {
+                self.sender
+                    .emit(UIMessage::FocusedWindowGone(id.protocol_id()));
+            }
+        }
+        let activated = self
+            .toplevels
+            .iter()
+            .find(|(_, toplevel)| toplevel.activated);
+        if let Some((id, _)) = activated.filter(|(id, _)| self.focused.as_ref() != Some(*id)) {
+            self.focused = Some(id.clone());
+            self.sender.emit(UIMessage::FocusedWindow(id.protocol_id()));
+        }
+        let app_id = activated.map(|(_, toplevel)| toplevel.app_id.clone());
+        if app_id != self.app_id {
+            debug!("[Fullscreen] Focused app: {:?}", app_id);
+            self.app_id = app_id.clone();
+            self.sender.emit(UIMessage::FocusedApp(app_id));
+        }
+
+        let fullscreen = self
+            .toplevels
+            .values()
+            .find(|toplevel| toplevel.activated && toplevel.fullscreen)
+            .map(|toplevel| toplevel.app_id.clone());
+        if fullscreen != self.fullscreen {
+            debug!("[Fullscreen] Focused: {:?}", fullscreen);
+            self.fullscreen = fullscreen.clone();
+            self.sender.emit(UIMessage::Fullscreen(fullscreen));
+        }
+    }
+}
+
+/// Send [`UIMessage::Fullscreen`] whenever a fullscreen
+/// window gains or loses focus, [`UIMessage::FocusedWindow`] and
+/// [`UIMessage::FocusedWindowGone`] as windows are focused and as the
+/// focused window closes or is minimized, and
+/// [`UIMessage::FocusedApp`] as focus moves between apps,
+/// from a background thread.
+/// Does nothing if the compositor doesn't support the protocol.
+pub fn watch(sender: Sender<UIMessage>) {
+    std::thread::spawn(move || {
+        let conn = match Connection::connect_to_env() {
+            Ok(conn) => conn,
+            Err(err) => {
+                error!("Failed to connect to Wayland: {}", err);
+                return;
+            }
+        };
+        let mut event_queue = conn.new_event_queue();
+        let qh = event_queue.handle();
+        conn.display().get_registry(&qh, ());
+
+        let mut state = State {
+            manager: None,
+            toplevels: HashMap::default(),
+            pending: HashMap::default(),
+            fullscreen: None,
+            focused: None,
+            app_id: None,
+            sender,
+        };
+        if let Err(err) = event_queue.roundtrip(&mut state) {
+            error!("Failed to get Wayland globals: {}", err);
+            return;
+        }
+        if state.manager.is_none() {
+            debug!("[Fullscreen] Foreign toplevel management isn't supported");
+            return;
+        }
+
+        loop {
+            if let Err(err) = event_queue.blocking_dispatch(&mut state) {
+                error!("Failed to watch for fullscreen windows: {}", err);
+                return;
+            }
+        }
+    });
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<State>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == ZwlrForeignToplevelManagerV1::interface().name => {
+                let manager = registry.bind::<ZwlrForeignToplevelManagerV1, _, _>(
+                    name,
+                    version.min(3),
+                    qh,
+                    (),
+                );
+                state.manager = Some(manager);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state.pending.insert(toplevel.id(), Toplevel::default());
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        use zwlr_foreign_toplevel_handle_v1::{Event, State as ToplevelState};
+
+        let id = handle.id();
+
+        match event {
+            Event::AppId { app_id } => {
+                state.pending(&id).app_id = app_id;
+            }
+            Event::State { state: states } => {
+                // An array of native-endian u32 state values.
+                let states: Vec<u32> = states
+                    .chunks_exact(4)
+                    .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                let toplevel = state.pending(&id);
+                toplevel.activated = states.contains(&(ToplevelState::Activated as u32));
+                toplevel.fullscreen = states.contains(&(ToplevelState::Fullscreen as u32));
+                toplevel.minimized = states.contains(&(ToplevelState::Minimized as u32));
+            }
+            Event::Done => {
+                if let Some(toplevel) = state.pending.remove(&id) {
+                    state.toplevels.insert(id, toplevel);
+                }
+                state.update();
+            }
+            Event::Closed => {
+                state.pending.remove(&id);
+                state.toplevels.remove(&id);
+                handle.destroy();
+                state.update();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Recognizing handwriting drawn on the handwriting panel.
+//!
+//! Recognizers are pluggable: implement [`Recognizer`] and add
+//! a variant for it to [`RecognizerDef`]. kway comes with one that
+//! runs a command, so that existing recognizers (e.g. zinnia or
+//! tegaki) can be used through a small script. The command is
+//! given the canvas size and then one stroke per line on stdin:
+//!
+//! ```text
+//! 320 240
+//! 10,12 11,15 13,20
+//! 40,12 38,30
+//! ```
+//!
+//! and prints candidates, best first, one per line.
+
+use std::{collections::HashMap, io::Write, process::Stdio, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::ui::commands;
+
+/// The points of a stroke, in order, from
+/// the canvas' top left corner.
+pub type Stroke = Vec<(f64, f64)>;
+
+pub trait Recognizer: Send + Sync {
+    /// What the strokes (drawn on a canvas of the given size)
+    /// could be, best first. This is called off the UI thread,
+    /// so it's fine for it to block.
+    fn recognize(&self, strokes: &[Stroke], size: (f64, f64)) -> Result<Vec<String>>;
+}
+
+/// Which recognizer to use, as set in the config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecognizerDef {
+    /// Run a command.
+    Command {
+        cmd: String,
+        #[serde(default)]
+        args: Vec<String>,
+
+        /// Give up on the command after this long (ms).
+        #[serde(default = "recognize_timeout")]
+        timeout: u64,
+    },
+}
+
+fn recognize_timeout() -> u64 {
+    5000
+}
+impl RecognizerDef {
+    pub fn recognizer(&self) -> Arc<dyn Recognizer> {
+        match self {
+            Self::Command { cmd, args, timeout } => Arc::new(CommandRecognizer {
+                cmd: cmd.clone(),
+                args: args.clone(),
+                timeout: *timeout,
+            }),
+        }
+    }
+
+    /// A name for the recognizer, for error messages.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Command { cmd, .. } => cmd,
+        }
+    }
+}
+
+struct CommandRecognizer {
+    cmd: String,
+    args: Vec<String>,
+    timeout: u64,
+}
+impl Recognizer for CommandRecognizer {
+    fn recognize(&self, strokes: &[Stroke], (width, height): (f64, f64)) -> Result<Vec<String>> {
+        let mut child = commands::build(&self.cmd, &self.args, &HashMap::new(), None)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut input = format!("{} {}\n", width.round(), height.round());
+        for stroke in strokes {
+            let points: Vec<String> = stroke
+                .iter()
+                .map(|(x, y)| format!("{},{}", x.round(), y.round()))
+                .collect();
+            input.push_str(&points.join(" "));
+            input.push('\n');
+        }
+        // Taking stdin closes it once written, so the command
+        // knows there's no more. It's written in another thread,
+        // as a command that doesn't read it would block us
+        // once the pipe is full, past the timeout.
+        if let Some(mut stdin) = child.stdin.take() {
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+        }
+
+        let output = commands::wait_with_timeout(child, self.timeout)
+            .map_err(|reason| anyhow!("{}", reason))?;
+        let candidates = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok(candidates)
+    }
+}
@@ -0,0 +1,44 @@
+//! Haptic feedback for key presses, via feedbackd
+//! (as used on phones, e.g. with Phosh).
+
+use std::collections::HashMap;
+
+use relm4::gtk::{
+    gio::{self, prelude::DBusProxyExt},
+    glib::{self, variant::ToVariant},
+};
+use tracing::debug;
+
+const FEEDBACKD: &str = "org.sigxcpu.Feedback";
+
+/// Connect to feedbackd. This succeeds even if it isn't
+/// running; feedback then just fails (which is logged).
+pub fn connect() -> Result<gio::DBusProxy, glib::Error> {
+    gio::DBusProxy::for_bus_sync(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        FEEDBACKD,
+        "/org/sigxcpu/Feedback",
+        FEEDBACKD,
+        gio::Cancellable::NONE,
+    )
+}
+
+/// Give feedback for a key press, without waiting for it.
+pub fn key_press(feedback: &gio::DBusProxy) {
+    let hints: HashMap<String, glib::Variant> = HashMap::new();
+    let args = ("kway", "button-pressed", hints, -1i32).to_variant();
+    feedback.call(
+        "TriggerFeedback",
+        Some(&args),
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+        |result| {
+            if let Err(err) = result {
+                debug!("[Haptics] Failed to give feedback: {}", err);
+            }
+        },
+    );
+}
@@ -0,0 +1,155 @@
+//! Grab hardware keys (e.g. a phone's volume keys) to show or
+//! hide the keyboard or toggle the mouse layer, for devices
+//! without a convenient place to touch for that.
+//!
+//! This reads the keys' evdev devices directly, so (like the
+//! pointer) it needs to be in the `input` group. A device is
+//! grabbed so that its bound keys don't also do what they normally
+//! would; its other events are passed through a uinput copy of it.
+//! Full keyboards are only grabbed when named, since while they're
+//! grabbed their LEDs don't follow and the typing watch can't see
+//! them.
+
+use std::{
+    collections::HashMap,
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use evdev::{
+    Device, EventType, InputEvent, InputEventKind, Key,
+    uinput::{VirtualDevice, VirtualDeviceBuilder},
+};
+use relm4::Sender;
+use tracing::{debug, error};
+
+use crate::{
+    config::{HardwareAction, HardwareKey},
+    typing::is_keyboard,
+    ui::UIMessage,
+};
+
+/// How long a key bound with `hold` has to be held.
+const LONG_PRESS: Duration = Duration::from_millis(500);
+
+pub fn watch(bindings: Vec<HardwareKey>, sender: Sender<UIMessage>) {
+    let devices: Vec<_> = evdev::enumerate()
+        .map(|(_, device)| {
+            let bindings = device_bindings(&device, &bindings);
+            (device, bindings)
+        })
+        .filter(|(_, bindings)| !bindings.is_empty())
+        .collect();
+    if devices.is_empty() {
+        error!("No devices found with the hardware keys to grab");
+    }
+    for (device, bindings) in devices {
+        let name = device.name().unwrap_or_default().to_string();
+        debug!("[Hardware] Grabbing: {:?}", name);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            if let Err(err) = grab_device(device, &bindings, &sender) {
+                // E.g. the device was unplugged.
+                error!("Stopped grabbing {:?}: {}", name, err);
+            }
+        });
+    }
+}
+
+/// The bindings to grab the device for.
+fn device_bindings(device: &Device, bindings: &[HardwareKey]) -> Vec<HardwareKey> {
+    let Some(keys) = device.supported_keys() else {
+        return vec![];
+    };
+    let name = device.name().unwrap_or_default();
+    let keyboard = is_keyboard(device);
+    bindings
+        .iter()
+        .filter(|binding| keys.contains(binding.key))
+        .filter(|binding| match &binding.device {
+            Some(device) => device == name,
+            None => !keyboard,
+        })
+        .cloned()
+        .collect()
+}
+
+/// A uinput copy of the device, for passing its events through.
+fn passthrough(device: &Device) -> io::Result<VirtualDevice> {
+    let name = format!("kway passthrough ({})", device.name().unwrap_or_default());
+    let mut builder = VirtualDeviceBuilder::new()?.name(&name);
+    if let Some(keys) = device.supported_keys() {
+        builder = builder.with_keys(keys)?;
+    }
+    if let Some(misc) = device.misc_properties() {
+        builder = builder.with_msc(misc)?;
+    }
+    if let Some(switches) = device.supported_switches() {
+        builder = builder.with_switches(switches)?;
+    }
+    if let Some(axes) = device.supported_relative_axes() {
+        builder = builder.with_relative_axes(axes)?;
+    }
+    builder.build()
+}
+
+fn grab_device(
+    mut device: Device,
+    bindings: &[HardwareKey],
+    sender: &Sender<UIMessage>,
+) -> io::Result<()> {
+    let mut passthrough = passthrough(&device)?;
+    device.grab()?;
+
+    // When each bound key (with `hold`) was pressed.
+    let mut held: HashMap<Key, Instant> = HashMap::new();
+    loop {
+        let mut passed = vec![];
+        for event in device.fetch_events()? {
+            // Emitting adds its own sync.
+            if event.event_type() == EventType::SYNCHRONIZATION {
+                continue;
+            }
+            let binding = match event.kind() {
+                InputEventKind::Key(key) => bindings.iter().find(|binding| binding.key == key),
+                _ => None,
+            };
+            let Some(binding) = binding else {
+                passed.push(event);
+                continue;
+            };
+            let key = binding.key;
+            match (event.value(), binding.hold) {
+                (1, false) => sender.emit(message(binding.action)),
+                (1, true) => {
+                    held.insert(key, Instant::now());
+                }
+                (0, true) => match held.remove(&key) {
+                    Some(at) if at.elapsed() >= LONG_PRESS => sender.emit(message(binding.action)),
+
+                    // Short presses do what they normally would.
+                    Some(_) => {
+                        passthrough.emit(&[InputEvent::new(EventType::KEY, key.code(), 1)])?;
+                        passthrough.emit(&[InputEvent::new(EventType::KEY, key.code(), 0)])?;
+                    }
+                    None => {}
+                },
+
+                // Repeats, and releases of keys
+                // that fire as soon as they're pressed.
+                _ => {}
+            }
+        }
+        if !passed.is_empty() {
+            passthrough.emit(&passed)?;
+        }
+    }
+}
+
+fn message(action: HardwareAction) -> UIMessage {
+    debug!("[Hardware] {:?}", action);
+    match action {
+        HardwareAction::ToggleKeyboard => UIMessage::ToggleKeyboard,
+        HardwareAction::ToggleMouseLayer => UIMessage::ToggleMouseLayer,
+    }
+}
@@ -0,0 +1,87 @@
+//! Watch touchscreens and pointing devices to tell how the user
+//! last interacted, so that focusing a text field by touch can
+//! show the keyboard while clicking one with a mouse doesn't.
+//!
+//! Like [`crate::typing`], this reads the devices' evdev
+//! devices directly, so it needs to be in the `input` group.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use evdev::{Device, InputEventKind, Key, PropType};
+use relm4::Sender;
+use tracing::{debug, error};
+
+use crate::ui::UIMessage;
+
+/// Presses closer together than this are only reported once.
+const THROTTLE: Duration = Duration::from_millis(250);
+
+/// The name of our own uinput device (see [`crate::pointer`]),
+/// which moves the pointer on the user's behalf.
+const OWN_POINTER: &str = "kway pointer";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interaction {
+    /// A touchscreen or stylus.
+    Touch,
+
+    /// A mouse or touchpad.
+    Pointer,
+}
+
+pub fn watch(sender: Sender<UIMessage>) {
+    let devices: Vec<_> = evdev::enumerate()
+        .map(|(_, device)| device)
+        .filter_map(|device| kind(&device).map(|kind| (device, kind)))
+        .collect();
+    if devices.is_empty() {
+        error!("No touchscreens or pointing devices found to watch");
+    }
+    for (device, kind) in devices {
+        debug!("[Interaction] Watching {:?}: {:?}", kind, device.name());
+        let sender = sender.clone();
+        thread::spawn(move || watch_device(device, kind, sender));
+    }
+}
+
+/// Touchscreens and tablets map touches directly onto the
+/// screen, unlike mice and touchpads, which move a pointer.
+fn kind(device: &Device) -> Option<Interaction> {
+    if device.name() == Some(OWN_POINTER) {
+        return None;
+    }
+    let keys = device.supported_keys()?;
+    let direct = device.properties().contains(PropType::DIRECT);
+    if direct && (keys.contains(Key::BTN_TOUCH) || keys.contains(Key::BTN_TOOL_PEN)) {
+        Some(Interaction::Touch)
+    } else if !direct && (keys.contains(Key::BTN_LEFT) || keys.contains(Key::BTN_TOUCH)) {
+        Some(Interaction::Pointer)
+    } else {
+        None
+    }
+}
+
+fn watch_device(mut device: Device, kind: Interaction, sender: Sender<UIMessage>) {
+    let name = device.name().unwrap_or_default().to_string();
+    let mut last: Option<Instant> = None;
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(err) => {
+                // E.g. the device was unplugged.
+                debug!("[Interaction] Stopped watching {:?}: {}", name, err);
+                return;
+            }
+        };
+        let pressed = events
+            .into_iter()
+            .any(|event| matches!(event.kind(), InputEventKind::Key(_)) && event.value() == 1);
+        if pressed && last.is_none_or(|last| last.elapsed() > THROTTLE) {
+            last = Some(Instant::now());
+            sender.emit(UIMessage::Interaction(kind));
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Control a running instance over a unix socket.
+//!
+//! Messages are newline-delimited commands, e.g. `profile docked`.
+//! They can be sent with `kway --msg "profile docked"`.
+//!
+//! `geometry` is a query: the response lists the regions
+//! the keyboard occupies, as `<name> <x>,<y> <w>x<h>`.
+//!
+//! `status` is a query for the keyboard's state as JSON, and
+//! `subscribe` keeps the connection open for
+//! a stream of events (see [`crate::events`]).
+
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use relm4::Sender;
+use tracing::{debug, error};
+
+use crate::{
+    config::{Setting, Theme},
+    ui::UIMessage,
+};
+
+/// How long to wait for the UI to answer a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `$XDG_RUNTIME_DIR/kway.sock`. There's no fallback (e.g. to
+/// `/tmp`), as other users could connect to it there and type.
+pub fn socket_path() -> Result<PathBuf> {
+    let dir =
+        std::env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| anyhow!("XDG_RUNTIME_DIR isn't set"))?;
+    Ok(PathBuf::from(dir).join("kway.sock"))
+}
+
+/// Parse a command into a message for the UI.
+fn parse(command: &str) -> Result<UIMessage> {
+    let mut parts = command.split_whitespace();
+    let name = parts.next().ok_or_else(|| anyhow!("Empty command"))?;
+    let arg = parts.next();
+    match (name, arg) {
+        ("show", None) => Ok(UIMessage::ShowKeyboard),
+        ("hide", None) => Ok(UIMessage::HideKeyboard),
+        ("toggle", None) => Ok(UIMessage::ToggleKeyboard),
+        ("quit", None) => Ok(UIMessage::Quit),
+        ("restart", None) => Ok(UIMessage::Restart),
+        ("lock", None) => Ok(UIMessage::TouchLock(true)),
+        ("unlock", None) => Ok(UIMessage::TouchLock(false)),
+        ("profile", Some(name)) => Ok(UIMessage::Profile(name.to_string())),
+        ("toast", Some(_)) => {
+            let text = command.trim_start().trim_start_matches("toast").trim();
+            Ok(UIMessage::Toast(text.to_string()))
+        }
+        ("swap", None) => Ok(UIMessage::Setting(Setting::SwapHalves)),
+        ("mirror", None) => Ok(UIMessage::Setting(Setting::Mirror)),
+        ("theme", Some(name)) => Theme::from_name(name)
+            .map(UIMessage::Theme)
+            .ok_or_else(|| anyhow!("Unknown theme: {}", name)),
+        _ => Err(anyhow!("Unknown command: {}", command)),
+    }
+}
+
+/// Listen for commands in a background thread,
+/// forwarding them to the UI.
+pub fn listen(sender: Sender<UIMessage>) {
+    let path = match socket_path() {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Not listening for commands: {}", err);
+            return;
+        }
+    };
+
+    // Clean up a socket left by a previous instance,
+    // but not one that another instance is listening on.
+    match UnixStream::connect(&path) {
+        Ok(_) => {
+            error!("Another instance is listening on {:?}", path);
+            return;
+        }
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            let _ = std::fs::remove_file(&path);
+        }
+        Err(_) => {}
+    }
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to bind IPC socket: {}", err);
+            return;
+        }
+    };
+
+    // Each connection gets its own thread, so that
+    // one that's left open can't hold up the others.
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = handle(stream, &sender) {
+                    error!("[IPC] {}", err);
+                }
+            });
+        }
+    });
+}
+
+fn handle(stream: UnixStream, sender: &Sender<UIMessage>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        debug!("[IPC] Received: {}", line);
+        if line.trim() == "geometry" {
+            let (reply, response) = mpsc::channel();
+            sender.emit(UIMessage::QueryGeometry(reply));
+            match response.recv_timeout(QUERY_TIMEOUT) {
+                Ok(geometry) => write!(writer, "{}", geometry)?,
+                Err(err) => writeln!(writer, "error: {}", err)?,
+            }
+            continue;
+        }
+        if line.trim() == "status" {
+            let (reply, response) = mpsc::channel();
+            sender.emit(UIMessage::QueryStatus(reply));
+            match response.recv_timeout(QUERY_TIMEOUT) {
+                Ok(status) => writeln!(writer, "{}", status)?,
+                Err(err) => writeln!(writer, "error: {}", err)?,
+            }
+            continue;
+        }
+        if line.trim() == "subscribe" {
+            sender.emit(UIMessage::Subscribe(writer));
+            return Ok(());
+        }
+        match parse(&line) {
+            Ok(msg) => {
+                sender.emit(msg);
+                writeln!(writer, "ok")?;
+            }
+            Err(err) => writeln!(writer, "error: {}", err)?,
+        }
+    }
+    Ok(())
+}
+
+/// Send a command to a running instance,
+/// returning its response.
+pub fn send(command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(socket_path()?)?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+pub fn cleanup() {
+    if let Ok(path) = socket_path() {
+        let _ = std::fs::remove_file(path);
+    }
+}
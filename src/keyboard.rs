@@ -1,11 +1,29 @@
-use tracing::debug;
-use wayland_client::{Connection, EventQueue, protocol::wl_keyboard::KeyState};
+use std::{
+    collections::{HashMap, HashSet},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::UnixStream,
+    },
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, error, warn};
+use wayland_client::{Connection, DispatchError, EventQueue, protocol::wl_keyboard::KeyState};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
 
 use crate::{
-    layout::{Layer, Layout, Side, TriggerKey},
-    session::SessionState,
+    layout::{Gesture, Layer, Layout, Modifier, Side, TriggerKey},
+    popup::CandidatePopup,
+    secret::Secret,
+    session::{PURPOSE_PASSWORD, PURPOSE_PIN, SessionState, SurroundingText, keymap},
+    transform::Transform,
 };
 
+/// How long to wait for the text field to
+/// send its state after keys were sent.
+const DONE_TIMEOUT: Duration = Duration::from_millis(100);
+
 pub enum KeyType {
     Mod,
     Lock,
@@ -40,6 +58,39 @@ pub enum KeyMessage {
     LockRelease(u16),
     Layer(Side, usize),
     MouseLayer(bool),
+    SettingsLayer(bool),
+
+    /// Remember the current selection as deleted text,
+    /// so the deletion can be undone.
+    RememberDeleted,
+
+    /// Re-insert the last deleted text.
+    UndoDelete,
+
+    /// Insert text via the input method.
+    Commit(String),
+
+    /// Show text being composed, via the input method.
+    /// It's replaced by the next commit.
+    Preedit(String),
+
+    /// Replace this many bytes before the cursor
+    /// with text, via the input method.
+    ReplaceBefore(u32, String),
+
+    /// Type a secret with key presses.
+    TypeSecret(Secret),
+
+    /// Replace the selected text with a transformed version.
+    Transform(Transform),
+
+    /// Turn selection mode on or off, in which Shift
+    /// is held so that moving the cursor selects.
+    Selecting(bool),
+
+    /// Send key events to another Wayland display
+    /// (e.g. a nested compositor), or back to ours.
+    Display(Option<String>),
 }
 
 pub struct Keyboard {
@@ -49,10 +100,55 @@ pub struct Keyboard {
     pub layer: (usize, usize),
     modifiers: u32,
     locks: u32,
+
+    /// Text removed by the last delete swipe.
+    deleted: Option<String>,
+
+    /// The input method serial when a key was last sent,
+    /// to tell when the text field has caught up with it.
+    key_serial: u32,
+
+    /// Keys currently pressed, so that presses
+    /// and releases are always sent in order.
+    pressed: HashSet<evdev::Key>,
+
+    /// Timestamp of the last key event.
+    last_time: u32,
+
+    /// Whether the keyboard is hidden, in which case
+    /// the input method is released.
+    idle: bool,
+
+    /// Keep the input method while idle, so that
+    /// focusing a text field can show the keyboard.
+    watch_focus: bool,
+
+    /// Release held keys and modifiers when going idle,
+    /// rather than keeping them for when it's shown again.
+    release_on_hide: bool,
+
+    /// Whether Shift is held for selection mode,
+    /// which releasing Shift otherwise doesn't end.
+    selecting: bool,
+
+    /// Shows candidates next to the text cursor,
+    /// for the input method it was created with.
+    popup: Option<CandidatePopup>,
+
+    /// Virtual keyboards on other displays, by display name,
+    /// and the one key events currently go to, if any.
+    displays: HashMap<String, (SessionState, EventQueue<SessionState>)>,
+    display: Option<String>,
 }
 impl Keyboard {
     pub fn new(layout: Layout) -> Self {
         let conn = Connection::connect_to_env().unwrap();
+        Self::with_connection(layout, conn)
+    }
+
+    /// A keyboard on an already open connection,
+    /// e.g. to a mock compositor for benchmarking.
+    pub fn with_connection(layout: Layout, conn: Connection) -> Self {
         let display = conn.display();
 
         let mut event_queue = conn.new_event_queue();
@@ -67,11 +163,29 @@ impl Keyboard {
         event_queue.roundtrip(&mut state).unwrap();
         event_queue.roundtrip(&mut state).unwrap();
 
+        if state.keyboard_manager.is_none() {
+            error!("Compositor doesn't support virtual-keyboard-v1, keys can't be typed");
+        }
+        if state.input_manager.is_none() {
+            warn!("Compositor doesn't support input-method-v2, text insertion is disabled");
+        }
+
         Self {
             session_state: state,
             event_queue,
             modifiers: 0,
             locks: 0,
+            deleted: None,
+            key_serial: 0,
+            pressed: HashSet::default(),
+            last_time: 0,
+            idle: false,
+            watch_focus: false,
+            release_on_hide: true,
+            selecting: false,
+            popup: None,
+            displays: HashMap::default(),
+            display: None,
 
             layout,
             layer: (0, 0),
@@ -98,14 +212,85 @@ impl Keyboard {
             KeyMessage::LockRelease(scan_code) => {
                 self.remove_lock(evdev::Key::new(scan_code));
             }
+            KeyMessage::Selecting(enable) => {
+                if enable {
+                    self.selecting = true;
+                    self.append_mod(evdev::Key::KEY_LEFTSHIFT);
+                } else {
+                    self.selecting = false;
+                    self.remove_mod(evdev::Key::KEY_LEFTSHIFT);
+                }
+            }
+            KeyMessage::Display(display) => {
+                self.set_display(display);
+            }
             KeyMessage::MouseLayer(enable) => {
-                // The mouse layer is added as the last layer.
                 if enable {
-                    self.layer.0 = self.layout.left.len() - 1;
+                    self.layer.0 = self.layout.mouse_layer;
                 } else {
                     self.layer.0 = 0;
                 }
             }
+            KeyMessage::SettingsLayer(enable) => {
+                if enable {
+                    self.layer.1 = self.layout.settings_layer;
+                } else {
+                    self.layer.1 = 0;
+                }
+            }
+            KeyMessage::RememberDeleted => {
+                self.deleted = None;
+                if self.is_secret_field() {
+                    return;
+                }
+                // The selection is only current once the text
+                // field has sent its state after the selecting keys.
+                self.wait_for_done(self.key_serial, DONE_TIMEOUT);
+                self.deleted = self
+                    .session_state
+                    .surrounding_text
+                    .as_ref()
+                    .and_then(|text| text.selection())
+                    .map(str::to_string);
+                debug!(
+                    "  [Delete] Remembered {} bytes",
+                    self.deleted.as_ref().map_or(0, String::len)
+                );
+            }
+            KeyMessage::UndoDelete => {
+                if let Some(text) = self.deleted.take() {
+                    self.commit_string(text);
+                }
+            }
+            KeyMessage::Commit(text) => {
+                self.commit_string(text);
+            }
+            KeyMessage::Preedit(text) => {
+                if let Some(input) = &self.session_state.input {
+                    debug!("  [Input] Preedit: {} bytes", text.len());
+                    let end = text.len() as i32;
+                    input.set_preedit_string(text, end, end);
+                    input.commit(self.session_state.input_serial);
+                    self.event_queue.roundtrip(&mut self.session_state).unwrap();
+                }
+            }
+            KeyMessage::ReplaceBefore(len, text) => {
+                if let Some(input) = &self.session_state.input {
+                    debug!("  [Input] Replace {} bytes with {}", len, text.len());
+                    input.delete_surrounding_text(len, 0);
+                    input.commit_string(text);
+                    input.commit(self.session_state.input_serial);
+                    self.event_queue.roundtrip(&mut self.session_state).unwrap();
+                }
+            }
+            KeyMessage::TypeSecret(secret) => {
+                if let Err(err) = self.type_secret(&secret) {
+                    error!("Failed to type the secret: {}", err);
+                }
+            }
+            KeyMessage::Transform(transform) => {
+                self.transform_selection(&transform);
+            }
             KeyMessage::Layer(side, idx) => {
                 debug!("  [Layer] Switched: {:?} -> {:?}", side, idx);
                 match side {
@@ -117,21 +302,209 @@ impl Keyboard {
     }
 
     fn press_key(&mut self, key: evdev::Key) {
-        if let Some(keyboard) = &self.session_state.keyboard {
-            debug!("  [Key] Pressed: {:?}", key);
-            keyboard.key(0, key.code().into(), KeyState::Pressed.into());
-            self.event_queue.roundtrip(&mut self.session_state).unwrap();
+        // Release first if the key is somehow still pressed,
+        // so that every press is paired with a release.
+        if self.pressed.contains(&key) {
+            self.release_key(key);
+        }
+        let time = self.timestamp();
+        let sent = self.send_virtual(|keyboard| {
+            debug!("  [Key] Pressed: {:?} @ {}", key, time);
+            keyboard.key(time, key.code().into(), KeyState::Pressed.into());
+        });
+        if sent {
+            self.pressed.insert(key);
+            self.key_serial = self.session_state.input_serial;
         }
     }
 
     fn release_key(&mut self, key: evdev::Key) {
-        if let Some(keyboard) = &self.session_state.keyboard {
-            debug!("  [Key] Released: {:?}", key);
-            keyboard.key(0, key.code().into(), KeyState::Released.into());
+        if !self.pressed.remove(&key) {
+            debug!("  [Key] Not pressed, skipping release: {:?}", key);
+            return;
+        }
+        let time = self.timestamp();
+        self.send_virtual(|keyboard| {
+            debug!("  [Key] Released: {:?} @ {}", key, time);
+            keyboard.key(time, key.code().into(), KeyState::Released.into());
+        });
+    }
+
+    /// Send to the virtual keyboard on the display key events
+    /// go to, returning whether there is one to send to.
+    fn send_virtual(&mut self, send: impl FnOnce(&ZwpVirtualKeyboardV1)) -> bool {
+        let other = self.display.is_some();
+        self.try_send_virtual(send).unwrap_or_else(|err| {
+            if other {
+                error!("Failed to send to the other display: {}", err);
+                true
+            } else {
+                panic!("Failed to send key events: {}", err);
+            }
+        })
+    }
+
+    /// Like [`Self::send_virtual`], but returning the error
+    /// if the events couldn't be sent.
+    fn try_send_virtual(
+        &mut self,
+        send: impl FnOnce(&ZwpVirtualKeyboardV1),
+    ) -> Result<bool, DispatchError> {
+        let other = self
+            .display
+            .as_ref()
+            .and_then(|display| self.displays.get_mut(display));
+        match other {
+            Some((state, event_queue)) => {
+                let Some(keyboard) = &state.keyboard else {
+                    return Ok(false);
+                };
+                send(keyboard);
+                event_queue.roundtrip(state)?;
+            }
+            None => {
+                let Some(keyboard) = &self.session_state.keyboard else {
+                    return Ok(false);
+                };
+                send(keyboard);
+                self.event_queue.roundtrip(&mut self.session_state)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Send key events to another display from now on, connecting
+    /// to it if needed, or back to ours. If it can't be connected
+    /// to they keep going to ours.
+    fn set_display(&mut self, display: Option<String>) {
+        let unconnected = display
+            .as_ref()
+            .filter(|display| !self.displays.contains_key(*display));
+        if let Some(name) = unconnected {
+            match connect_display(name) {
+                Ok(session) => {
+                    debug!("[Keyboard] Connected to display: {}", name);
+                    self.displays.insert(name.clone(), session);
+                }
+                Err(err) => {
+                    error!("Failed to connect to display `{}`: {}", name, err);
+                    return;
+                }
+            }
+        }
+        self.display = display;
+        // The display may not have the current modifiers.
+        self.update_state();
+    }
+
+    /// A `CLOCK_MONOTONIC` timestamp in milliseconds,
+    /// as expected for key events. Timestamps never go backwards.
+    fn timestamp(&mut self) -> u32 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid timespec to write into.
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        let millis = (ts.tv_sec as u64 * 1000 + ts.tv_nsec as u64 / 1_000_000) as u32;
+
+        // Timestamps wrap around, so compare
+        // with wrapping arithmetic.
+        if millis.wrapping_sub(self.last_time) < u32::MAX / 2 {
+            self.last_time = millis;
+        }
+        self.last_time
+    }
+
+    /// Whether text can be inserted directly
+    /// (rather than typed as key presses).
+    pub fn has_input_method(&self) -> bool {
+        self.session_state.input_manager.is_some()
+    }
+
+    /// Insert text via the input method.
+    fn commit_string(&mut self, text: String) {
+        if let Some(input) = &self.session_state.input {
+            debug!("  [Input] Commit: {} bytes", text.len());
+            input.commit_string(text);
+            input.commit(self.session_state.input_serial);
+            self.event_queue.roundtrip(&mut self.session_state).unwrap();
+        }
+    }
+
+    /// Replace the selection with the transformed text.
+    fn transform_selection(&mut self, transform: &Transform) {
+        self.refresh();
+        let Some(text) = &self.session_state.surrounding_text else {
+            return;
+        };
+        let Some(selection) = text.selection() else {
+            debug!("  [Transform] Nothing selected");
+            return;
+        };
+        let transformed = transform.apply(selection);
+
+        // Committed text replaces the selection, so there's
+        // nothing to delete (the lengths to delete exclude it).
+        if let Some(input) = &self.session_state.input {
+            debug!("  [Transform] {:?}", transform);
+            input.commit_string(transformed);
+            input.commit(self.session_state.input_serial);
             self.event_queue.roundtrip(&mut self.session_state).unwrap();
         }
     }
 
+    /// Type a secret with key presses, without logging it.
+    /// Characters that can't be typed with the keymap
+    /// are inserted via the input method instead.
+    fn type_secret(&mut self, secret: &Secret) -> Result<(), DispatchError> {
+        debug!("  [Secret] Typing");
+
+        // Held modifiers and locks would change what's typed.
+        let (modifiers, locks) = (self.modifiers, self.locks);
+        self.locks = 0;
+        let typed = self.type_secret_chars(secret);
+        self.modifiers = modifiers;
+        self.locks = locks;
+        self.try_send_virtual(|keyboard| keyboard.modifiers(modifiers, 0, locks, 0))?;
+        typed
+    }
+
+    fn type_secret_chars(&mut self, secret: &Secret) -> Result<(), DispatchError> {
+        let shift = Self::map_mod_key(evdev::Key::KEY_LEFTSHIFT);
+        for c in secret.chars() {
+            match keymap().chars.get(&c).copied() {
+                Some((key, shifted)) => {
+                    let modifiers = if shifted { shift } else { 0 };
+                    self.modifiers = modifiers;
+                    self.try_send_virtual(|keyboard| keyboard.modifiers(modifiers, 0, 0, 0))?;
+
+                    // Not with `press_key`, as that logs the key.
+                    // Tracked as pressed until released, in case
+                    // sending the release fails.
+                    let (code, time) = (key.code().into(), self.timestamp());
+                    if self.try_send_virtual(|keyboard| {
+                        keyboard.key(time, code, KeyState::Pressed.into())
+                    })? {
+                        self.pressed.insert(key);
+                    }
+                    self.try_send_virtual(|keyboard| {
+                        keyboard.key(time, code, KeyState::Released.into())
+                    })?;
+                    self.pressed.remove(&key);
+                }
+                None => {
+                    if let Some(input) = &self.session_state.input {
+                        input.commit_string(c.to_string());
+                        input.commit(self.session_state.input_serial);
+                        self.event_queue.roundtrip(&mut self.session_state)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn append_mod(&mut self, key: evdev::Key) {
         debug!("  [Mod] Appended: {:?}", key);
         let mod_code = Self::map_mod_key(key);
@@ -141,8 +514,13 @@ impl Keyboard {
     }
 
     fn remove_mod(&mut self, key: evdev::Key) {
-        debug!("  [Mod] Removed: {:?}", key);
         let mod_code = Self::map_mod_key(key);
+        if self.selecting && mod_code == Self::map_mod_key(evdev::Key::KEY_LEFTSHIFT) {
+            // E.g. a select swipe, which presses and releases Shift.
+            debug!("  [Mod] Kept for selecting: {:?}", key);
+            return;
+        }
+        debug!("  [Mod] Removed: {:?}", key);
         self.modifiers &= !mod_code;
 
         self.update_state();
@@ -164,6 +542,98 @@ impl Keyboard {
         self.update_state();
     }
 
+    /// Go idle while the keyboard is hidden: release held
+    /// keys and modifiers (unless they're kept), and give up
+    /// the input method so there's nothing to keep in sync
+    /// (unless watching for focus). Returns whether any
+    /// modifiers were released.
+    pub fn suspend(&mut self) -> bool {
+        if self.idle {
+            return false;
+        }
+        debug!("[Keyboard] Suspending");
+        self.idle = true;
+
+        let mut released_mods = false;
+        if self.release_on_hide {
+            let pressed: Vec<_> = self.pressed.iter().copied().collect();
+            for key in pressed {
+                self.release_key(key);
+            }
+            self.selecting = false;
+            released_mods = self.modifiers != 0;
+            if released_mods {
+                self.modifiers = 0;
+                self.update_state();
+            }
+        }
+
+        if !self.watch_focus {
+            if let Some(popup) = self.popup.take() {
+                popup.destroy();
+            }
+            if let Some(input) = self.session_state.input.take() {
+                input.destroy();
+                self.event_queue.roundtrip(&mut self.session_state).unwrap();
+            }
+            self.session_state.surrounding_text = None;
+        }
+        self.deleted = None;
+        released_mods
+    }
+
+    /// Leave the idle state, taking the input method again.
+    pub fn resume(&mut self) {
+        if !self.idle {
+            return;
+        }
+        debug!("[Keyboard] Resuming");
+        self.idle = false;
+
+        let state = &mut self.session_state;
+        if let (Some(input_manager), Some(seat), None) =
+            (&state.input_manager, &state.seat, &state.input)
+        {
+            let input = input_manager.get_input_method(seat, &self.event_queue.handle(), ());
+            state.input = Some(input);
+            state.input_serial = 0;
+            self.event_queue.roundtrip(&mut self.session_state).unwrap();
+        }
+    }
+
+    /// Keep the input method while hidden, to notice text fields
+    /// being focused. Set before the keyboard first goes idle.
+    pub fn set_watch_focus(&mut self, watch: bool) {
+        self.watch_focus = watch;
+    }
+
+    /// Keep held keys and modifiers while hidden,
+    /// rather than releasing them when going idle.
+    pub fn set_release_on_hide(&mut self, release: bool) {
+        self.release_on_hide = release;
+    }
+
+    /// The connection to the compositor, to watch for events
+    /// arriving while nothing else is reading them.
+    pub fn connection_fd(&self) -> Option<RawFd> {
+        self.event_queue
+            .prepare_read()
+            .map(|guard| guard.connection_fd().as_raw_fd())
+    }
+
+    /// Handle any events waiting on the connection, returning
+    /// whether a text field was focused since this last did.
+    pub fn poll(&mut self) -> bool {
+        if let Some(guard) = self.event_queue.prepare_read() {
+            // Having nothing to read isn't a problem.
+            let _ = guard.read();
+        }
+        if let Err(err) = self.event_queue.dispatch_pending(&mut self.session_state) {
+            error!("Failed to handle compositor events: {}", err);
+        }
+        std::mem::take(&mut self.session_state.activated)
+    }
+
     pub fn destroy(&mut self) {
         if let Some(keyboard) = &self.session_state.keyboard {
             keyboard.destroy();
@@ -172,10 +642,8 @@ impl Keyboard {
     }
 
     fn update_state(&mut self) {
-        if let Some(keyboard) = &self.session_state.keyboard {
-            keyboard.modifiers(self.modifiers, 0, self.locks, 0);
-            self.event_queue.roundtrip(&mut self.session_state).unwrap();
-        }
+        let (modifiers, locks) = (self.modifiers, self.locks);
+        self.send_virtual(|keyboard| keyboard.modifiers(modifiers, 0, locks, 0));
     }
 
     fn map_mod_key(key: evdev::Key) -> u32 {
@@ -197,10 +665,163 @@ impl Keyboard {
         }
     }
 
+    /// The lock keys that are on, by evdev code.
+    pub fn active_locks(&self) -> Vec<u16> {
+        [
+            evdev::Key::KEY_CAPSLOCK,
+            evdev::Key::KEY_NUMLOCK,
+            evdev::Key::KEY_SCROLLLOCK,
+        ]
+        .into_iter()
+        .filter(|key| self.locks & Self::map_lock_key(*key) != 0)
+        .map(|key| key.code())
+        .collect()
+    }
+
+    /// Show candidates next to the text cursor, or hide them if
+    /// there are none. Does nothing without an input method.
+    pub fn show_candidates(&mut self, candidates: &[String]) {
+        let state = &self.session_state;
+        let (Some(input), Some(compositor), Some(shm)) =
+            (&state.input, &state.compositor, &state.shm)
+        else {
+            return;
+        };
+        let qh = self.event_queue.handle();
+        let popup = self
+            .popup
+            .get_or_insert_with(|| CandidatePopup::new(input, compositor, &qh));
+        if candidates.is_empty() {
+            popup.hide();
+        } else if let Err(err) = popup.show(candidates, shm, &qh) {
+            error!("Failed to draw the candidate popup: {}", err);
+        }
+        if let Err(err) = self.event_queue.flush() {
+            error!("Failed to show the candidate popup: {}", err);
+        }
+    }
+
+    /// Whether selection mode is on.
+    pub fn selecting(&self) -> bool {
+        self.selecting
+    }
+
+    /// Whether a modifier is currently held.
+    pub fn mod_active(&self, modifier: &Modifier) -> bool {
+        let mod_code = Self::map_mod_key(evdev::Key::new(modifier.code()));
+        self.modifiers & mod_code != 0
+    }
+
+    /// Pick up any changes to the focused text field.
+    pub fn refresh(&mut self) {
+        if self.session_state.input.is_some() {
+            self.event_queue.roundtrip(&mut self.session_state).unwrap();
+        }
+    }
+
+    /// Wait (up to the timeout) for a `done` after the one with
+    /// the serial, e.g. for the text field to send its state
+    /// after keys were sent. Fields send nothing if the keys
+    /// didn't change anything, hence the timeout.
+    fn wait_for_done(&mut self, serial: u32, timeout: Duration) {
+        self.refresh();
+        let started = Instant::now();
+        while self.session_state.input.is_some() && self.session_state.input_serial == serial {
+            let Some(left) = timeout.checked_sub(started.elapsed()) else {
+                debug!("  [Input] Gave up waiting for the text field");
+                return;
+            };
+            if let Some(guard) = self.event_queue.prepare_read() {
+                let mut fd = libc::pollfd {
+                    fd: guard.connection_fd().as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let timeout = left.as_millis().max(1) as libc::c_int;
+                if unsafe { libc::poll(&mut fd, 1, timeout) } <= 0 {
+                    continue;
+                }
+                // Having nothing to read isn't a problem.
+                let _ = guard.read();
+            }
+            if let Err(err) = self.event_queue.dispatch_pending(&mut self.session_state) {
+                error!("Failed to handle compositor events: {}", err);
+                return;
+            }
+        }
+    }
+
+    /// Text around the cursor in the focused text field, if known.
+    pub fn surrounding_text(&self) -> Option<&SurroundingText> {
+        self.session_state.surrounding_text.as_ref()
+    }
+
+    /// What the focused text field is for,
+    /// as a text-input-v3 `content_purpose`.
+    pub fn content_purpose(&self) -> u32 {
+        self.session_state.content_purpose
+    }
+
+    /// Whether the focused text field is for a password or PIN,
+    /// whose text mustn't be kept or passed on.
+    pub fn is_secret_field(&self) -> bool {
+        matches!(
+            self.session_state.content_purpose,
+            PURPOSE_PASSWORD | PURPOSE_PIN
+        )
+    }
+
+    /// Text removed by the last delete swipe, if any.
+    pub fn deleted(&self) -> Option<&str> {
+        self.deleted.as_deref()
+    }
+
+    pub fn mouse_layer_active(&self) -> bool {
+        self.layer.0 == self.layout.mouse_layer
+    }
+
+    pub fn settings_open(&self) -> bool {
+        self.layer.1 == self.layout.settings_layer
+    }
+
+    /// Replace the layout, resetting to the base layers.
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.layer = (0, 0);
+    }
+
+    /// A human-readable name for the active layer on a side.
+    pub fn layer_name(&self, side: Side) -> String {
+        match side {
+            Side::Left if self.mouse_layer_active() => "Mouse layer".into(),
+            Side::Right if self.settings_open() => "Settings".into(),
+            Side::Left => format!("Left layer {}", self.layer.0),
+            Side::Right => format!("Right layer {}", self.layer.1),
+        }
+    }
+
     pub fn trigger_key(&self) -> &TriggerKey {
         &self.layout.trigger
     }
 
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.layout.gestures
+    }
+
+    /// Whether either active layer is a vim layer.
+    pub fn vim_active(&self) -> bool {
+        let left = self.layout.left.get(self.layer.0);
+        let right = self.layout.right.get(self.layer.1);
+        left.into_iter().chain(right).any(Layer::is_vim)
+    }
+
+    /// Whether either active layer is a calculator.
+    pub fn calculator_active(&self) -> bool {
+        let left = self.layout.left.get(self.layer.0);
+        let right = self.layout.right.get(self.layer.1);
+        left.into_iter().chain(right).any(|layer| layer.calculator)
+    }
+
     pub fn left_layers(&self) -> impl Iterator<Item = &Layer> {
         self.layout.left.iter()
     }
@@ -208,4 +829,74 @@ impl Keyboard {
     pub fn right_layers(&self) -> impl Iterator<Item = &Layer> {
         self.layout.right.iter()
     }
+
+    /// How much the layout scales a side's keys.
+    pub fn side_scale(&self, side: Side) -> f32 {
+        self.layout.scale.side(side)
+    }
+}
+
+/// Create a virtual keyboard on another display, by
+/// its name (e.g. `wayland-1`) or its socket's path.
+fn connect_display(display: &str) -> anyhow::Result<(SessionState, EventQueue<SessionState>)> {
+    let path = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(display);
+    let conn = Connection::from_socket(UnixStream::connect(path)?)?;
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+    conn.display().get_registry(&qh, ());
+
+    // As for our own display (see `Keyboard::new`).
+    let mut state = SessionState::default();
+    event_queue.roundtrip(&mut state)?;
+    event_queue.roundtrip(&mut state)?;
+
+    // Only key events are sent there, so it
+    // shouldn't take over its input method.
+    if let Some(input) = state.input.take() {
+        input.destroy();
+    }
+    if state.keyboard.is_none() {
+        anyhow::bail!("it doesn't support virtual-keyboard-v1");
+    }
+    Ok((state, event_queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    /// A keyboard holding Shift and A.
+    fn holding() -> Keyboard {
+        let mut keyboard = Keyboard::with_connection(Layout::default(), mock::connect());
+        keyboard.handle(KeyMessage::ModPress(evdev::Key::KEY_LEFTSHIFT.code()));
+        keyboard.handle(KeyMessage::ButtonPress(evdev::Key::KEY_A.code()));
+        assert!(keyboard.pressed.contains(&evdev::Key::KEY_A));
+        assert_ne!(keyboard.modifiers, 0);
+        keyboard
+    }
+
+    #[test]
+    fn suspend_releases_held() {
+        let mut keyboard = holding();
+        assert!(keyboard.suspend());
+        assert!(keyboard.pressed.is_empty());
+        assert_eq!(keyboard.modifiers, 0);
+
+        // Already idle, so there's nothing left to release.
+        assert!(!keyboard.suspend());
+    }
+
+    #[test]
+    fn suspend_keeps_held() {
+        let mut keyboard = holding();
+        let modifiers = keyboard.modifiers;
+        keyboard.set_release_on_hide(false);
+        assert!(!keyboard.suspend());
+        assert!(keyboard.pressed.contains(&evdev::Key::KEY_A));
+        assert_eq!(keyboard.modifiers, modifiers);
+    }
 }
@@ -1,8 +1,12 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
-use serde::{Deserialize, Deserializer};
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::pointer::PointerButton;
+use crate::{
+    capture::CaptureAction, compositor::CompositorAction, config::Setting, plugin::PluginAction,
+    pointer::PointerButton, secret::SecretSource, transform::Transform,
+};
 
 /// A `Layout` has two [`Side`]s,
 /// each of which consists of one or more [`Layer`]s.
@@ -11,38 +15,316 @@ pub struct Layout {
     pub left: Vec<Layer>,
     pub right: Vec<Layer>,
     pub trigger: TriggerKey,
+
+    #[serde(default)]
+    pub gestures: Vec<Gesture>,
+
+    /// Scale each side's keys, e.g. to make
+    /// the half under the dominant hand larger.
+    #[serde(default)]
+    pub scale: SideScale,
+
+    /// Where the built-in mouse (left)
+    /// and settings (right) layers are.
+    #[serde(skip)]
+    pub mouse_layer: usize,
+    #[serde(skip)]
+    pub settings_layer: usize,
+}
+
+/// Key size multipliers for each side.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct SideScale {
+    pub left: f32,
+    pub right: f32,
+}
+impl SideScale {
+    pub fn side(&self, side: Side) -> f32 {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+}
+impl Default for SideScale {
+    fn default() -> Self {
+        Self {
+            left: 1.,
+            right: 1.,
+        }
+    }
 }
 
 impl Layout {
+    /// The YAML for the default layout.
+    pub const DEFAULT: &str = include_str!("../assets/layout.yml");
+
     pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
         let layout: String = fs_err::read_to_string(path).expect("Layout file not found");
         Self::from_str(&layout)
     }
 
+    /// Like [`Layout::from_path`] but fails gracefully,
+    /// for loading layouts while running.
+    pub fn try_from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let layout: String = fs_err::read_to_string(path)?;
+        Self::try_from_str(&layout)
+    }
+
+    pub fn try_from_str(s: &str) -> Result<Self> {
+        let layout: Layout = serde_yaml::from_str(s)?;
+        Ok(layout.with_builtin_layers())
+    }
+
     fn from_str(s: &str) -> Self {
-        let mut layout: Layout = serde_yaml::from_str(s).expect("Layout is invalid");
+        let layout: Layout = serde_yaml::from_str(s).expect("Layout is invalid");
+        layout.with_builtin_layers()
+    }
+
+    fn with_builtin_layers(mut self) -> Self {
         let mouse_layer = include_str!("../assets/mouse-layer.yml");
         let mouse_layer: Layer = serde_yaml::from_str(mouse_layer).expect("Mouse layer is invalid");
-        layout.left.push(mouse_layer);
-        layout
+        self.mouse_layer = self.left.len();
+        self.left.push(mouse_layer);
+        let settings_layer = include_str!("../assets/settings-layer.yml");
+        let settings_layer: Layer =
+            serde_yaml::from_str(settings_layer).expect("Settings layer is invalid");
+        self.settings_layer = self.right.len();
+        self.right.push(settings_layer);
+        self
     }
 }
 impl Default for Layout {
     fn default() -> Self {
-        let default = include_str!("../assets/layout.yml");
-        Self::from_str(default)
+        Self::from_str(Self::DEFAULT)
+    }
+}
+impl Layout {
+    /// The default layout for a locale (e.g. "hi_IN.UTF-8").
+    /// Where the script is typed with bundled layers these are
+    /// added after the default layout's own layers, and each
+    /// half's is shown while swiping down on `-` (left) or `/`
+    /// (right) on the other half, as with the symbol layers.
+    pub fn for_locale(locale: Option<&str>) -> Self {
+        let locale = locale.unwrap_or_default();
+        let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+        let bundled = if locale.starts_with("zh_TW") {
+            [BundledLayer::ZhuyinLeft, BundledLayer::ZhuyinRight]
+        } else if matches!(language, "hi" | "mr" | "ne") {
+            [BundledLayer::DevanagariLeft, BundledLayer::DevanagariRight]
+        } else {
+            return Self::default();
+        };
+
+        let mut layout: serde_yaml::Value =
+            serde_yaml::from_str(Self::DEFAULT).expect("Layout is invalid");
+        let mut added = [0; 2];
+        for ((side, layer), added) in ["left", "right"].into_iter().zip(bundled).zip(&mut added) {
+            if let Some(layers) = layout.get_mut(side).and_then(|v| v.as_sequence_mut()) {
+                let layer = serde_yaml::to_value(layer).expect("Bundled layers are names");
+                layers.push(layer);
+                *added = layers.len() - 1;
+            }
+        }
+        // The keys that switch to them, on the other half's first layer.
+        let switches = [
+            ("right", "KEY_SLASH", "Left", added[0]),
+            ("left", "KEY_MINUS", "Right", added[1]),
+        ];
+        for (side, key, target, index) in switches {
+            let Some(rows) = layout
+                .get_mut(side)
+                .and_then(|layers| layers.get_mut(0))
+                .and_then(|rows| rows.as_sequence_mut())
+            else {
+                continue;
+            };
+            let switch = rows
+                .iter_mut()
+                .filter_map(|row| row.as_sequence_mut())
+                .flatten()
+                .find(|def| {
+                    def.get("key").and_then(|k| k.as_str()) == Some(key)
+                        && def.get("mods").is_none()
+                });
+            if let Some(def) = switch {
+                def["s"] = serde_yaml::from_str(&format!("Layer: [{}, {}]", target, index))
+                    .expect("Layer switch is valid");
+            }
+        }
+        let layout: Layout = serde_yaml::from_value(layout).expect("Layout is invalid");
+        layout.with_builtin_layers()
     }
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(transparent)]
+#[serde(from = "LayerDef")]
 pub struct Layer {
     layout: Vec<Vec<KeyDef>>,
+
+    /// An accent color (any CSS color) to tint the layer with,
+    /// so it's easy to tell which layer is active.
+    pub color: Option<String>,
+
+    /// Whether expressions typed on this layer are
+    /// evaluated, with the result offered as a suggestion.
+    pub calculator: bool,
 }
 impl Layer {
     pub fn rows(&self) -> impl Iterator<Item = &Vec<KeyDef>> {
         self.layout.iter()
     }
+
+    /// Whether this is a vim layer, i.e. it has vim keys.
+    pub fn is_vim(&self) -> bool {
+        self.rows()
+            .flatten()
+            .any(|key| matches!(key.unconditional(), KeyDef::Vim(_)))
+    }
+
+    /// Whether any keys depend on the [`KeyContext`] (e.g. they
+    /// have conditions), in which case the layer has to be
+    /// re-rendered as it changes.
+    pub fn is_contextual(&self) -> bool {
+        self.rows().flatten().any(|key| {
+            matches!(
+                key,
+                KeyDef::Conditional(_) | KeyDef::Paste(_) | KeyDef::Selection
+            )
+        })
+    }
+
+    /// Whether this has paste keys.
+    pub fn has_paste(&self) -> bool {
+        self.rows()
+            .flatten()
+            .any(|key| matches!(key.unconditional(), KeyDef::Paste(_)))
+    }
+}
+
+/// A layer is either just its rows, its rows
+/// along with other options, or a bundled layer.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LayerDef {
+    Bundled(BundledLayer),
+    Rows(Vec<RowDef>),
+    Full {
+        rows: Vec<RowDef>,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        calculator: bool,
+    },
+}
+/// Layers that come with kway, referred to by name.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum BundledLayer {
+    /// The left and right halves of the standard Zhuyin layout.
+    ZhuyinLeft,
+    ZhuyinRight,
+
+    /// The left and right halves of the InScript
+    /// Devanagari layout, with shifted characters
+    /// on swipe up.
+    DevanagariLeft,
+    DevanagariRight,
+}
+impl BundledLayer {
+    fn yaml(&self) -> &'static str {
+        match self {
+            Self::ZhuyinLeft => include_str!("../assets/zhuyin-left.yml"),
+            Self::ZhuyinRight => include_str!("../assets/zhuyin-right.yml"),
+            Self::DevanagariLeft => include_str!("../assets/devanagari-left.yml"),
+            Self::DevanagariRight => include_str!("../assets/devanagari-right.yml"),
+        }
+    }
+}
+
+impl From<LayerDef> for Layer {
+    fn from(def: LayerDef) -> Self {
+        match def {
+            LayerDef::Bundled(layer) => {
+                serde_yaml::from_str(layer.yaml()).expect("Bundled layer is invalid")
+            }
+            LayerDef::Rows(rows) => Self {
+                layout: expand(rows),
+                color: None,
+                calculator: false,
+            },
+            LayerDef::Full {
+                rows,
+                color,
+                calculator,
+            } => Self {
+                layout: expand(rows),
+                color,
+                calculator,
+            },
+        }
+    }
+}
+
+/// A row is either its keys, or a directive
+/// that's expanded into one or more rows.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RowDef {
+    Keys(Vec<KeyDef>),
+    Directive(Directive),
+}
+
+/// Shorthand for common blocks of keys.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Directive {
+    /// Arrows, with Home/End and Page Up/Down around them:
+    ///
+    /// ```text
+    /// Home  ↑  End   PgUp
+    ///  ←    ↓   →    PgDn
+    /// ```
+    NavCluster,
+}
+impl Directive {
+    fn rows(&self) -> Vec<Vec<KeyDef>> {
+        let keys = |keys: [evdev::Key; 4]| {
+            keys.into_iter()
+                .map(|key| {
+                    KeyDef::Basic(BasicKey {
+                        key,
+                        ..Default::default()
+                    })
+                })
+                .collect()
+        };
+        match self {
+            Self::NavCluster => vec![
+                keys([
+                    evdev::Key::KEY_HOME,
+                    evdev::Key::KEY_UP,
+                    evdev::Key::KEY_END,
+                    evdev::Key::KEY_PAGEUP,
+                ]),
+                keys([
+                    evdev::Key::KEY_LEFT,
+                    evdev::Key::KEY_DOWN,
+                    evdev::Key::KEY_RIGHT,
+                    evdev::Key::KEY_PAGEDOWN,
+                ]),
+            ],
+        }
+    }
+}
+
+fn expand(rows: Vec<RowDef>) -> Vec<Vec<KeyDef>> {
+    rows.into_iter()
+        .flat_map(|row| match row {
+            RowDef::Keys(keys) => vec![keys],
+            RowDef::Directive(directive) => directive.rows(),
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -51,7 +333,7 @@ pub enum Side {
     Right,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum Modifier {
     Alt,
     Ctrl,
@@ -76,24 +358,427 @@ pub struct Command {
     #[serde(default)]
     pub args: Vec<String>,
     pub label: String,
+
+    /// Extra environment variables to run with.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// The directory to run in.
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+impl Command {
+    /// A command that isn't bound to a key.
+    pub fn new(cmd: String, args: Vec<String>) -> Self {
+        Self {
+            cmd,
+            args,
+            label: String::new(),
+            env: HashMap::default(),
+            cwd: None,
+        }
+    }
+}
+
+/// Run a command and insert its output.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandInsert {
+    pub insert: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub label: String,
+
+    /// Give up on the command after this long (ms).
+    #[serde(default = "insert_timeout")]
+    pub timeout: u64,
+
+    /// Extra environment variables to run with.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+
+    /// The directory to run in.
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+fn insert_timeout() -> u64 {
+    5000
+}
+
+/// Run a Rhai script with the keyboard's state,
+/// acting on what it asks for (see [`crate::script`]).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Script {
+    /// Path to the script.
+    pub script: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub label: String,
+
+    /// How long (ms) to wait for the script to finish.
+    #[serde(default = "script_timeout")]
+    pub timeout: u64,
+}
+
+fn script_timeout() -> u64 {
+    1000
+}
+
+/// Paste the clipboard's text, which is previewed on the key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasteKey {
+    pub paste: PasteOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PasteOptions {
+    /// What to do with newlines in the pasted text.
+    pub newlines: Newlines,
+
+    /// Refuse to paste more than this many characters.
+    pub limit: usize,
+}
+impl Default for PasteOptions {
+    fn default() -> Self {
+        Self {
+            newlines: Newlines::Keep,
+            limit: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub enum Newlines {
+    #[default]
+    Keep,
+
+    /// Replace them with spaces, e.g. so that
+    /// pasting into a terminal doesn't run anything.
+    Space,
+
+    /// Remove them.
+    Strip,
+}
+impl Newlines {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Keep => text.to_string(),
+            Self::Space => text.replace("\r\n", " ").replace(['\n', '\r'], " "),
+            Self::Strip => text.replace(['\n', '\r'], ""),
+        }
+    }
+}
+
+/// Take a screenshot or record the screen (see [`crate::capture`]).
+#[derive(Debug, Clone, Deserialize)]
+pub struct CaptureKey {
+    pub capture: CaptureAction,
+}
+
+/// The spacebar, `space` keys wide. Dragging it sideways moves
+/// the text cursor, and a long-press opens the switcher
+/// for languages and profiles. Either can be turned off.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpaceKey {
+    pub space: u8,
+
+    #[serde(default = "enabled")]
+    pub drag: bool,
+
+    #[serde(default = "enabled")]
+    pub switcher: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// A key whose label and actions come from a
+/// script, refreshed periodically (see [`crate::dynamic`]).
+#[derive(Debug, Clone, Deserialize)]
+pub struct DynamicKey {
+    pub dynamic: String,
+
+    #[serde(default)]
+    pub args: Vec<String>,
+
+    /// How often (ms) to refresh the key while it's shown.
+    /// The script is given up on if it takes longer.
+    #[serde(default = "dynamic_interval")]
+    pub interval: u64,
+}
+
+fn dynamic_interval() -> u64 {
+    5000
+}
+
+/// Insert text from a template, e.g. the date
+/// (see [`crate::template`] for what's supported).
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateKey {
+    pub template: String,
+    pub label: String,
+}
+
+/// A vi command, e.g. `w` to move forward a word.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VimKey {
+    pub vim: char,
+
+    /// Shown instead of the command.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Insert text via the input method, e.g. for scripts
+/// the keymap can't type. Combining marks (e.g. Devanagari
+/// vowel signs) join up with the text before them, so
+/// conjuncts form as they're typed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextKey {
+    pub text: String,
+
+    /// Inserted instead when swiping up.
+    #[serde(default)]
+    pub up: Option<String>,
+
+    /// Shown instead of the text.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// A Zhuyin symbol or tone mark, composed into a syllable.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ZhuyinKey {
+    pub zhuyin: char,
+}
+
+/// Type a secret (e.g. a password) from a secret store.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecretKey {
+    pub secret: SecretSource,
+    pub label: String,
+
+    /// Require a second tap to type the secret.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// A key that's only enabled under some conditions,
+/// checked whenever its layer is rendered.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConditionalKey {
+    #[serde(flatten)]
+    pub conditions: Conditions,
+
+    #[serde(flatten)]
+    pub key: Box<KeyDef>,
+}
+
+/// All given conditions have to hold. At least one
+/// has to be given, so that other keys aren't taken
+/// for conditional ones.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawConditions")]
+pub struct Conditions {
+    /// The focused app's id.
+    pub if_app: Option<String>,
+
+    /// Whether a lock key (e.g. Num Lock) is on.
+    pub if_lock_key: Option<bool>,
+
+    /// Whether the output is taller than it is wide.
+    pub if_portrait: Option<bool>,
+
+    /// Hide the key, rather than greying it out.
+    pub hide: bool,
+}
+impl Conditions {
+    pub fn met(&self, context: &KeyContext) -> bool {
+        self.if_app
+            .as_ref()
+            .is_none_or(|app| context.app.as_ref() == Some(app))
+            && self
+                .if_lock_key
+                .is_none_or(|locked| locked != context.locks.is_empty())
+            && self
+                .if_portrait
+                .is_none_or(|portrait| portrait == context.portrait)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawConditions {
+    if_app: Option<String>,
+    if_lock_key: Option<bool>,
+    if_portrait: Option<bool>,
+    #[serde(default)]
+    hide: bool,
+}
+impl TryFrom<RawConditions> for Conditions {
+    type Error = &'static str;
+
+    fn try_from(raw: RawConditions) -> Result<Self, Self::Error> {
+        if raw.if_app.is_none() && raw.if_lock_key.is_none() && raw.if_portrait.is_none() {
+            return Err("no conditions");
+        }
+        Ok(Self {
+            if_app: raw.if_app,
+            if_lock_key: raw.if_lock_key,
+            if_portrait: raw.if_portrait,
+            hide: raw.hide,
+        })
+    }
+}
+
+impl KeyDef {
+    /// The key itself, without any conditions.
+    pub fn unconditional(&self) -> &KeyDef {
+        match self {
+            Self::Conditional(key) => key.key.unconditional(),
+            key => key,
+        }
+    }
+
+    /// How many key widths the key takes up.
+    pub fn width(&self) -> f32 {
+        match self.unconditional() {
+            Self::Basic(key) => key.width(),
+            Self::Space(key) => f32::from(key.space),
+            _ => 1.,
+        }
+    }
+}
+
+/// The state that key conditions depend on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyContext {
+    /// The focused app's id, if known.
+    pub app: Option<String>,
+
+    /// The lock keys that are on, by evdev code.
+    pub locks: Vec<u16>,
+
+    pub portrait: bool,
+
+    /// The start of the clipboard's text, for paste keys.
+    pub clipboard: Option<String>,
+
+    /// Whether selection mode is on.
+    pub selecting: bool,
 }
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub enum KeyDef {
+    /// A key with conditions for being enabled.
+    /// This has to come first, as the other
+    /// variants ignore fields they don't know.
+    Conditional(ConditionalKey),
+
     /// A basic key, with optional swipe actions.
     Basic(BasicKey),
 
     /// Execute an arbitrary command.
     Command(Command),
 
+    /// Execute a command and insert its output.
+    CommandInsert(CommandInsert),
+
+    /// Insert text from a template.
+    Template(TemplateKey),
+
+    /// Insert text, e.g. in another script.
+    Text(TextKey),
+
+    /// Run a script that can act on the keyboard's state.
+    Script(Script),
+
+    /// Show and act on a script's output, refreshed periodically.
+    Dynamic(DynamicKey),
+
+    /// Paste the clipboard, previewing it on the key.
+    Paste(PasteKey),
+
+    /// Take a screenshot or record the screen.
+    Capture(CaptureKey),
+
+    /// The spacebar, with gestures common on phones.
+    Space(SpaceKey),
+
+    /// A vi command, for vim layers.
+    Vim(VimKey),
+
+    /// A Zhuyin symbol, for Zhuyin layers.
+    Zhuyin(ZhuyinKey),
+
+    /// Type a secret from a secret store.
+    Secret(SecretKey),
+
     /// Send a mouse/pointer button.
     PointerButton(PointerButton),
 
+    /// Change a setting.
+    Setting(SettingKey),
+
+    /// Trigger a compositor action, e.g. the overview.
+    Compositor(CompositorKey),
+
+    /// Run a plugin action.
+    Plugin(PluginKey),
+
     /// Control the mouse/pointer.
     #[serde(deserialize_with = "pointer")]
     Pointer,
+
+    /// Toggle the full-window touchpad.
+    #[serde(deserialize_with = "touchpad")]
+    Touchpad,
+
+    /// Nudge the pointer by a fixed number of pixels,
+    /// as `[dx, dy]` with y increasing downwards.
+    PointerNudge(i32, i32),
+
+    /// Hold to show a cheat sheet of the active layers.
+    #[serde(deserialize_with = "help")]
+    Help,
+
+    /// Toggle the handwriting panel.
+    #[serde(deserialize_with = "handwriting")]
+    Handwriting,
+
+    /// Scan a barcode or QR code and insert its text.
+    #[serde(deserialize_with = "scan")]
+    Scan,
+
+    /// Toggle selection mode, in which moving
+    /// the cursor (e.g. with swipes) selects.
+    #[serde(deserialize_with = "selection")]
+    Selection,
+
+    /// Toggle the PIN pad.
+    #[serde(deserialize_with = "pin_pad")]
+    PinPad,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingKey {
+    pub setting: Setting,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompositorKey {
+    pub compositor: CompositorAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginKey {
+    pub plugin: PluginAction,
+    pub label: String,
 }
 
 // Hack to deserialize an untagged unit variant by name.
@@ -111,6 +796,84 @@ where
     Ok(())
 }
 
+fn touchpad<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "Touchpad")]
+        Touchpad,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
+fn help<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "Help")]
+        Help,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
+fn handwriting<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "Handwriting")]
+        Handwriting,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
+fn scan<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "Scan")]
+        Scan,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
+fn selection<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "Selection")]
+        Selection,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
+fn pin_pad<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    enum Helper {
+        #[serde(rename = "PinPad")]
+        PinPad,
+    }
+    Helper::deserialize(deserializer)?;
+    Ok(())
+}
+
 /// Define special swipe behaviors
 /// for the button that opens the keyboard.
 #[derive(Debug, Clone, Deserialize)]
@@ -140,6 +903,62 @@ impl TriggerKey {
     }
 }
 
+/// A swipe on the keyboard background (i.e. not on a key).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawGesture")]
+pub struct Gesture {
+    /// How many fingers/touch points the swipe requires.
+    pub fingers: u32,
+
+    pub up: Option<SwipeAction>,
+    pub right: Option<SwipeAction>,
+    pub left: Option<SwipeAction>,
+    pub down: Option<SwipeAction>,
+}
+
+#[derive(Deserialize)]
+struct RawGesture {
+    #[serde(default = "one_finger")]
+    fingers: u32,
+
+    #[serde(default, rename = "n")]
+    up: Option<SwipeAction>,
+
+    #[serde(default, rename = "e")]
+    right: Option<SwipeAction>,
+
+    #[serde(default, rename = "w")]
+    left: Option<SwipeAction>,
+
+    #[serde(default, rename = "s")]
+    down: Option<SwipeAction>,
+}
+impl TryFrom<RawGesture> for Gesture {
+    type Error = &'static str;
+
+    fn try_from(raw: RawGesture) -> Result<Self, Self::Error> {
+        // Gestures aren't on a key, so there's no key to modify.
+        let actions = [&raw.up, &raw.right, &raw.left, &raw.down];
+        if actions
+            .iter()
+            .any(|action| matches!(action, Some(SwipeAction::Modified(_))))
+        {
+            return Err("Modified needs a key, so it can't be a gesture");
+        }
+        Ok(Self {
+            fingers: raw.fingers,
+            up: raw.up,
+            right: raw.right,
+            left: raw.left,
+            down: raw.down,
+        })
+    }
+}
+
+fn one_finger() -> u32 {
+    1
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct BasicKey {
     pub key: evdev::Key,
@@ -164,6 +983,24 @@ pub struct BasicKey {
 
     #[serde(default)]
     pub label: Option<String>,
+
+    /// Override the hold term (ms) for this key.
+    #[serde(default)]
+    pub hold: Option<u64>,
+
+    /// Override how far (px) a drag swipe (e.g. `Arrow`,
+    /// `Scroll`, `Select`, `Delete`) moves between repeats.
+    #[serde(default)]
+    pub increment: Option<f64>,
+
+    /// Send this key (and its swipes) to another Wayland
+    /// display, e.g. a nested compositor's `wayland-1`.
+    #[serde(default)]
+    pub display: Option<String>,
+
+    /// Override the label font for this key.
+    #[serde(default)]
+    pub font: Option<Font>,
 }
 impl Default for BasicKey {
     fn default() -> Self {
@@ -176,9 +1013,26 @@ impl Default for BasicKey {
             down: None,
             width: None,
             label: None,
+            hold: None,
+            increment: None,
+            display: None,
+            font: None,
         }
     }
 }
+
+/// A key label font. Anything not set
+/// falls back to the config, then the theme.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq, Hash)]
+pub struct Font {
+    pub family: Option<String>,
+
+    /// Weight, from 100 (thin) to 900 (black).
+    pub weight: Option<u16>,
+
+    /// Size (px).
+    pub size: Option<u32>,
+}
 impl BasicKey {
     pub fn width(&self) -> f32 {
         self.width.unwrap_or(1) as f32
@@ -239,4 +1093,47 @@ pub enum SwipeAction {
 
     /// Hide the keyboard.
     HideKeyboard,
+
+    /// Switch to the named profile.
+    Profile(String),
+
+    /// Toggle the settings layer.
+    Settings,
+
+    /// Toggle the app launcher.
+    Launcher,
+
+    /// Toggle completing URLs and paths in the suggestion bar.
+    Complete,
+
+    /// Switch to the next language.
+    Language,
+
+    /// Quit kway.
+    Quit,
+
+    /// Restart kway with the same arguments.
+    Restart,
+
+    /// Trigger a compositor action, e.g. switching workspaces.
+    Compositor(CompositorAction),
+
+    /// Transform the selected text, e.g. uppercasing it.
+    Transform(Transform),
+
+    /// Run a plugin action.
+    Plugin(PluginAction),
+
+    /// Run a script that can act on the keyboard's state.
+    Script(Script),
+}
+impl SwipeAction {
+    /// Whether this is a one-shot (see above),
+    /// rather than held or dragged.
+    pub fn fires_once(&self) -> bool {
+        !matches!(
+            self,
+            Self::Layer(..) | Self::Arrow | Self::Scroll | Self::Select | Self::Delete
+        )
+    }
 }
@@ -0,0 +1,41 @@
+pub mod app;
+mod calculator;
+mod capture;
+mod clipboard;
+mod completion;
+mod compositor;
+pub mod config;
+pub mod doctor;
+mod dynamic;
+mod events;
+mod fullscreen;
+mod handwriting;
+mod haptics;
+mod hardware;
+mod interaction;
+pub mod ipc;
+pub mod keyboard;
+pub mod layout;
+pub mod locale;
+mod lock;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod plugin;
+mod pointer;
+mod popup;
+mod portal;
+mod power;
+mod prediction;
+mod script;
+mod secret;
+pub mod session;
+mod shortcut;
+pub mod state;
+pub mod status;
+mod template;
+mod transform;
+mod transliterate;
+mod typing;
+pub mod ui;
+mod vim;
+mod zhuyin;
@@ -0,0 +1,114 @@
+//! Pick the keymap (and so what keys type and show)
+//! for the user's keyboard layout or locale, rather
+//! than always assuming US.
+
+use std::process;
+
+use crate::compositor::Compositor;
+
+/// Languages whose layout isn't named after a country,
+/// or is the same across countries.
+const LANGUAGE_LAYOUTS: &[(&str, &str)] = &[
+    ("ar", "ara"),
+    ("el", "gr"),
+    ("fa", "ir"),
+    ("he", "il"),
+    ("ja", "jp"),
+    ("ko", "kr"),
+    ("ru", "ru"),
+    ("uk", "ua"),
+    // Typed through bundled layers instead.
+    ("hi", "us"),
+    ("mr", "us"),
+    ("ne", "us"),
+    ("zh", "us"),
+];
+
+/// The user's locale, e.g. "de_DE.UTF-8",
+/// unless it's the default (C) locale.
+pub fn current() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .filter(|locale| !matches!(locale.as_str(), "C" | "POSIX") && !locale.starts_with("C."))
+}
+
+/// The xkb layout to use, e.g. "de" or "fr(bepo)":
+/// the compositor's, if it says, or else the locale's (if given).
+pub fn xkb_layout(locale: Option<&str>) -> String {
+    compositor_layout()
+        .or_else(|| locale.and_then(locale_layout))
+        .unwrap_or_else(|| "us".into())
+}
+
+/// The compositor's (first) layout. wlroots compositors (e.g. Sway)
+/// take theirs from `XKB_DEFAULT_LAYOUT` unless configured otherwise.
+fn compositor_layout() -> Option<String> {
+    let layouts =
+        std::env::var("XKB_DEFAULT_LAYOUT")
+            .ok()
+            .or_else(|| match Compositor::detect() {
+                Compositor::Hyprland => hyprland_layouts(),
+                _ => None,
+            })?;
+    let layout = layouts.split(',').next()?.trim();
+    (!layout.is_empty()).then(|| layout.to_string())
+}
+
+fn hyprland_layouts() -> Option<String> {
+    let output = process::Command::new("hyprctl")
+        .args(["getoption", "input:kb_layout", "-j"])
+        .output()
+        .ok()?;
+    let option: serde_yaml::Value = serde_yaml::from_slice(&output.stdout).ok()?;
+    option.get("str")?.as_str().map(str::to_string)
+}
+
+/// The layout for a locale, e.g. "ch" for "de_CH.UTF-8",
+/// as xkb layouts are mostly named after countries.
+fn locale_layout(locale: &str) -> Option<String> {
+    let locale = locale.split(['.', '@']).next()?;
+    let (language, territory) = locale.split_once('_').unwrap_or((locale, ""));
+    if let Some((_, layout)) = LANGUAGE_LAYOUTS.iter().find(|(lang, _)| *lang == language) {
+        return Some(layout.to_string());
+    }
+    match (language, territory) {
+        (_, "") => None,
+        ("en", territory) if !matches!(territory, "GB" | "IE") => Some("us".into()),
+        // xkb's "in" is Devanagari, which India's other
+        // languages (e.g. Tamil or Bengali) aren't written in.
+        (_, "IN") => Some("us".into()),
+        (_, territory) => Some(territory.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_by_territory() {
+        assert_eq!(locale_layout("de_DE.UTF-8").as_deref(), Some("de"));
+        assert_eq!(locale_layout("de_CH.UTF-8").as_deref(), Some("ch"));
+        assert_eq!(locale_layout("sr_RS@latin").as_deref(), Some("rs"));
+        assert_eq!(locale_layout("fr"), None);
+    }
+
+    #[test]
+    fn english_is_us_outside_britain_and_ireland() {
+        assert_eq!(locale_layout("en_US.UTF-8").as_deref(), Some("us"));
+        assert_eq!(locale_layout("en_AU").as_deref(), Some("us"));
+        assert_eq!(locale_layout("en_GB.UTF-8").as_deref(), Some("gb"));
+        assert_eq!(locale_layout("en_IE").as_deref(), Some("ie"));
+    }
+
+    #[test]
+    fn layouts_by_language() {
+        assert_eq!(locale_layout("ja_JP.UTF-8").as_deref(), Some("jp"));
+        assert_eq!(locale_layout("ru_UA").as_deref(), Some("ru"));
+        assert_eq!(locale_layout("uk").as_deref(), Some("ua"));
+        assert_eq!(locale_layout("hi_IN").as_deref(), Some("us"));
+        assert_eq!(locale_layout("ta_IN").as_deref(), Some("us"));
+    }
+}
@@ -0,0 +1,84 @@
+//! Watch for the session being locked, via logind.
+
+use relm4::{
+    Sender,
+    gtk::{
+        gio::{self, prelude::DBusProxyExt},
+        glib::{self, object::ObjectExt, variant::ToVariant},
+    },
+};
+use tracing::debug;
+
+use crate::ui::UIMessage;
+
+const LOGIND: &str = "org.freedesktop.login1";
+
+/// Send [`UIMessage::SessionLocked`] when the session is locked or unlocked.
+/// The returned proxy has to be kept alive to keep watching.
+pub fn watch(sender: Sender<UIMessage>) -> Result<gio::DBusProxy, glib::Error> {
+    let session = session_proxy()?;
+
+    // Lockers set this hint while the screen is locked.
+    let sender_cb = sender.clone();
+    session.connect_local("g-properties-changed", false, move |args| {
+        let changed = args[1].get::<glib::Variant>().ok()?;
+        let changed = glib::VariantDict::new(Some(&changed));
+        let locked = changed.lookup_value("LockedHint", None)?.get::<bool>()?;
+        debug!("[Lock] LockedHint: {}", locked);
+        sender_cb.emit(UIMessage::SessionLocked(locked));
+        None
+    });
+
+    // `loginctl lock-session` and the like.
+    session.connect_local("g-signal", false, move |args| {
+        let signal = args[2].get::<String>().ok()?;
+        debug!("[Lock] Signal: {}", signal);
+        match signal.as_str() {
+            "Lock" => sender.emit(UIMessage::SessionLocked(true)),
+            "Unlock" => sender.emit(UIMessage::SessionLocked(false)),
+            _ => (),
+        }
+        None
+    });
+
+    Ok(session)
+}
+
+/// A proxy for our logind session.
+fn session_proxy() -> Result<gio::DBusProxy, glib::Error> {
+    let manager = gio::DBusProxy::for_bus_sync(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        LOGIND,
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+        gio::Cancellable::NONE,
+    )?;
+
+    // Signals come from the session's actual path,
+    // so we can't just use `/session/auto`.
+    let path = manager.call_sync(
+        "GetSession",
+        Some(&("auto",).to_variant()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    )?;
+    let Some((path,)) = path.get::<(glib::variant::ObjectPath,)>() else {
+        return Err(glib::Error::new(
+            gio::IOErrorEnum::InvalidData,
+            &format!("Unexpected GetSession reply: {}", path),
+        ));
+    };
+
+    gio::DBusProxy::for_bus_sync(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        LOGIND,
+        path.as_str(),
+        "org.freedesktop.login1.Session",
+        gio::Cancellable::NONE,
+    )
+}
@@ -1,16 +1,9 @@
-mod app;
-mod autocorrect;
-mod keyboard;
-mod layout;
-mod pointer;
-mod session;
-mod ui;
-
 use std::path::PathBuf;
 
-use app::App;
 use bpaf::Bpaf;
-use layout::Layout;
+use kway::{
+    app::App, config::Config, doctor, ipc, layout::Layout, locale, session, state::State, status,
+};
 use tracing_subscriber::EnvFilter;
 
 #[derive(Clone, Debug, Bpaf)]
@@ -19,11 +12,120 @@ use tracing_subscriber::EnvFilter;
 struct Args {
     /// Path to layout file
     layout: Option<PathBuf>,
+
+    /// Path to config file
+    config: Option<PathBuf>,
+
+    /// Name of the profile to use
+    profile: Option<String>,
+
+    /// Keyboard layout to type with (xkb name, e.g. "de" or "fr(bepo)"),
+    /// instead of the compositor's or the locale's
+    keymap: Option<String>,
+
+    /// Send a command to the running instance and exit
+    msg: Option<String>,
+
+    #[bpaf(external(command), optional)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Debug, Bpaf)]
+enum Command {
+    /// Check for the protocols and permissions kway needs, then exit
+    #[bpaf(command)]
+    Doctor,
+
+    /// Print the running instance's state for status bars (e.g. waybar), then exit
+    #[bpaf(command)]
+    Status {
+        /// Keep printing the state as it changes
+        follow: bool,
+    },
 }
 
 fn main() {
     let opts = args().run();
-    let layout = opts.layout.map_or_else(Layout::default, Layout::from_path);
+    if let Some(msg) = opts.msg {
+        match ipc::send(&msg) {
+            Ok(response) => print!("{}", response),
+            Err(err) => eprintln!("Couldn't reach kway: {}", err),
+        }
+        return;
+    }
+
+    if let Some(Command::Status { follow }) = opts.command {
+        if !status::run(follow) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Command::Doctor) = opts.command {
+        if !doctor::run() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut config = Config::load(opts.config.as_deref()).unwrap_or_else(|err| {
+        eprintln!("{:#}, using the defaults", err);
+        Config::default()
+    });
+
+    // Come back as left, unless told otherwise.
+    let mut state = State::load();
+    config.apply_settings(&state.settings);
+    if let Some(profile) = &opts.profile {
+        if config.use_profile(profile).is_none() {
+            let names = config.profile_names();
+            if names.is_empty() {
+                eprintln!(
+                    "No profile named {}, none are defined in the config",
+                    profile
+                );
+            } else {
+                eprintln!(
+                    "No profile named {}, defined profiles: {}",
+                    profile,
+                    names.join(", ")
+                );
+            }
+            std::process::exit(1);
+        }
+    }
+
+    match &opts.profile {
+        None => {
+            if let Some(profile) = &state.profile {
+                config.use_profile(profile);
+            }
+        }
+        // The profile brings its own layout.
+        Some(_) => state.layout = None,
+    }
+    let known = |name: &String| config.languages.iter().any(|lang| &lang.name == name);
+    if let Some(language) = state.language.clone().filter(known) {
+        config.language = Some(language);
+    }
+    let layout_path = opts
+        .layout
+        .as_deref()
+        .or(state.layout.as_deref().filter(|path| path.exists()))
+        .or(config
+            .active_language()
+            .and_then(|lang| lang.layout.as_deref()))
+        .or(config.profile_layout());
+
+    let locale = locale::current();
+    let xkb_layout = opts.keymap.unwrap_or_else(|| {
+        let locale = locale.as_deref().filter(|_| config.locale_keymap);
+        locale::xkb_layout(locale)
+    });
+    session::use_xkb_layout(xkb_layout);
+
+    let layout =
+        layout_path.map_or_else(|| Layout::for_locale(locale.as_deref()), Layout::from_path);
 
     let filter = "none,kway=debug";
     let log_file = std::fs::OpenOptions::new()
@@ -40,6 +142,6 @@ fn main() {
         .with_writer(log_file)
         .init();
 
-    let app = App::new(layout);
+    let app = App::new(layout, config, state);
     app.run();
 }
@@ -0,0 +1,120 @@
+//! A mock compositor, for benchmarks and tests of the keyboard
+//! that don't need a display. It only offers a seat and virtual
+//! keyboards, and answers roundtrips.
+
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+};
+
+use wayland_client::Connection;
+
+/// The names the mock compositor gives its globals.
+const SEAT: u32 = 1;
+const KEYBOARD_MANAGER: u32 = 2;
+
+/// `wl_seat`'s keyboard capability.
+const CAPABILITY_KEYBOARD: u32 = 2;
+
+/// The object every connection starts with.
+const DISPLAY: u32 = 1;
+
+/// Connect to a new mock compositor,
+/// which runs until the connection is closed.
+pub fn connect() -> Connection {
+    let (client, server) = UnixStream::pair().expect("Socket pair could not be created");
+    thread::spawn(move || serve(server));
+    Connection::from_socket(client).expect("Mock compositor could not be connected")
+}
+
+/// Act as a compositor on one end of a socket, until it's closed.
+/// Requests are read off the socket and otherwise ignored, i.e.
+/// this is where key events end up.
+fn serve(mut socket: UnixStream) {
+    let mut registry = None;
+    let mut buf = vec![];
+    let mut chunk = [0; 4096];
+    loop {
+        // File descriptors sent along (i.e. the keymap) are dropped.
+        let read = match socket.read(&mut chunk) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read,
+        };
+        buf.extend_from_slice(&chunk[..read]);
+
+        // Each message starts with the object it's for,
+        // then its size (in bytes) and opcode.
+        while buf.len() >= 8 {
+            let object = word(&buf, 0);
+            let size = (word(&buf, 4) >> 16) as usize;
+            let opcode = word(&buf, 4) & 0xffff;
+            if buf.len() < size {
+                break;
+            }
+            let args = &buf[8..size];
+            let reply = match (object, opcode) {
+                // `wl_display.sync`, i.e. a roundtrip.
+                (DISPLAY, 0) => {
+                    let callback = word(args, 0);
+                    [
+                        event(callback, 0, &0u32.to_ne_bytes()),
+                        event(DISPLAY, 1, &callback.to_ne_bytes()),
+                    ]
+                    .concat()
+                }
+                // `wl_display.get_registry`.
+                (DISPLAY, 1) => {
+                    let id = word(args, 0);
+                    registry = Some(id);
+                    [
+                        event(id, 0, &global(SEAT, "wl_seat", 1)),
+                        event(
+                            id,
+                            0,
+                            &global(KEYBOARD_MANAGER, "zwp_virtual_keyboard_manager_v1", 1),
+                        ),
+                    ]
+                    .concat()
+                }
+                // `wl_registry.bind` for the seat, whose new id comes last.
+                (id, 0) if Some(id) == registry && word(args, 0) == SEAT => {
+                    let seat = word(args, args.len() - 4);
+                    event(seat, 0, &CAPABILITY_KEYBOARD.to_ne_bytes())
+                }
+                _ => vec![],
+            };
+            buf.drain(..size);
+            if socket.write_all(&reply).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The word at an offset into a message.
+fn word(buf: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// An event for an object, with its encoded arguments.
+fn event(object: u32, opcode: u32, args: &[u8]) -> Vec<u8> {
+    let size = 8 + args.len() as u32;
+    [
+        &object.to_ne_bytes()[..],
+        &(size << 16 | opcode).to_ne_bytes(),
+        args,
+    ]
+    .concat()
+}
+
+/// The arguments of `wl_registry.global`. The interface is
+/// sent with its length, NUL-terminated and padded to a word.
+fn global(name: u32, interface: &str, version: u32) -> Vec<u8> {
+    let mut args = name.to_ne_bytes().to_vec();
+    args.extend((interface.len() as u32 + 1).to_ne_bytes());
+    args.extend(interface.as_bytes());
+    args.resize(args.len() + 4 - interface.len() % 4, 0);
+    args.extend(version.to_ne_bytes());
+    args
+}
@@ -0,0 +1,89 @@
+//! Plugins add key and swipe actions beyond the built-in ones,
+//! e.g. controlling a music player, without changing the UI code.
+//!
+//! Plugins are compiled in: implement [`Plugin`] and add it to
+//! [`registered`]. Keys then refer to it by name, e.g.
+//! `plugin: { name: mpd, action: toggle }`.
+
+mod mpd;
+
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{
+    layout::{Modifier, Side},
+    pointer::{PointerButton, PointerMessage},
+};
+
+/// A call to a plugin, as bound to a key or swipe.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginAction {
+    /// The plugin's name.
+    pub name: String,
+
+    /// What to have the plugin do.
+    pub action: String,
+
+    /// Any further arguments, as the plugin expects them.
+    #[serde(default)]
+    pub args: serde_yaml::Value,
+}
+
+/// What a plugin has the keyboard do once it's run.
+#[derive(Debug)]
+pub enum Effect {
+    /// Insert text via the input method.
+    Insert(String),
+
+    /// Press a key with modifiers.
+    Key(Vec<Modifier>, evdev::Key),
+
+    /// Click a pointer button.
+    Click(PointerButton),
+
+    /// Move the pointer or scroll.
+    Pointer(PointerMessage),
+
+    /// Switch layers.
+    Layer(Side, usize),
+
+    /// Briefly show a message over the keyboard.
+    Toast(String),
+}
+
+pub trait Plugin: Send + Sync {
+    /// The name keys refer to the plugin by.
+    fn name(&self) -> &'static str;
+
+    /// Run an action. This is called off the UI thread,
+    /// so it's fine for it to block (e.g. on the network).
+    fn run(&self, action: &str, args: &serde_yaml::Value) -> anyhow::Result<Vec<Effect>>;
+}
+
+/// The compiled-in plugins.
+fn registered() -> Vec<Box<dyn Plugin>> {
+    vec![Box::new(mpd::Mpd)]
+}
+
+/// The available plugins, by name.
+#[derive(Clone)]
+pub struct Plugins(Arc<HashMap<&'static str, Box<dyn Plugin>>>);
+impl Default for Plugins {
+    fn default() -> Self {
+        let plugins = registered()
+            .into_iter()
+            .map(|plugin| (plugin.name(), plugin))
+            .collect();
+        Self(Arc::new(plugins))
+    }
+}
+impl Plugins {
+    pub fn run(&self, call: &PluginAction) -> anyhow::Result<Vec<Effect>> {
+        let plugin = self
+            .0
+            .get(call.name.as_str())
+            .ok_or_else(|| anyhow::anyhow!("no plugin named {}", call.name))?;
+        plugin.run(&call.action, &call.args)
+    }
+}
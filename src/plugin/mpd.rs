@@ -0,0 +1,87 @@
+//! Control the Music Player Daemon, over its text protocol.
+//!
+//! Actions: `toggle`, `play`, `stop`, `next`, `previous`,
+//! `volume` (with the change as `args`, e.g. `-5`),
+//! and `current`, which inserts the current song.
+//!
+//! The server is found via `MPD_HOST` and `MPD_PORT`,
+//! defaulting to `localhost:6600`.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+
+use super::{Effect, Plugin};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Mpd;
+impl Plugin for Mpd {
+    fn name(&self) -> &'static str {
+        "mpd"
+    }
+
+    fn run(&self, action: &str, args: &serde_yaml::Value) -> anyhow::Result<Vec<Effect>> {
+        let command = match action {
+            // Without an argument, `pause` toggles.
+            "toggle" => "pause".to_string(),
+            "play" | "stop" | "next" | "previous" => action.to_string(),
+            "volume" => {
+                let change = args.as_i64().context("volume needs a change, e.g. -5")?;
+                format!("volume {}", change)
+            }
+            "current" => {
+                let song = request("currentsong")?;
+                let field = |name: &str| {
+                    song.iter()
+                        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                        .map(str::to_string)
+                };
+                let text = match (field("Artist"), field("Title")) {
+                    (Some(artist), Some(title)) => format!("{} – {}", artist, title),
+                    (None, Some(title)) => title,
+                    _ => field("file").context("nothing is playing")?,
+                };
+                return Ok(vec![Effect::Insert(text)]);
+            }
+            _ => bail!("unknown action {}", action),
+        };
+        request(&command)?;
+        Ok(vec![])
+    }
+}
+
+/// Send a command, returning the lines of its response.
+fn request(command: &str) -> anyhow::Result<Vec<String>> {
+    let host = std::env::var("MPD_HOST").unwrap_or_else(|_| "localhost".into());
+    let port = std::env::var("MPD_PORT").unwrap_or_else(|_| "6600".into());
+    let stream = TcpStream::connect(format!("{}:{}", host, port))
+        .with_context(|| format!("couldn't connect to {}:{}", host, port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting)?;
+    if !greeting.starts_with("OK MPD") {
+        bail!("not an MPD server");
+    }
+
+    writeln!(&stream, "{}", command)?;
+    let mut lines = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line == "OK" {
+            return Ok(lines);
+        }
+        if let Some(error) = line.strip_prefix("ACK ") {
+            bail!("{}", error);
+        }
+        lines.push(line);
+    }
+    bail!("the connection closed early")
+}
@@ -1,16 +1,59 @@
-use anyhow::{Result, anyhow};
-use mouse_keyboard_input::{BTN_LEFT, BTN_MIDDLE, BTN_RIGHT, Button, VirtualDevice};
+use std::{
+    fmt, io,
+    time::{Duration, Instant},
+};
+
+use evdev::{
+    AttributeSet, EventType, InputEvent, Key, RelativeAxisType,
+    uinput::{VirtualDevice, VirtualDeviceBuilder},
+};
 use serde::Deserialize;
+use tracing::{error, info};
+
+/// High-resolution wheel units per wheel click ("detent").
+const HI_RES_PER_DETENT: i32 = 120;
 
-const SCROLL_STEP: i32 = 10;
+/// How long the device is left alone after it fails,
+/// doubling with each failure in a row up to the max.
+const BACKOFF_MIN: Duration = Duration::from_millis(250);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum PointerError {
+    /// Writing to the device failed.
+    Device(io::Error),
+
+    /// The device failed recently and
+    /// won't be retried for this long.
+    BackingOff(Duration),
+}
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device(err) => write!(f, "Pointer device failed: {}", err),
+            Self::BackingOff(wait) => {
+                write!(f, "Pointer device unavailable, retrying in {:.1?}", wait)
+            }
+        }
+    }
+}
+impl std::error::Error for PointerError {}
 
 #[derive(Debug)]
 pub enum PointerMessage {
+    /// Scroll by one wheel click.
     ScrollUp,
     ScrollDown,
     ScrollLeft,
     ScrollRight,
     Move(i32, i32),
+
+    /// Scroll by high-resolution wheel units,
+    /// with y positive upwards.
+    ScrollBy(i32, i32),
+
+    /// Send any pending (coalesced) movement.
+    Flush,
     Press(PointerButton),
     Release(PointerButton),
 }
@@ -26,76 +69,234 @@ pub enum PointerButton {
     #[serde(rename = "PointerRight")]
     Right,
 }
-impl From<PointerButton> for Button {
+impl From<PointerButton> for Key {
     fn from(val: PointerButton) -> Self {
         match val {
-            PointerButton::Left => BTN_LEFT,
-            PointerButton::Middle => BTN_MIDDLE,
-            PointerButton::Right => BTN_RIGHT,
+            PointerButton::Left => Key::BTN_LEFT,
+            PointerButton::Middle => Key::BTN_MIDDLE,
+            PointerButton::Right => Key::BTN_RIGHT,
         }
     }
 }
 
+/// How scrolling goes, as currently configured. It's
+/// passed with each message so config changes apply.
+#[derive(Debug, Clone, Copy)]
+pub struct Scrolling {
+    /// Whether scrolling is inverted so that
+    /// content follows the swipe.
+    pub natural: bool,
+}
+
 pub struct Pointer {
-    device: VirtualDevice,
+    /// The uinput device, which is (re)created when
+    /// it's next needed if it's missing or has failed.
+    device: Option<VirtualDevice>,
+
+    /// Movement accumulated since the last flush.
+    /// Movement is coalesced and sent at a fixed rate
+    /// to avoid flooding the device.
+    pending: (i32, i32),
+
+    /// High-resolution scroll accumulated (x, y) towards
+    /// the next legacy wheel click, for apps that
+    /// don't support high-resolution scrolling.
+    scroll_remainder: (i32, i32),
+
+    /// How many times in a row the device has failed.
+    failures: u32,
+
+    /// When the device can be tried again after failing.
+    retry_at: Option<Instant>,
 }
 impl Pointer {
     pub fn new() -> Self {
-        let device = VirtualDevice::default().unwrap();
-        Self { device }
+        // If this fails it's retried on the first pointer message.
+        let device = Self::build_device()
+            .inspect_err(|err| error!("Failed to create pointer device: {}", err))
+            .ok();
+        Self {
+            device,
+            pending: (0, 0),
+            scroll_remainder: (0, 0),
+            failures: 0,
+            retry_at: None,
+        }
     }
 
-    pub fn handle(&mut self, message: PointerMessage) -> Result<()> {
-        match message {
-            PointerMessage::ScrollUp => self.scroll_up(),
-            PointerMessage::ScrollDown => self.scroll_down(),
-            PointerMessage::ScrollLeft => self.scroll_left(),
-            PointerMessage::ScrollRight => self.scroll_right(),
-            PointerMessage::Move(x, y) => self.translate(x, y),
-            PointerMessage::Press(btn) => self.press(btn),
-            PointerMessage::Release(btn) => self.release(btn),
+    fn build_device() -> io::Result<VirtualDevice> {
+        let mut buttons = AttributeSet::<Key>::new();
+        for button in [Key::BTN_LEFT, Key::BTN_MIDDLE, Key::BTN_RIGHT] {
+            buttons.insert(button);
         }
+
+        let mut axes = AttributeSet::<RelativeAxisType>::new();
+        for axis in [
+            RelativeAxisType::REL_X,
+            RelativeAxisType::REL_Y,
+            RelativeAxisType::REL_WHEEL,
+            RelativeAxisType::REL_HWHEEL,
+            RelativeAxisType::REL_WHEEL_HI_RES,
+            RelativeAxisType::REL_HWHEEL_HI_RES,
+        ] {
+            axes.insert(axis);
+        }
+
+        let device = VirtualDeviceBuilder::new()?
+            .name("kway pointer")
+            .with_keys(&buttons)?
+            .with_relative_axes(&axes)?
+            .build()?;
+        Ok(device)
     }
 
-    fn scroll_up(&mut self) -> Result<()> {
-        self.device
-            .scroll_y(SCROLL_STEP)
-            .map_err(|err| anyhow!(err.to_string()))
+    /// Whether there's movement waiting to be flushed.
+    pub fn has_pending(&self) -> bool {
+        self.pending != (0, 0)
     }
 
-    fn scroll_down(&mut self) -> Result<()> {
-        self.device
-            .scroll_y(-SCROLL_STEP)
-            .map_err(|err| anyhow!(err.to_string()))
+    /// The movement waiting to be flushed,
+    /// with `y` positive upwards.
+    pub fn pending(&self) -> (i32, i32) {
+        self.pending
     }
 
-    fn scroll_left(&mut self) -> Result<()> {
-        self.device
-            .scroll_x(-SCROLL_STEP)
-            .map_err(|err| anyhow!(err.to_string()))
+    /// Handle a message, backing off from the device
+    /// for a while whenever it fails.
+    pub fn handle(
+        &mut self,
+        message: PointerMessage,
+        scrolling: Scrolling,
+    ) -> Result<(), PointerError> {
+        // Movement is only accumulated here,
+        // so it doesn't touch the device.
+        if let PointerMessage::Move(x, y) = message {
+            self.pending.0 += x;
+            self.pending.1 += y;
+            return Ok(());
+        }
+
+        if let Some(retry_at) = self.retry_at {
+            let now = Instant::now();
+            if now < retry_at {
+                // Drop movement rather than sending
+                // it all at once when the device is back.
+                self.pending = (0, 0);
+                return Err(PointerError::BackingOff(retry_at - now));
+            }
+        }
+
+        match self.send(message, scrolling) {
+            Ok(()) => {
+                if self.failures > 0 {
+                    info!("Pointer device recovered");
+                }
+                self.failures = 0;
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(err) => {
+                let backoff = BACKOFF_MIN
+                    .saturating_mul(2u32.saturating_pow(self.failures))
+                    .min(BACKOFF_MAX);
+                self.failures += 1;
+                self.retry_at = Some(Instant::now() + backoff);
+
+                // The device may have gone away (e.g. uinput was
+                // reloaded), so start over with a new one.
+                self.device = None;
+                Err(PointerError::Device(err))
+            }
+        }
     }
 
-    fn scroll_right(&mut self) -> Result<()> {
-        self.device
-            .scroll_x(SCROLL_STEP)
-            .map_err(|err| anyhow!(err.to_string()))
+    /// The device, creating it if needed.
+    fn device(&mut self) -> io::Result<&mut VirtualDevice> {
+        if self.device.is_none() {
+            info!("Creating pointer device");
+            let device = Self::build_device()
+                .inspect_err(|err| error!("Failed to create pointer device: {}", err))?;
+            self.device = Some(device);
+        }
+        Ok(self.device.as_mut().expect("Device was just created"))
     }
 
-    fn translate(&mut self, x: i32, y: i32) -> Result<()> {
-        self.device
-            .smooth_move_mouse(x, y)
-            .map_err(|err| anyhow!(err.to_string()))
+    fn send(&mut self, message: PointerMessage, scrolling: Scrolling) -> io::Result<()> {
+        // Clicks and scrolls happen where the
+        // pointer's been moved to, so catch up first.
+        if !matches!(message, PointerMessage::Flush) {
+            self.flush()?;
+        }
+        match message {
+            PointerMessage::ScrollUp => self.scroll(0, HI_RES_PER_DETENT, scrolling),
+            PointerMessage::ScrollDown => self.scroll(0, -HI_RES_PER_DETENT, scrolling),
+            PointerMessage::ScrollLeft => self.scroll(-HI_RES_PER_DETENT, 0, scrolling),
+            PointerMessage::ScrollRight => self.scroll(HI_RES_PER_DETENT, 0, scrolling),
+            PointerMessage::ScrollBy(x, y) => self.scroll(x, y, scrolling),
+            // Accumulated in `handle`.
+            PointerMessage::Move(..) => Ok(()),
+            PointerMessage::Flush => self.flush(),
+            PointerMessage::Press(btn) => self.button(btn, 1),
+            PointerMessage::Release(btn) => self.button(btn, 0),
+        }
+    }
+
+    /// Scroll by high-resolution amounts, also sending
+    /// legacy wheel clicks as whole clicks accumulate.
+    fn scroll(&mut self, x: i32, y: i32, scrolling: Scrolling) -> io::Result<()> {
+        let (x, y) = if scrolling.natural { (-x, -y) } else { (x, y) };
+
+        let rel =
+            |axis: RelativeAxisType, value| InputEvent::new(EventType::RELATIVE, axis.0, value);
+
+        if (x, y) == (0, 0) {
+            return Ok(());
+        }
+
+        let mut events = vec![];
+        if x != 0 {
+            events.push(rel(RelativeAxisType::REL_HWHEEL_HI_RES, x));
+            self.scroll_remainder.0 += x;
+            let detents = self.scroll_remainder.0 / HI_RES_PER_DETENT;
+            if detents != 0 {
+                self.scroll_remainder.0 -= detents * HI_RES_PER_DETENT;
+                events.push(rel(RelativeAxisType::REL_HWHEEL, detents));
+            }
+        }
+        if y != 0 {
+            events.push(rel(RelativeAxisType::REL_WHEEL_HI_RES, y));
+            self.scroll_remainder.1 += y;
+            let detents = self.scroll_remainder.1 / HI_RES_PER_DETENT;
+            if detents != 0 {
+                self.scroll_remainder.1 -= detents * HI_RES_PER_DETENT;
+                events.push(rel(RelativeAxisType::REL_WHEEL, detents));
+            }
+        }
+        self.device()?.emit(&events)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending);
+        if (x, y) == (0, 0) {
+            return Ok(());
+        }
+        self.translate(x, y)
     }
 
-    fn press(&mut self, button: PointerButton) -> Result<()> {
-        self.device
-            .press(button.into())
-            .map_err(|err| anyhow!(err.to_string()))
+    /// Note that `y` is positive upwards.
+    fn translate(&mut self, x: i32, y: i32) -> io::Result<()> {
+        self.device()?.emit(&[
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_X.0, x),
+            InputEvent::new(EventType::RELATIVE, RelativeAxisType::REL_Y.0, -y),
+        ])?;
+        Ok(())
     }
 
-    fn release(&mut self, button: PointerButton) -> Result<()> {
-        self.device
-            .release(button.into())
-            .map_err(|err| anyhow!(err.to_string()))
+    fn button(&mut self, button: PointerButton, value: i32) -> io::Result<()> {
+        let key: Key = button.into();
+        self.device()?
+            .emit(&[InputEvent::new(EventType::KEY, key.code(), value)])?;
+        Ok(())
     }
 }
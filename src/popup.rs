@@ -0,0 +1,151 @@
+//! Show candidates (e.g. for Zhuyin or handwriting) next to the
+//! text cursor as well, like desktop input methods do. This uses
+//! an input method popup surface, which the compositor places by
+//! the text field. GTK can't draw into a surface it doesn't own,
+//! so the candidates are drawn with pango and cairo into shared memory.
+
+use std::{io::Write, os::fd::AsFd, path::PathBuf};
+
+use pangocairo::{cairo, pango};
+use wayland_client::{
+    Connection, Dispatch, QueueHandle,
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_compositor::WlCompositor,
+        wl_shm::{self, WlShm},
+        wl_surface::WlSurface,
+    },
+};
+use wayland_protocols_misc::zwp_input_method_v2::client::{
+    zwp_input_method_v2::ZwpInputMethodV2, zwp_input_popup_surface_v2::ZwpInputPopupSurfaceV2,
+};
+
+use crate::session::SessionState;
+
+/// Font size (px) of the candidates.
+const FONT_SIZE: i32 = 18;
+
+/// Space (px) around and between the candidates.
+const PADDING: f64 = 6.;
+const GAP: f64 = 14.;
+
+pub struct CandidatePopup {
+    surface: WlSurface,
+    popup: ZwpInputPopupSurfaceV2,
+}
+impl CandidatePopup {
+    pub fn new(
+        input: &ZwpInputMethodV2,
+        compositor: &WlCompositor,
+        qh: &QueueHandle<SessionState>,
+    ) -> Self {
+        let surface = compositor.create_surface(qh, ());
+        let popup = input.get_input_popup_surface(&surface, qh, ());
+        Self { surface, popup }
+    }
+
+    /// Show the candidates, the first (the default) highlighted.
+    pub fn show(
+        &self,
+        candidates: &[String],
+        shm: &WlShm,
+        qh: &QueueHandle<SessionState>,
+    ) -> anyhow::Result<()> {
+        let (width, height, stride, pixels) = render(candidates)?;
+
+        let dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        let mut file = tempfile::tempfile_in(dir)?;
+        file.write_all(&pixels)?;
+        file.flush()?;
+
+        let pool = shm.create_pool(file.as_fd(), pixels.len() as i32, qh, ());
+        let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, qh, ());
+        pool.destroy();
+
+        self.surface.attach(Some(&buffer), 0, 0);
+        self.surface.damage_buffer(0, 0, width, height);
+        self.surface.commit();
+        Ok(())
+    }
+
+    /// A surface without a buffer isn't shown.
+    pub fn hide(&self) {
+        self.surface.attach(None, 0, 0);
+        self.surface.commit();
+    }
+
+    pub fn destroy(self) {
+        self.popup.destroy();
+        self.surface.destroy();
+    }
+}
+
+/// Draw the candidates in a row, returning the
+/// size, stride, and pixels (premultiplied ARGB).
+fn render(candidates: &[String]) -> Result<(i32, i32, i32, Vec<u8>), cairo::Error> {
+    // Lay out on a scratch surface first, to measure.
+    let scratch = cairo::ImageSurface::create(cairo::Format::ARgb32, 1, 1)?;
+    let ctx = cairo::Context::new(&scratch)?;
+    let mut font = pango::FontDescription::from_string("sans-serif");
+    font.set_absolute_size((FONT_SIZE * pango::SCALE) as f64);
+    let layouts: Vec<_> = candidates
+        .iter()
+        .map(|candidate| {
+            let layout = pangocairo::functions::create_layout(&ctx);
+            layout.set_font_description(Some(&font));
+            layout.set_text(candidate);
+            layout
+        })
+        .collect();
+    let sizes: Vec<_> = layouts.iter().map(|layout| layout.pixel_size()).collect();
+    let gaps = GAP * candidates.len().saturating_sub(1) as f64;
+    let text_width: i32 = sizes.iter().map(|(width, _)| width).sum();
+    let text_height = sizes.iter().map(|(_, height)| *height).max().unwrap_or(0);
+    let width = (text_width as f64 + gaps + PADDING * 2.).ceil() as i32;
+    let height = (text_height as f64 + PADDING * 2.).ceil() as i32;
+
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    {
+        let ctx = cairo::Context::new(&surface)?;
+        ctx.set_source_rgba(16. / 255., 16. / 255., 16. / 255., 0.95);
+        ctx.paint()?;
+        let mut x = PADDING;
+        for (i, (layout, (advance, _))) in layouts.iter().zip(sizes).enumerate() {
+            if i == 0 {
+                ctx.set_source_rgb(44. / 255., 171. / 255., 99. / 255.);
+            } else {
+                ctx.set_source_rgb(1., 1., 1.);
+            }
+            ctx.move_to(x, PADDING);
+            // The layouts were made for the scratch context.
+            pangocairo::functions::update_layout(&ctx, layout);
+            pangocairo::functions::show_layout(&ctx, layout);
+            x += advance as f64 + GAP;
+        }
+    }
+    surface.flush();
+    let stride = surface.stride();
+    let pixels = surface
+        .data()
+        .map_err(|_| cairo::Error::SurfaceFinished)?
+        .to_vec();
+    Ok((width, height, stride, pixels))
+}
+
+/// Buffers are only needed until the compositor has them.
+impl Dispatch<WlBuffer, ()> for SessionState {
+    fn event(
+        _: &mut Self,
+        buffer: &WlBuffer,
+        event: wl_buffer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    }
+}
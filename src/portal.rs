@@ -0,0 +1,112 @@
+//! Calling `xdg-desktop-portal` interfaces, whose
+//! results come back later as a `Response` signal.
+
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use relm4::gtk::{
+    gio::{self, prelude::DBusProxyExt},
+    glib::{self, object::ObjectExt, variant::ToVariant},
+};
+use tracing::error;
+
+pub const PORTAL: &str = "org.freedesktop.portal.Desktop";
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const REQUEST: &str = "org.freedesktop.portal.Request";
+
+/// Distinguishes requests made with the same method.
+static REQUESTS: AtomicU32 = AtomicU32::new(0);
+
+/// A proxy for one of the portal's interfaces.
+pub fn proxy(interface: &str) -> Result<gio::DBusProxy, glib::Error> {
+    gio::DBusProxy::for_bus_sync(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::NONE,
+        None,
+        PORTAL,
+        PORTAL_PATH,
+        interface,
+        gio::Cancellable::NONE,
+    )
+}
+
+/// Call a portal method, whose results come later in a `Response`
+/// signal on a request object. The options (the last argument)
+/// get a token so that the request's path is known in advance,
+/// and it can be listened to before the response could arrive.
+/// The results are `None` if the request was denied or cancelled.
+pub fn request(
+    portal: &gio::DBusProxy,
+    method: &str,
+    mut args: Vec<glib::Variant>,
+    options: glib::VariantDict,
+    on_response: impl FnOnce(Option<glib::VariantDict>) + 'static,
+) -> Result<(), glib::Error> {
+    let token = format!(
+        "kway_{}_{}",
+        method.to_lowercase(),
+        REQUESTS.fetch_add(1, Ordering::Relaxed)
+    );
+    options.insert_value("handle_token", &token.to_variant());
+    args.push(options.end());
+
+    // Unique names look like `:1.42`, which become `1_42` in paths.
+    let unique_name = portal
+        .connection()
+        .unique_name()
+        .map(|name| name.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default();
+    let path = format!("{}/request/{}/{}", PORTAL_PATH, unique_name, token);
+    let request = gio::DBusProxy::for_bus_sync(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        PORTAL,
+        &path,
+        REQUEST,
+        gio::Cancellable::NONE,
+    )?;
+
+    // The request is kept alive until it's answered.
+    let pending = RefCell::new(Some(request.clone()));
+    let on_response = RefCell::new(Some(on_response));
+    let method_cb = method.to_string();
+    request.connect_local("g-signal", false, move |args| {
+        let signal = args[2].get::<String>().ok()?;
+        if signal != "Response" {
+            return None;
+        }
+        pending.borrow_mut().take();
+        let params = args[3].get::<glib::Variant>().ok()?;
+        let (response, results) = params.get::<(u32, glib::VariantDict)>()?;
+        let results = match response {
+            0 => Some(results),
+            _ => {
+                error!("Portal request `{}` was denied or cancelled", method_cb);
+                None
+            }
+        };
+        if let Some(on_response) = on_response.borrow_mut().take() {
+            on_response(results);
+        }
+        None
+    });
+
+    portal.call_sync(
+        method,
+        Some(&glib::Variant::tuple_from_iter(args)),
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    )?;
+    Ok(())
+}
+
+/// A session handle as an object path argument.
+pub fn object_path(path: &str) -> Result<glib::Variant, glib::Error> {
+    glib::variant::ObjectPath::try_from(path.to_string())
+        .map(|path| path.to_variant())
+        .map_err(|err| glib::Error::new(gio::IOErrorEnum::InvalidArgument, &err.to_string()))
+}
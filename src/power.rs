@@ -0,0 +1,102 @@
+//! Watch the battery (via UPower) and power profile (via
+//! power-profiles-daemon), to save power when it's running low.
+
+use relm4::{
+    Sender,
+    gtk::{
+        gio::{self, prelude::DBusProxyExt},
+        glib::{self, object::ObjectExt},
+    },
+};
+use tracing::debug;
+
+use crate::{config::PowerSaving, ui::UIMessage};
+
+const UPOWER: &str = "org.freedesktop.UPower";
+
+/// Combines all of the system's batteries.
+const DISPLAY_DEVICE: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+
+const PROFILES: &str = "net.hadess.PowerProfiles";
+const PROFILES_PATH: &str = "/net/hadess/PowerProfiles";
+
+/// UPower's device state while running on battery.
+const DISCHARGING: u32 = 2;
+
+/// The proxies have to be kept alive to keep watching.
+pub struct Watch {
+    _battery: gio::DBusProxy,
+    _profiles: gio::DBusProxy,
+}
+
+/// Send [`UIMessage::SavePower`] now and whenever the battery
+/// or power profile changes, with whether to save power.
+pub fn watch(options: PowerSaving, sender: Sender<UIMessage>) -> Result<Watch, glib::Error> {
+    let battery = proxy(UPOWER, DISPLAY_DEVICE, "org.freedesktop.UPower.Device")?;
+    let profiles = proxy(PROFILES, PROFILES_PATH, PROFILES)?;
+
+    let update = {
+        let battery = battery.clone();
+        let profiles = profiles.clone();
+        move || {
+            let save = saving_power(&options, &battery, &profiles);
+            sender.emit(UIMessage::SavePower(save));
+        }
+    };
+    update();
+    for proxy in [&battery, &profiles] {
+        let update = update.clone();
+        proxy.connect_local("g-properties-changed", false, move |_| {
+            update();
+            None
+        });
+    }
+
+    Ok(Watch {
+        _battery: battery,
+        _profiles: profiles,
+    })
+}
+
+/// If either service isn't running its
+/// properties are missing, and ignored.
+fn saving_power(
+    options: &PowerSaving,
+    battery: &gio::DBusProxy,
+    profiles: &gio::DBusProxy,
+) -> bool {
+    let discharging = battery
+        .cached_property("State")
+        .and_then(|state| state.get::<u32>())
+        == Some(DISCHARGING);
+    let percentage = battery
+        .cached_property("Percentage")
+        .and_then(|percentage| percentage.get::<f64>());
+    let low_battery = match (options.low_battery, percentage) {
+        (Some(threshold), Some(percentage)) => discharging && percentage <= threshold as f64,
+        _ => false,
+    };
+
+    let profile = profiles
+        .cached_property("ActiveProfile")
+        .and_then(|profile| profile.get::<String>());
+    let power_saver = options.power_saver && profile.as_deref() == Some("power-saver");
+
+    debug!(
+        "[Power] Battery: {:?}% (discharging: {}), profile: {:?}",
+        percentage, discharging, profile
+    );
+    low_battery || power_saver
+}
+
+fn proxy(name: &str, path: &str, interface: &str) -> Result<gio::DBusProxy, glib::Error> {
+    gio::DBusProxy::for_bus_sync(
+        gio::BusType::System,
+        gio::DBusProxyFlags::NONE,
+        None,
+        name,
+        path,
+        interface,
+        gio::Cancellable::NONE,
+    )
+}
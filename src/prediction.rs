@@ -0,0 +1,203 @@
+//! Word predictions for the suggestion bar, and
+//! corrections for autocorrect, from a language's word list.
+
+use std::path::Path;
+
+use tracing::error;
+
+pub struct Dictionary {
+    /// Most common first.
+    words: Vec<String>,
+    /// The words lowercased, with their index in `words`,
+    /// sorted so words with a prefix are next to each other.
+    index: Vec<(String, usize)>,
+    /// The letters words are made of, to try in corrections.
+    letters: Vec<char>,
+}
+impl Dictionary {
+    /// Load a word list, one word per line, most common first.
+    /// If it can't be read (which is logged) it's empty.
+    pub fn load(path: &Path) -> Self {
+        let words: Vec<String> = match fs_err::read_to_string(path) {
+            Ok(text) => text
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect(),
+            Err(err) => {
+                error!("Failed to load dictionary: {}", err);
+                vec![]
+            }
+        };
+        Self::new(words)
+    }
+
+    /// From words, most common first.
+    fn new(words: Vec<String>) -> Self {
+        let mut index: Vec<_> = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| (word.to_lowercase(), i))
+            .collect();
+        index.sort_unstable();
+        let mut letters: Vec<_> = index
+            .iter()
+            .flat_map(|(word, _)| word.chars())
+            .filter(|c| c.is_alphabetic())
+            .collect();
+        letters.sort_unstable();
+        letters.dedup();
+        Self {
+            words,
+            index,
+            letters,
+        }
+    }
+
+    /// The rank of a (lowercased) word, if it's known.
+    fn rank(&self, lower: &str) -> Option<usize> {
+        let i = self
+            .index
+            .binary_search_by(|(word, _)| word.as_str().cmp(lower))
+            .ok()?;
+        Some(self.index[i].1)
+    }
+
+    /// The most common word one edit (a deleted, inserted,
+    /// replaced, or swapped letter) away, if the word isn't known.
+    pub fn correct(&self, word: &str) -> Option<String> {
+        if word.chars().count() < 2 || !word.chars().all(char::is_alphabetic) {
+            return None;
+        }
+        let lower = word.to_lowercase();
+        if self.rank(&lower).is_some() {
+            return None;
+        }
+        let chars: Vec<char> = lower.chars().collect();
+        let mut edits = vec![];
+        for i in 0..=chars.len() {
+            let (head, tail) = chars.split_at(i);
+            if let Some((_, rest)) = tail.split_first() {
+                edits.push(head.iter().chain(rest).collect::<String>());
+            }
+            if let [a, b, rest @ ..] = tail {
+                edits.push(head.iter().chain([b, a]).chain(rest).collect());
+            }
+            for letter in &self.letters {
+                if let Some((_, rest)) = tail.split_first() {
+                    edits.push(head.iter().chain([letter]).chain(rest).collect());
+                }
+                edits.push(head.iter().chain([letter]).chain(tail).collect());
+            }
+        }
+        let best = edits.iter().filter_map(|edit| self.rank(edit)).min()?;
+        let capitalize = word.chars().next().is_some_and(char::is_uppercase);
+        let correction = &self.words[best];
+        Some(if capitalize {
+            capitalized(correction)
+        } else {
+            correction.clone()
+        })
+    }
+
+    /// Words starting with the prefix, most common first.
+    /// Matching ignores case, and a capitalized prefix
+    /// gives capitalized words.
+    pub fn predict(&self, prefix: &str, limit: usize) -> Vec<String> {
+        if !prefix.chars().all(char::is_alphabetic) {
+            return vec![];
+        }
+        let lower = prefix.to_lowercase();
+        let capitalize = prefix.chars().next().is_some_and(char::is_uppercase);
+        let start = self
+            .index
+            .partition_point(|(word, _)| word.as_str() < lower.as_str());
+        let mut matches: Vec<usize> = self.index[start..]
+            .iter()
+            .take_while(|(word, _)| word.starts_with(&lower))
+            .filter(|(word, _)| word.len() > lower.len())
+            .map(|(_, i)| *i)
+            .collect();
+        matches.sort_unstable();
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|i| &self.words[i])
+            .map(|word| {
+                if capitalize {
+                    capitalized(word)
+                } else {
+                    word.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(words: &[&str]) -> Dictionary {
+        Dictionary::new(words.iter().map(|word| word.to_string()).collect())
+    }
+
+    #[test]
+    fn corrects_single_edits() {
+        let dict = dictionary(&["the", "hello", "world", "word"]);
+        assert_eq!(dict.correct("teh"), Some("the".into()));
+        assert_eq!(dict.correct("wrld"), Some("world".into()));
+        assert_eq!(dict.correct("wodr"), Some("word".into()));
+        assert_eq!(dict.correct("hellp"), Some("hello".into()));
+        assert_eq!(dict.correct("helloo"), Some("hello".into()));
+    }
+
+    #[test]
+    fn corrects_to_the_most_common() {
+        let dict = dictionary(&["hello", "help"]);
+        assert_eq!(dict.correct("helo"), Some("hello".into()));
+        let dict = dictionary(&["help", "hello"]);
+        assert_eq!(dict.correct("helo"), Some("help".into()));
+    }
+
+    #[test]
+    fn leaves_known_and_unfixable_words() {
+        let dict = dictionary(&["the", "word"]);
+        assert_eq!(dict.correct("word"), None);
+        assert_eq!(dict.correct("Word"), None);
+        assert_eq!(dict.correct("xyzzy"), None);
+        assert_eq!(dict.correct("t"), None);
+        assert_eq!(dict.correct("th3"), None);
+    }
+
+    #[test]
+    fn corrects_keeping_capitals() {
+        let dict = dictionary(&["the"]);
+        assert_eq!(dict.correct("Teh"), Some("The".into()));
+    }
+
+    #[test]
+    fn predicts_completions_most_common_first() {
+        let dict = dictionary(&["the", "then", "they", "hello", "them"]);
+        assert_eq!(dict.predict("the", 5), ["then", "they", "them"]);
+        assert_eq!(dict.predict("the", 2), ["then", "they"]);
+        assert_eq!(dict.predict("he", 5), ["hello"]);
+        assert!(dict.predict("x", 5).is_empty());
+        assert!(dict.predict("th3", 5).is_empty());
+    }
+
+    #[test]
+    fn predicts_keeping_capitals() {
+        let dict = dictionary(&["then", "they"]);
+        assert_eq!(dict.predict("Th", 5), ["Then", "They"]);
+        assert_eq!(dict.predict("TH", 5), ["Then", "They"]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Script keys run a [Rhai](https://rhai.rs) script, giving it
+//! the keyboard's state and acting on what it asks for.
+//!
+//! The state is in these constants:
+//! - `layers`: the active layers, as `[left, right]`
+//! - `modifiers`: the held modifiers, e.g. `["Ctrl", "Shift"]`
+//! - `text_before`, `text_after`: the text around the cursor
+//! - `selection`: the selected text
+//! - `args`: the key's `args`
+//!
+//! The text is empty without the input method,
+//! and in password and PIN fields.
+//!
+//! And these functions act on the keyboard:
+//! - `insert(text)`: insert the text
+//! - `key(chord)`: press a key, e.g. `key("ctrl+shift+KEY_Z")`
+//! - `click(button)`: click `"left"`, `"middle"`, or `"right"`
+//! - `move_pointer(dx, dy)`: move the pointer
+//! - `scroll(dir)`: scroll `"up"`, `"down"`, `"left"`, or `"right"`
+//! - `layer(side, n)`: switch `"left"` or `"right"` layers
+//! - `toast(text)`: briefly show a message
+//!
+//! What's asked for is done once the script finishes.
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position, Scope};
+
+use crate::{
+    layout::{Modifier, Side},
+    plugin::Effect,
+    pointer::{PointerButton, PointerMessage},
+    session::SurroundingText,
+};
+
+/// The keyboard's state, for a script to act on.
+#[derive(Debug, Default)]
+pub struct State {
+    pub layers: (usize, usize),
+    pub modifiers: Vec<Modifier>,
+
+    /// Withheld for password and PIN fields.
+    pub text: Option<SurroundingText>,
+}
+impl State {
+    fn scope(&self, args: &[String]) -> Scope<'static> {
+        let mut scope = Scope::new();
+        let layers: Array = vec![(self.layers.0 as i64).into(), (self.layers.1 as i64).into()];
+        let modifiers: Array = self
+            .modifiers
+            .iter()
+            .map(|m| format!("{:?}", m).into())
+            .collect();
+        let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+        scope.push_constant("layers", layers);
+        scope.push_constant("modifiers", modifiers);
+        scope.push_constant("args", args);
+
+        let (before, after, selection) = match &self.text {
+            Some(text) => {
+                let start = text.cursor.min(text.anchor) as usize;
+                let end = text.cursor.max(text.anchor) as usize;
+                let part = |range: std::ops::Range<usize>| {
+                    text.text.get(range).unwrap_or_default().to_string()
+                };
+                (part(0..start), part(end..text.text.len()), part(start..end))
+            }
+            None => Default::default(),
+        };
+        scope.push_constant("text_before", before);
+        scope.push_constant("text_after", after);
+        scope.push_constant("selection", selection);
+        scope
+    }
+}
+
+/// Run a script, returning what it asked for. It's given
+/// up on if it runs for longer than the timeout.
+/// This blocks, so it should be called in another thread.
+pub fn run(
+    source: &str,
+    args: &[String],
+    state: &State,
+    timeout: Duration,
+) -> Result<Vec<Effect>, String> {
+    let effects: Rc<RefCell<Vec<Effect>>> = Rc::default();
+    let mut engine = Engine::new();
+
+    let started = Instant::now();
+    engine.on_progress(move |_| (started.elapsed() > timeout).then(|| "timed out".into()));
+
+    let push = |effects: &Rc<RefCell<Vec<Effect>>>| {
+        let effects = effects.clone();
+        move |effect: Option<Effect>, what: &str| match effect {
+            Some(effect) => {
+                effects.borrow_mut().push(effect);
+                Ok(())
+            }
+            None => Err(invalid(what)),
+        }
+    };
+
+    let emit = push(&effects);
+    engine.register_fn("insert", move |text: &str| {
+        emit(Some(Effect::Insert(text.into())), text)
+    });
+    let emit = push(&effects);
+    engine.register_fn("toast", move |text: &str| {
+        emit(Some(Effect::Toast(text.into())), text)
+    });
+    let emit = push(&effects);
+    engine.register_fn("key", move |chord: &str| emit(parse_chord(chord), chord));
+    let emit = push(&effects);
+    engine.register_fn("click", move |button: &str| {
+        let button = match button {
+            "left" => Some(PointerButton::Left),
+            "middle" => Some(PointerButton::Middle),
+            "right" => Some(PointerButton::Right),
+            _ => None,
+        };
+        emit(button.map(Effect::Click), "button")
+    });
+    let emit = push(&effects);
+    engine.register_fn("move_pointer", move |dx: i64, dy: i64| {
+        let effect = Effect::Pointer(PointerMessage::Move(dx as i32, dy as i32));
+        emit(Some(effect), "movement")
+    });
+    let emit = push(&effects);
+    engine.register_fn("scroll", move |dir: &str| {
+        let scroll = match dir {
+            "up" => Some(PointerMessage::ScrollUp),
+            "down" => Some(PointerMessage::ScrollDown),
+            "left" => Some(PointerMessage::ScrollLeft),
+            "right" => Some(PointerMessage::ScrollRight),
+            _ => None,
+        };
+        emit(scroll.map(Effect::Pointer), dir)
+    });
+    let emit = push(&effects);
+    engine.register_fn("layer", move |side: &str, n: i64| {
+        let side = match side {
+            "left" => Some(Side::Left),
+            "right" => Some(Side::Right),
+            _ => None,
+        };
+        let layer = side.zip(usize::try_from(n).ok());
+        emit(layer.map(|(side, n)| Effect::Layer(side, n)), "layer")
+    });
+
+    let mut scope = state.scope(args);
+    engine
+        .run_with_scope(&mut scope, source)
+        .map_err(|err| err.to_string())?;
+    Ok(effects.take())
+}
+
+fn invalid(what: &str) -> Box<EvalAltResult> {
+    EvalAltResult::ErrorRuntime(format!("invalid: {}", what).into(), Position::NONE).into()
+}
+
+/// A chord such as `ctrl+shift+KEY_Z`.
+fn parse_chord(chord: &str) -> Option<Effect> {
+    let mut parts: Vec<_> = chord.trim().split('+').collect();
+    let key = serde_yaml::from_str(parts.pop()?).ok()?;
+    let modifiers = parts
+        .into_iter()
+        .map(|part| match part.to_lowercase().as_str() {
+            "alt" => Some(Modifier::Alt),
+            "ctrl" => Some(Modifier::Ctrl),
+            "shift" => Some(Modifier::Shift),
+            "meta" | "super" => Some(Modifier::Meta),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    Some(Effect::Key(modifiers, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev::Key;
+
+    use super::*;
+
+    fn chord(chord: &str) -> Option<(Vec<Modifier>, Key)> {
+        match parse_chord(chord)? {
+            Effect::Key(modifiers, key) => Some((modifiers, key)),
+            effect => panic!("Not a key: {:?}", effect),
+        }
+    }
+
+    #[test]
+    fn chords() {
+        assert_eq!(
+            chord("ctrl+shift+KEY_Z"),
+            Some((vec![Modifier::Ctrl, Modifier::Shift], Key::KEY_Z))
+        );
+        assert_eq!(
+            chord(" Super+Alt+KEY_TAB "),
+            Some((vec![Modifier::Meta, Modifier::Alt], Key::KEY_TAB))
+        );
+        assert_eq!(chord("KEY_ENTER"), Some((vec![], Key::KEY_ENTER)));
+    }
+
+    #[test]
+    fn invalid_chords() {
+        assert_eq!(chord("hyper+KEY_A"), None);
+        assert_eq!(chord("ctrl+"), None);
+        assert_eq!(chord("ctrl+KEY_NOPE"), None);
+        assert_eq!(chord(""), None);
+    }
+
+    #[test]
+    fn scripts_see_the_text() {
+        let state = State {
+            text: Some(SurroundingText {
+                text: "one two three".into(),
+                cursor: 4,
+                anchor: 7,
+            }),
+            ..Default::default()
+        };
+        let source = r#"insert(text_before + "|" + selection + "|" + text_after)"#;
+        let effects = run(source, &[], &state, Duration::from_secs(1)).unwrap();
+        assert!(matches!(
+            effects.as_slice(),
+            [Effect::Insert(text)] if text == "one |two| three"
+        ));
+    }
+
+    #[test]
+    fn scripts_fail_on_invalid_requests() {
+        let state = State::default();
+        let run = |source| run(source, &[], &state, Duration::from_secs(1));
+        assert!(run(r#"key("ctrl+KEY_NOPE")"#).is_err());
+        assert!(run(r#"click("back")"#).is_err());
+        assert!(run(r#"layer("left", -1)"#).is_err());
+        assert!(matches!(
+            run(r#"layer("right", 2)"#).as_deref(),
+            Ok([Effect::Layer(Side::Right, 2)])
+        ));
+    }
+
+    #[test]
+    fn scripts_time_out() {
+        let err = run("loop {}", &[], &State::default(), Duration::from_millis(10)).unwrap_err();
+        assert!(err.contains("terminated"), "{}", err);
+    }
+}
@@ -0,0 +1,98 @@
+//! Secrets (e.g. passwords) to type, fetched from a secret store.
+//!
+//! Secrets are never logged, and kway's own copies are wiped from
+//! memory once typed. Characters that aren't on the keymap are
+//! typed as text through the input method instead, and the copies
+//! left in the Wayland connection's buffers aren't wiped.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    sync::atomic::{Ordering, compiler_fence},
+};
+
+use serde::Deserialize;
+
+/// Where to fetch a secret from.
+#[derive(Debug, Clone, Deserialize)]
+pub enum SecretSource {
+    /// An entry in the `pass` password store;
+    /// its first line is the secret.
+    Pass(String),
+
+    /// A Secret Service item (e.g. GNOME Keyring, KeePassXC),
+    /// looked up by its attributes with `secret-tool`.
+    SecretService(HashMap<String, String>),
+}
+impl SecretSource {
+    /// The command that prints the secret.
+    pub fn command(&self) -> (&'static str, Vec<String>) {
+        match self {
+            Self::Pass(entry) => ("pass", vec!["show".into(), entry.clone()]),
+            Self::SecretService(attrs) => {
+                let args = std::iter::once("lookup".to_string())
+                    .chain(
+                        attrs
+                            .iter()
+                            .flat_map(|(key, val)| [key.clone(), val.clone()]),
+                    )
+                    .collect();
+                ("secret-tool", args)
+            }
+        }
+    }
+}
+
+pub struct Secret(Vec<u8>);
+impl Secret {
+    /// Read a command's output, taking its first line as the secret.
+    /// It's read straight into buffers that are wiped, including
+    /// those left behind as the secret grows.
+    pub fn read(mut output: impl Read) -> io::Result<Option<Self>> {
+        let mut secret = Self(Vec::with_capacity(256));
+        let mut chunk = Self(vec![0; 256]);
+        loop {
+            let read = match output.read(&mut chunk.0) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            let len = secret.0.len() + read;
+            if len > secret.0.capacity() {
+                let mut grown = Self(Vec::with_capacity(len * 2));
+                grown.0.extend_from_slice(&secret.0);
+                secret = grown;
+            }
+            secret.0.extend_from_slice(&chunk.0[..read]);
+        }
+
+        if let Some(end) = secret.0.iter().position(|b| *b == b'\n') {
+            secret.0[end..].fill(0);
+            secret.0.truncate(end);
+        }
+        Ok(std::str::from_utf8(&secret.0).is_ok().then_some(secret))
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        std::str::from_utf8(&self.0).unwrap_or_default().chars()
+    }
+}
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // Overwrite the whole allocation, including spare capacity,
+        // in a way that won't be optimized out.
+        let cap = self.0.capacity();
+        self.0.resize(cap, 0);
+        for byte in self.0.iter_mut() {
+            // SAFETY: `byte` is a valid, aligned reference.
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+}
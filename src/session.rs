@@ -1,15 +1,24 @@
-use std::{ffi::CString, fs::File, io::Write, os::fd::AsFd, path::PathBuf};
+use std::{
+    collections::HashMap, ffi::CString, fs::File, io::Write, os::fd::AsFd, path::PathBuf,
+    sync::OnceLock,
+};
+use tracing::warn;
 use wayland_client::{
     Connection, Dispatch, Proxy, QueueHandle, WEnum,
     protocol::{
+        wl_compositor::WlCompositor,
         wl_keyboard::{self},
         wl_registry,
         wl_seat::{self, WlSeat},
+        wl_shm::WlShm,
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
     },
 };
 use wayland_protocols_misc::zwp_input_method_v2::client::{
     zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
     zwp_input_method_v2::{self, ZwpInputMethodV2},
+    zwp_input_popup_surface_v2::ZwpInputPopupSurfaceV2,
 };
 use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
     zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
@@ -18,6 +27,34 @@ use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
 
 use xkbcommon::xkb;
 
+/// Text around the cursor in the focused text field,
+/// as reported by the input method protocol.
+/// `cursor` and `anchor` are byte offsets into `text`.
+#[derive(Debug, Clone)]
+pub struct SurroundingText {
+    pub text: String,
+    pub cursor: u32,
+    pub anchor: u32,
+}
+impl SurroundingText {
+    /// The currently selected text, if any.
+    pub fn selection(&self) -> Option<&str> {
+        let start = self.cursor.min(self.anchor) as usize;
+        let end = self.cursor.max(self.anchor) as usize;
+        if start == end {
+            None
+        } else {
+            self.text.get(start..end)
+        }
+    }
+}
+
+/// The text-input-v3 `content_purpose` for URLs,
+/// which the input method protocol passes through.
+pub const PURPOSE_URL: u32 = 5;
+pub const PURPOSE_PASSWORD: u32 = 8;
+pub const PURPOSE_PIN: u32 = 9;
+
 #[derive(Debug, Default)]
 pub struct SessionState {
     pub keyboard_manager: Option<ZwpVirtualKeyboardManagerV1>,
@@ -25,7 +62,27 @@ pub struct SessionState {
     pub input_manager: Option<ZwpInputMethodManagerV2>,
     pub input: Option<ZwpInputMethodV2>,
     pub input_serial: u32,
+    pub surrounding_text: Option<SurroundingText>,
+
+    /// The text field's state as sent, which
+    /// only takes effect on the next `done`.
+    pending_text: Option<SurroundingText>,
+    pending_purpose: u32,
+
+    /// What the focused text field is for (e.g. URLs),
+    /// as a text-input-v3 `content_purpose`.
+    pub content_purpose: u32,
     pub seat: Option<WlSeat>,
+
+    /// For drawing the candidate popup.
+    pub compositor: Option<WlCompositor>,
+    pub shm: Option<WlShm>,
+
+    /// Whether a text field was focused, pending the
+    /// next `done`, and whether one has been since
+    /// this was last checked.
+    pub activating: bool,
+    pub activated: bool,
 }
 
 impl Dispatch<wl_registry::WlRegistry, ()> for SessionState {
@@ -58,6 +115,15 @@ impl Dispatch<wl_registry::WlRegistry, ()> for SessionState {
                     let seat = registry.bind::<wl_seat::WlSeat, _, _>(name, 1, qh, ());
                     state.seat = Some(seat);
                 }
+                "wl_compositor" => {
+                    let compositor =
+                        registry.bind::<WlCompositor, _, _>(name, version.min(4), qh, ());
+                    state.compositor = Some(compositor);
+                }
+                "wl_shm" => {
+                    let shm = registry.bind::<WlShm, _, _>(name, 1, qh, ());
+                    state.shm = Some(shm);
+                }
                 _ => {}
             }
         }
@@ -82,6 +148,11 @@ macro_rules! impl_dispatch {
 impl_dispatch!(ZwpVirtualKeyboardManagerV1);
 impl_dispatch!(ZwpVirtualKeyboardV1);
 impl_dispatch!(ZwpInputMethodManagerV2);
+impl_dispatch!(ZwpInputPopupSurfaceV2);
+impl_dispatch!(WlCompositor);
+impl_dispatch!(WlShm);
+impl_dispatch!(WlShmPool);
+impl_dispatch!(WlSurface);
 
 impl Dispatch<WlSeat, ()> for SessionState {
     fn event(
@@ -130,38 +201,156 @@ impl Dispatch<ZwpInputMethodV2, ()> for SessionState {
             zwp_input_method_v2::Event::Activate => {
                 // debug!("[Input]: Activated");
                 state.input_serial = 0;
+                state.activating = true;
+                state.pending_text = None;
+                state.pending_purpose = 0;
+            }
+            zwp_input_method_v2::Event::Deactivate => {
+                state.activating = false;
+                state.pending_text = None;
+                state.pending_purpose = 0;
+            }
+            zwp_input_method_v2::Event::ContentType { purpose, .. } => {
+                state.pending_purpose = purpose.into();
+            }
+            zwp_input_method_v2::Event::SurroundingText {
+                text,
+                cursor,
+                anchor,
+            } => {
+                state.pending_text = Some(SurroundingText {
+                    text,
+                    cursor,
+                    anchor,
+                });
             }
             zwp_input_method_v2::Event::Done => {
                 state.input_serial = state.input_serial.wrapping_add(1);
+                state.surrounding_text = state.pending_text.clone();
+                state.content_purpose = state.pending_purpose;
+                if state.activating {
+                    state.activating = false;
+                    state.activated = true;
+                }
             }
             _ => {}
         }
     }
 }
 
-// NOTE: This assumes US layout.
-fn default_keymap() -> xkb::State {
+/// The virtual keyboard's keymap, along with lookups derived from it.
+/// This is built once and shared, as compiling it is relatively slow.
+pub struct Keymap {
+    /// The keymap in the text format it's uploaded in.
+    text: CString,
+
+    /// The layout's name, e.g. "English (US)".
+    pub language: String,
+
+    /// Which key types each character,
+    /// and whether it needs Shift.
+    pub chars: HashMap<char, (evdev::Key, bool)>,
+
+    /// What each key (by evdev code) types without modifiers.
+    glyphs: HashMap<u16, String>,
+
+    /// What each key (by evdev code) types with Shift.
+    shifted: HashMap<u16, String>,
+}
+impl Keymap {
+    fn new() -> Self {
+        let keymap = default_keymap();
+        let language = keymap.layout_get_name(0).to_string();
+        let text = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        let text = CString::new(text).expect("Keymap should not contain interior nul bytes");
+
+        let mut chars = HashMap::new();
+        let mut glyphs = HashMap::new();
+        let mut shifted = HashMap::new();
+        keymap.key_for_each(|keymap, keycode| {
+            // xkb keycodes are offset from evdev's by 8.
+            let key = evdev::Key::new((keycode.raw() - 8) as u16);
+            let levels = keymap.num_levels_for_key(keycode, 0).min(2);
+            for level in 0..levels {
+                for sym in keymap.key_get_syms_by_level(keycode, 0, level) {
+                    let c = char::from_u32(xkb::keysym_to_utf32(*sym));
+                    if let Some(c) = c.filter(|c| !c.is_control()) {
+                        chars.entry(c).or_insert((key, level == 1));
+                        let glyphs = if level == 0 {
+                            &mut glyphs
+                        } else {
+                            &mut shifted
+                        };
+                        glyphs.entry(key.code()).or_insert_with(|| c.to_string());
+                    }
+                }
+            }
+        });
+
+        Self {
+            text,
+            language,
+            chars,
+            glyphs,
+            shifted,
+        }
+    }
+
+    /// What the key types without modifiers, if it types a character.
+    pub fn glyph(&self, key: &evdev::Key) -> Option<&str> {
+        self.glyphs.get(&key.code()).map(String::as_str)
+    }
+
+    /// What the key types with Shift, if it types a character.
+    pub fn shifted_glyph(&self, key: &evdev::Key) -> Option<&str> {
+        self.shifted.get(&key.code()).map(String::as_str)
+    }
+}
+
+pub fn keymap() -> &'static Keymap {
+    static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+    KEYMAP.get_or_init(Keymap::new)
+}
+
+/// The xkb layout the keymap is built from, e.g. "de" or "fr(bepo)".
+static XKB_LAYOUT: OnceLock<String> = OnceLock::new();
+
+/// Use this xkb layout instead of US.
+/// This has to be set before the keymap is first used.
+pub fn use_xkb_layout(layout: String) {
+    if XKB_LAYOUT.set(layout).is_err() {
+        warn!("The keymap's layout was already set");
+    }
+}
+
+fn default_keymap() -> xkb::Keymap {
     let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-    let keymap = xkb::Keymap::new_from_names(
-        &context,
-        "",
-        "",
-        "us",
-        "",
-        None,
-        xkb::KEYMAP_COMPILE_NO_FLAGS,
-    )
-    .expect("xkbcommon keymap panicked!");
-    xkb::State::new(&keymap)
+    let compile = |layout: &str, variant: &str| {
+        xkb::Keymap::new_from_names(
+            &context,
+            "",
+            "",
+            layout,
+            variant,
+            None,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+    };
+    let name = XKB_LAYOUT.get().map_or("us", String::as_str);
+    let (layout, variant) = name
+        .strip_suffix(')')
+        .and_then(|name| name.split_once('('))
+        .unwrap_or((name, ""));
+    compile(layout, variant)
+        .or_else(|| {
+            warn!("Unknown keyboard layout {:?}, using US", name);
+            compile("us", "")
+        })
+        .expect("xkbcommon keymap panicked!")
 }
 
 pub fn get_keymap_as_file() -> (File, u32) {
-    let xkb_state = default_keymap();
-    let keymap = xkb_state
-        .get_keymap()
-        .get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
-    let keymap = CString::new(keymap).expect("Keymap should not contain interior nul bytes");
-    let keymap = keymap.as_bytes_with_nul();
+    let keymap = keymap().text.as_bytes_with_nul();
     let dir = std::env::var_os("XDG_RUNTIME_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(std::env::temp_dir);
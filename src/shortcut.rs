@@ -0,0 +1,78 @@
+//! Bind a global shortcut to show or hide the keyboard,
+//! via the `xdg-desktop-portal` GlobalShortcuts interface.
+//! The desktop may ask the user to confirm the shortcut
+//! or pick another, and remembers it for next time.
+
+use relm4::{
+    Sender,
+    gtk::{
+        gio,
+        glib::{self, object::ObjectExt, variant::ToVariant},
+    },
+};
+use tracing::{debug, error};
+
+use crate::{
+    portal::{self, request},
+    ui::UIMessage,
+};
+
+const SHORTCUTS: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+/// The id of the one shortcut we bind.
+const TOGGLE: &str = "toggle";
+
+/// Bind the shortcut, suggesting `trigger` (e.g. `LOGO+k`), and
+/// send [`UIMessage::ToggleKeyboard`] when it's pressed.
+/// The returned proxy has to be kept alive to keep listening.
+pub fn bind(trigger: &str, sender: Sender<UIMessage>) -> Result<gio::DBusProxy, glib::Error> {
+    let portal = portal::proxy(SHORTCUTS)?;
+
+    portal.connect_local("g-signal", false, move |args| {
+        let signal = args[2].get::<String>().ok()?;
+        let params = args[3].get::<glib::Variant>().ok()?;
+        let id = params.try_child_value(1)?;
+        if signal == "Activated" && id.str() == Some(TOGGLE) {
+            debug!("[Shortcut] Activated");
+            sender.emit(UIMessage::ToggleKeyboard);
+        }
+        None
+    });
+
+    let options = glib::VariantDict::new(None);
+    options.insert_value("session_handle_token", &"kway".to_variant());
+    let portal_cb = portal.clone();
+    let trigger = trigger.to_string();
+    request(&portal, "CreateSession", vec![], options, move |results| {
+        let Some(session) = results
+            .and_then(|results| results.lookup_value("session_handle", None))
+            .and_then(|handle| handle.str().map(str::to_string))
+        else {
+            error!("Failed to create a global shortcuts session");
+            return;
+        };
+        if let Err(err) = bind_shortcut(&portal_cb, &session, &trigger) {
+            error!("Failed to bind the global shortcut: {}", err);
+        }
+    })?;
+    Ok(portal)
+}
+
+fn bind_shortcut(portal: &gio::DBusProxy, session: &str, trigger: &str) -> Result<(), glib::Error> {
+    let props = glib::VariantDict::new(None);
+    props.insert_value("description", &"Show or hide the keyboard".to_variant());
+    props.insert_value("preferred_trigger", &trigger.to_variant());
+    let shortcut = glib::Variant::tuple_from_iter([TOGGLE.to_variant(), props.end()]);
+    let shortcuts = glib::Variant::array_from_iter_with_type(shortcut.type_(), [shortcut.clone()]);
+    let args = vec![portal::object_path(session)?, shortcuts, "".to_variant()];
+    request(
+        portal,
+        "BindShortcuts",
+        args,
+        glib::VariantDict::new(None),
+        |results| {
+            let bound = results.and_then(|results| results.lookup_value("shortcuts", None));
+            debug!("[Shortcut] Bound: {:?}", bound);
+        },
+    )
+}
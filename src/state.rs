@@ -0,0 +1,74 @@
+//! Runtime state (whether the keyboard is shown, which layers
+//! are active, etc.), kept across restarts so that the keyboard
+//! comes back as it was left, e.g. after a reboot.
+//! Unlike the config this is only written by kway itself.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::config::Settings;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub visible: bool,
+
+    /// The layout switched to at runtime (e.g. by
+    /// a language or profile), if any.
+    pub layout: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub language: Option<String>,
+
+    /// The active layer of each half.
+    pub layers: (usize, usize),
+
+    /// The keyboard's opacity, if it was faded.
+    pub opacity: Option<f64>,
+
+    /// Settings changed from the keyboard, which
+    /// apply over the config file's.
+    pub settings: Settings,
+}
+impl State {
+    /// `$XDG_STATE_HOME/kway/state.yml`,
+    /// falling back to `~/.local/state/kway/state.yml`.
+    fn path() -> PathBuf {
+        let dir = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })
+            .unwrap_or_else(std::env::temp_dir);
+        dir.join("kway").join("state.yml")
+    }
+
+    /// Load the saved state. If there is none (or it can't
+    /// be read) the keyboard starts as it normally would.
+    pub fn load() -> Self {
+        let Ok(s) = fs_err::read_to_string(Self::path()) else {
+            return State::default();
+        };
+        serde_yaml::from_str(&s)
+            .inspect_err(|err| error!("Ignoring invalid saved state: {}", err))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = self.try_save() {
+            error!("Failed to save state: {}", err);
+        }
+    }
+
+    fn try_save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+        let state = serde_yaml::to_string(self)?;
+        fs_err::write(&path, state)?;
+        Ok(())
+    }
+}
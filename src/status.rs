@@ -0,0 +1,187 @@
+//! Report the running instance's state for status bars,
+//! as waybar custom module JSON, e.g.:
+//!
+//! ```json
+//! "custom/kway": {
+//!     "exec": "kway status --follow",
+//!     "return-type": "json",
+//!     "on-click": "kway --msg show"
+//! }
+//! ```
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+use crate::{
+    events::{EVENTS_OFF, Status, StatusUpdate},
+    ipc,
+};
+
+/// How long to wait before reconnecting
+/// to an instance that's gone away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Print the status once or, if following,
+/// on every change. Returns whether it succeeded.
+pub fn run(follow: bool) -> bool {
+    if !follow {
+        return match snapshot() {
+            Ok(status) => {
+                println!("{}", waybar(Some(&status)));
+                true
+            }
+            Err(err) => {
+                eprintln!("Couldn't get the status: {}", err);
+                false
+            }
+        };
+    }
+
+    // Keep going across restarts of kway,
+    // so that the bar module doesn't need restarting.
+    loop {
+        match stream() {
+            Err(err) if err.to_string() == EVENTS_OFF => {
+                eprintln!("kway is running, but {}", EVENTS_OFF);
+                println!("{}", events_off());
+            }
+            result => {
+                if let Err(err) = result {
+                    eprintln!("{}", err);
+                }
+                println!("{}", waybar(None));
+            }
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+fn snapshot() -> Result<Status> {
+    let response = ipc::send("status")?;
+    let event: StatusUpdate = serde_json::from_str(&response)?;
+    let mut status = Status::default();
+    status.update(event);
+    Ok(status)
+}
+
+/// Print the status on every event, until the connection closes.
+fn stream() -> Result<()> {
+    let mut stream = UnixStream::connect(ipc::socket_path()?)?;
+    writeln!(stream, "subscribe")?;
+
+    let mut status = Status::default();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if let Some(err) = line.strip_prefix("error: ") {
+            return Err(anyhow!("{}", err));
+        }
+        let Ok(event) = serde_json::from_str::<StatusUpdate>(&line) else {
+            continue;
+        };
+        status.update(event);
+        println!("{}", waybar(Some(&status)));
+    }
+    Ok(())
+}
+
+/// A waybar custom module's JSON.
+#[derive(Serialize)]
+struct Waybar<'a> {
+    text: &'a str,
+    alt: &'a str,
+    class: &'a str,
+    tooltip: &'a str,
+}
+impl Waybar<'_> {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Waybar modules are valid JSON")
+    }
+}
+
+/// The waybar module for an instance that isn't sending events.
+fn events_off() -> String {
+    let tooltip = format!("kway is running, but {}", EVENTS_OFF);
+    Waybar {
+        text: "⌨",
+        alt: "no-events",
+        class: "no-events",
+        tooltip: &tooltip,
+    }
+    .to_json()
+}
+
+/// The status as a waybar module, or
+/// as not running if there's no status.
+fn waybar(status: Option<&Status>) -> String {
+    let Some(status) = status else {
+        return Waybar {
+            text: "",
+            alt: "stopped",
+            class: "stopped",
+            tooltip: "kway isn't running",
+        }
+        .to_json();
+    };
+    let state = if status.visible { "shown" } else { "hidden" };
+    let text = if status.modifiers.is_empty() {
+        "⌨".to_string()
+    } else {
+        format!("⌨ {}", status.modifiers.join("+"))
+    };
+    let tooltip = format!("{}\n{}\n{}", status.left, status.right, status.language);
+    Waybar {
+        text: &text,
+        alt: state,
+        class: state,
+        tooltip: &tooltip,
+    }
+    .to_json()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn module(status: Option<&Status>) -> Value {
+        serde_json::from_str(&waybar(status)).unwrap()
+    }
+
+    #[test]
+    fn not_running() {
+        let module = module(None);
+        assert_eq!(module["class"], "stopped");
+        assert_eq!(module["text"], "");
+    }
+
+    #[test]
+    fn shows_modifiers_and_layers() {
+        let status = Status {
+            visible: true,
+            left: "Left".into(),
+            right: "Right".into(),
+            modifiers: vec!["Ctrl".into(), "Shift".into()],
+            language: "de".into(),
+        };
+        assert_eq!(
+            module(Some(&status)),
+            json!({
+                "text": "⌨ Ctrl+Shift",
+                "alt": "shown",
+                "class": "shown",
+                "tooltip": "Left\nRight\nde"
+            })
+        );
+        let hidden = Status::default();
+        assert_eq!(module(Some(&hidden))["class"], "hidden");
+        assert_eq!(module(Some(&hidden))["text"], "⌨");
+    }
+}
@@ -0,0 +1,76 @@
+//! Text templates for template keys, e.g. `%Y-%m-%d` for today's date.
+//!
+//! Templates are first formatted with `strftime`,
+//! then these placeholders are filled in:
+//! - `{clipboard}`: the clipboard's text (via `wl-paste`)
+//! - `{selection}`: the selected text
+//! - `{selection_len}`: the number of characters selected
+
+use std::{
+    ffi::{CString, c_char},
+    process::Command,
+};
+
+use tracing::error;
+
+/// The largest `strftime` output that's allowed.
+const MAX_FORMATTED: usize = 4096;
+
+/// Fill in a template. This may run `wl-paste`,
+/// so it shouldn't be called on the UI thread.
+pub fn expand(template: &str, selection: Option<&str>) -> String {
+    let mut text = strftime(template);
+    if text.contains("{clipboard}") {
+        text = text.replace("{clipboard}", &clipboard());
+    }
+    let selection = selection.unwrap_or_default();
+    text.replace("{selection_len}", &selection.chars().count().to_string())
+        .replace("{selection}", selection)
+}
+
+/// Format the current local time.
+fn strftime(format: &str) -> String {
+    let Ok(c_format) = CString::new(format) else {
+        return format.to_string();
+    };
+
+    // SAFETY: `tm` is written by `localtime_r` before it's read,
+    // and `strftime` is given the real size of the buffer.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+
+        // A result of 0 means either the buffer was too small or
+        // the output is empty, so try again with more room.
+        let mut size = format.len() * 4 + 64;
+        while size <= MAX_FORMATTED {
+            let mut buf = vec![0u8; size];
+            let len = libc::strftime(
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+                c_format.as_ptr(),
+                &tm,
+            );
+            if len > 0 {
+                buf.truncate(len);
+                return String::from_utf8_lossy(&buf).into_owned();
+            }
+            size *= 2;
+        }
+    }
+    String::new()
+}
+
+fn clipboard() -> String {
+    match Command::new("wl-paste").arg("--no-newline").output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        }
+        Ok(_) => String::new(),
+        Err(err) => {
+            error!("Failed to read the clipboard: {}", err);
+            String::new()
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Transformations of the selected text,
+//! e.g. uppercasing it or wrapping it in quotes.
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Transform {
+    /// Uppercase the selection.
+    Upper,
+
+    /// Lowercase the selection.
+    Lower,
+
+    /// Put text before and after the selection,
+    /// e.g. `Wrap: ["(", ")"]`.
+    Wrap(String, String),
+
+    /// Percent-encode the selection for use in a URL.
+    UrlEncode,
+}
+impl Transform {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Upper => text.to_uppercase(),
+            Self::Lower => text.to_lowercase(),
+            Self::Wrap(before, after) => format!("{}{}{}", before, text, after),
+            Self::UrlEncode => url_encode(text),
+        }
+    }
+}
+
+/// Percent-encode everything but the unreserved characters (RFC 3986).
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,239 @@
+//! Transliteration of Latin key taps into another script,
+//! e.g. Greek or Cyrillic, committed via the input method.
+//!
+//! A table maps Latin sequences to what they type, e.g.:
+//!
+//! ```yaml
+//! a: α
+//! th: θ
+//! ps: ψ
+//! ```
+//!
+//! Sequences are matched as they're typed, so a key that starts
+//! a longer sequence is held back (and shown as preedit text)
+//! until it's clear which one is meant. Capitalizing the first
+//! letter of a sequence capitalizes what it types.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Transliteration {
+    Bundled(BundledTable),
+
+    /// Path to a YAML table.
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum BundledTable {
+    Greek,
+    Cyrillic,
+}
+impl BundledTable {
+    fn yaml(&self) -> &'static str {
+        match self {
+            Self::Greek => include_str!("../assets/translit-greek.yml"),
+            Self::Cyrillic => include_str!("../assets/translit-cyrillic.yml"),
+        }
+    }
+}
+
+pub struct Table(HashMap<String, String>);
+impl Table {
+    /// Load a table, logging and skipping it if it can't be read.
+    pub fn load(source: &Transliteration) -> Self {
+        let text = match source {
+            Transliteration::Bundled(table) => Ok(table.yaml().to_string()),
+            Transliteration::File(path) => {
+                fs_err::read_to_string(path).map_err(|err| err.to_string())
+            }
+        };
+        let entries =
+            text.and_then(|text| serde_yaml::from_str(&text).map_err(|err| err.to_string()));
+        Self(entries.unwrap_or_else(|err| {
+            error!("Failed to load transliteration table: {}", err);
+            HashMap::new()
+        }))
+    }
+
+    /// Whether the character is part of any sequence.
+    pub fn handles(&self, c: char) -> bool {
+        let c = c.to_lowercase().next().unwrap_or(c);
+        self.0.keys().any(|seq| seq.contains(c))
+    }
+
+    /// What the sequence types, if anything.
+    fn get(&self, seq: &str) -> Option<String> {
+        let out = self.0.get(&seq.to_lowercase())?;
+        if seq.starts_with(char::is_uppercase) {
+            let mut chars = out.chars();
+            Some(
+                chars
+                    .next()
+                    .into_iter()
+                    .flat_map(char::to_uppercase)
+                    .chain(chars)
+                    .collect(),
+            )
+        } else {
+            Some(out.clone())
+        }
+    }
+
+    /// Whether a longer sequence starts with this one.
+    fn extends(&self, seq: &str) -> bool {
+        let seq = seq.to_lowercase();
+        self.0
+            .keys()
+            .any(|key| key.len() > seq.len() && key.starts_with(&seq))
+    }
+
+    /// Transliterate text, longest sequences first.
+    /// Anything that isn't part of a sequence is kept as-is.
+    fn convert(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let matched = rest
+                .char_indices()
+                .map(|(i, c)| i + c.len_utf8())
+                .rev()
+                .find_map(|end| Some((end, self.get(&rest[..end])?)));
+            let end = match matched {
+                Some((end, text)) => {
+                    out.push_str(&text);
+                    end
+                }
+                None => {
+                    out.push(c);
+                    c.len_utf8()
+                }
+            };
+            rest = &rest[end..];
+        }
+        out
+    }
+}
+
+/// The Latin sequence being typed.
+#[derive(Debug, Default)]
+pub struct Transliterator {
+    pending: String,
+}
+impl Transliterator {
+    /// Whether a sequence is being held back.
+    pub fn is_composing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Add a typed character, returning the
+    /// text that's ready to commit, if any.
+    pub fn push(&mut self, c: char, table: &Table) -> String {
+        let seq = format!("{}{}", self.pending, c);
+        if table.extends(&seq) {
+            self.pending = seq;
+            return String::new();
+        }
+        if let Some(out) = table.get(&seq) {
+            self.pending.clear();
+            return out;
+        }
+        if self.pending.is_empty() {
+            return table.convert(&seq);
+        }
+
+        // The pending sequence is done;
+        // start again from this character.
+        let mut done = self.take(table);
+        done.push_str(&self.push(c, table));
+        done
+    }
+
+    /// Remove the last typed character.
+    pub fn pop(&mut self) {
+        self.pending.pop();
+    }
+
+    /// What the pending sequence would type.
+    pub fn preview(&self, table: &Table) -> String {
+        table.convert(&self.pending)
+    }
+
+    /// Finish the pending sequence, returning what it types.
+    pub fn take(&mut self, table: &Table) -> String {
+        table.convert(&std::mem::take(&mut self.pending))
+    }
+
+    /// Drop the pending sequence.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let entries = [("a", "α"), ("t", "τ"), ("th", "θ"), ("ps", "ψ"), ("s", "σ")];
+        Table(
+            entries
+                .into_iter()
+                .map(|(seq, out)| (seq.to_string(), out.to_string()))
+                .collect(),
+        )
+    }
+
+    fn type_text(transliterator: &mut Transliterator, text: &str) -> String {
+        let table = table();
+        text.chars()
+            .map(|c| transliterator.push(c, &table))
+            .collect()
+    }
+
+    #[test]
+    fn push() {
+        let table = table();
+        let mut transliterator = Transliterator::default();
+        assert_eq!(transliterator.push('a', &table), "α");
+        assert_eq!(transliterator.push('t', &table), "");
+        assert!(transliterator.is_composing());
+        assert_eq!(transliterator.preview(&table), "τ");
+        assert_eq!(transliterator.push('h', &table), "θ");
+        assert!(!transliterator.is_composing());
+
+        // A held back sequence that doesn't continue is typed.
+        assert_eq!(type_text(&mut transliterator, "ta"), "τα");
+        assert_eq!(type_text(&mut transliterator, "Tha"), "Θα");
+        assert_eq!(type_text(&mut transliterator, "px"), "px");
+    }
+
+    #[test]
+    fn pop_take_and_clear() {
+        let table = table();
+        let mut transliterator = Transliterator::default();
+        type_text(&mut transliterator, "p");
+        transliterator.pop();
+        assert!(!transliterator.is_composing());
+
+        type_text(&mut transliterator, "t");
+        assert_eq!(transliterator.take(&table), "τ");
+        assert!(!transliterator.is_composing());
+
+        type_text(&mut transliterator, "p");
+        transliterator.clear();
+        assert_eq!(transliterator.take(&table), "");
+    }
+
+    #[test]
+    fn convert() {
+        let table = table();
+        assert_eq!(table.convert("psath"), "ψαθ");
+        assert_eq!(table.convert("Tha x"), "Θα x");
+        assert_eq!(table.convert(""), "");
+    }
+}
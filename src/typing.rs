@@ -0,0 +1,65 @@
+//! Watch physical keyboards for typing, so that the
+//! trigger can get out of the way while they're used.
+//!
+//! This reads the keyboards' evdev devices directly, so
+//! (like the pointer) it needs to be in the `input` group.
+//! Only that a key was pressed is passed on, not which.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use evdev::{Device, InputEventKind, Key};
+use relm4::Sender;
+use tracing::{debug, error};
+
+use crate::ui::UIMessage;
+
+/// Presses closer together than this are only reported once.
+const THROTTLE: Duration = Duration::from_millis(250);
+
+pub fn watch(sender: Sender<UIMessage>) {
+    let keyboards: Vec<_> = evdev::enumerate()
+        .map(|(_, device)| device)
+        .filter(is_keyboard)
+        .collect();
+    if keyboards.is_empty() {
+        error!("No keyboards found to watch for typing");
+    }
+    for device in keyboards {
+        debug!("[Typing] Watching: {:?}", device.name());
+        let sender = sender.clone();
+        thread::spawn(move || watch_device(device, sender));
+    }
+}
+
+/// Whether the device is a full keyboard, rather
+/// than e.g. a phone's power and volume buttons.
+pub fn is_keyboard(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::KEY_A) && keys.contains(Key::KEY_SPACE))
+}
+
+fn watch_device(mut device: Device, sender: Sender<UIMessage>) {
+    let name = device.name().unwrap_or_default().to_string();
+    let mut last: Option<Instant> = None;
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(err) => {
+                // E.g. the keyboard was unplugged.
+                debug!("[Typing] Stopped watching {:?}: {}", name, err);
+                return;
+            }
+        };
+        let pressed = events
+            .into_iter()
+            .any(|event| matches!(event.kind(), InputEventKind::Key(_)) && event.value() == 1);
+        if pressed && last.is_none_or(|last| last.elapsed() > THROTTLE) {
+            last = Some(Instant::now());
+            sender.emit(UIMessage::HardwareTyping);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Layer accent colors, applied with CSS generated at runtime.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use tracing::error;
+
+use crate::{keyboard::Keyboard, layout::Layer};
+
+impl Layer {
+    /// A CSS class for the layer's accent color, if it has one.
+    pub fn accent_class(&self) -> Option<String> {
+        let color = self.color.as_ref().filter(|color| is_valid(color))?;
+        let mut hasher = DefaultHasher::new();
+        color.hash(&mut hasher);
+        Some(format!("accent-{:x}", hasher.finish()))
+    }
+}
+
+/// Only allow what CSS colors are made of (e.g. `#4a90d9`,
+/// `rgb(74, 144, 217)`, `teal`), so that a color can't
+/// inject other CSS.
+fn is_valid(color: &str) -> bool {
+    let valid = color
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "#(),.% ".contains(c));
+    if !valid {
+        error!("Invalid layer color: {:?}", color);
+    }
+    valid
+}
+
+/// CSS for the accent colors of the layout's layers.
+/// Keys are outlined with the color, and the
+/// keyboard background is tinted with it.
+pub fn css(keyboard: &Keyboard) -> String {
+    let mut css = String::new();
+    for layer in keyboard.left_layers().chain(keyboard.right_layers()) {
+        if let (Some(class), Some(color)) = (layer.accent_class(), &layer.color) {
+            css.push_str(&format!(
+                ".{class} {{ background-color: alpha({color}, 0.15); }}
+                .{class} button {{ border-color: {color}; }}"
+            ));
+        }
+    }
+    css
+}
@@ -0,0 +1,100 @@
+//! Blur on KDE. KWin has no IPC for layer rules,
+//! so blur is asked for through its blur protocol,
+//! on the window's own surface.
+
+use anyhow::Context;
+use gdk4_wayland::{WaylandSurface, prelude::WaylandSurfaceExtManual};
+use relm4::gtk::{self, prelude::*};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, protocol::wl_registry};
+use wayland_protocols_plasma::blur::client::{
+    org_kde_kwin_blur::OrgKdeKwinBlur, org_kde_kwin_blur_manager::OrgKdeKwinBlurManager,
+};
+
+#[derive(Default)]
+struct State {
+    manager: Option<OrgKdeKwinBlurManager>,
+}
+
+/// Blur what's behind the window. Its surface is replaced
+/// whenever it's mapped, so this has to be done on each map.
+pub fn kde(window: &gtk::Window) -> anyhow::Result<()> {
+    let surface = window
+        .surface()
+        .and_then(|surface| surface.downcast::<WaylandSurface>().ok())
+        .and_then(|surface| surface.wl_surface())
+        .context("The window has no Wayland surface")?;
+    let backend = surface
+        .backend()
+        .upgrade()
+        .context("The Wayland connection is closed")?;
+
+    // GTK's connection is shared, with our own queue.
+    let conn = Connection::from_backend(backend);
+    let mut event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+    conn.display().get_registry(&qh, ());
+    let mut state = State::default();
+    event_queue.roundtrip(&mut state)?;
+    let manager = state
+        .manager
+        .context("The compositor doesn't support blur")?;
+
+    // No region blurs the whole surface.
+    let blur = manager.create(&surface, &qh, ());
+    blur.set_region(None);
+    blur.commit();
+    blur.release();
+    conn.flush()?;
+
+    // Blur is applied on the surface's next commit.
+    window.queue_draw();
+    Ok(())
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<State>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == OrgKdeKwinBlurManager::interface().name => {
+                let manager =
+                    registry.bind::<OrgKdeKwinBlurManager, _, _>(name, version.min(1), qh, ());
+                state.manager = Some(manager);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<OrgKdeKwinBlurManager, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &OrgKdeKwinBlurManager,
+        _: <OrgKdeKwinBlurManager as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
+
+impl Dispatch<OrgKdeKwinBlur, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &OrgKdeKwinBlur,
+        _: <OrgKdeKwinBlur as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+    }
+}
@@ -0,0 +1,96 @@
+//! A cheat sheet of the active layers, listing every
+//! key along with what its swipes and holds do.
+
+use relm4::{
+    ComponentSender,
+    gtk::{self, prelude::BoxExt},
+};
+
+use crate::{
+    config::Config,
+    keyboard::Keyboard,
+    layout::{Layer, Side},
+};
+
+use super::{UIMessage, UIModel, close_button};
+
+pub fn cheat_sheet(
+    width: i32,
+    height: i32,
+    keyboard: &Keyboard,
+    config: &Config,
+    sender: &ComponentSender<UIModel>,
+) -> gtk::Overlay {
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(12)
+        .css_classes(["cheat-sheet"])
+        .build();
+
+    let (left, right) = keyboard.layer;
+    let layers = [
+        (Side::Left, keyboard.left_layers().nth(left)),
+        (Side::Right, keyboard.right_layers().nth(right)),
+    ];
+    for (side, layer) in layers {
+        let Some(layer) = layer else { continue };
+        let heading = gtk::Label::builder()
+            .label(keyboard.layer_name(side))
+            .halign(gtk::Align::Start)
+            .css_classes(["cheat-sheet-heading"])
+            .build();
+        content.append(&heading);
+        content.append(&layer_sheet(layer, config));
+    }
+
+    let scroll = gtk::ScrolledWindow::builder()
+        .child(&content)
+        .width_request(width)
+        .height_request(height)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .build();
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&scroll));
+
+    let sender = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender.input(UIMessage::ToggleCheatSheet);
+    }));
+    overlay
+}
+
+/// The layer's keys, one cell per key,
+/// wrapping as needed to fit the width.
+fn layer_sheet(layer: &Layer, config: &Config) -> gtk::FlowBox {
+    let grid = gtk::FlowBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .homogeneous(true)
+        .min_children_per_line(2)
+        .build();
+
+    for key in layer.rows().flatten() {
+        let (glyph, actions) = key.summary(config);
+        let cell = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .css_classes(["cheat-sheet-key"])
+            .build();
+        cell.append(
+            &gtk::Label::builder()
+                .label(glyph)
+                .css_classes(["cheat-sheet-glyph"])
+                .build(),
+        );
+        for action in actions {
+            cell.append(
+                &gtk::Label::builder()
+                    .label(action)
+                    .wrap(true)
+                    .css_classes(["cheat-sheet-action"])
+                    .build(),
+            );
+        }
+        grid.insert(&cell, -1);
+    }
+    grid
+}
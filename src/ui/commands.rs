@@ -0,0 +1,437 @@
+//! Run commands bound to keys in the background,
+//! reporting failures back to the UI.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    process::{self, Child, Output, Stdio},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use relm4::Sender;
+
+use crate::{
+    dynamic,
+    handwriting::{RecognizerDef, Stroke},
+    keyboard::KeyMessage,
+    layout::{Command, CommandInsert, DynamicKey, Script},
+    plugin::{Effect, PluginAction, Plugins},
+    pointer::PointerMessage,
+    script,
+    secret::{Secret, SecretSource},
+    template,
+};
+
+use super::UIMessage;
+
+/// How much of a command's error output (bytes) is kept,
+/// from the end, for reporting why it failed. Apps may run
+/// for a long time and log a lot.
+const MAX_STDERR: usize = 4096;
+
+/// How long to wait for a secret, which may
+/// need a passphrase entered first (e.g. by pinentry).
+const SECRET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run a command, reporting if it
+/// fails to start or exits with an error.
+pub fn run(command: Command, sender: Sender<UIMessage>) {
+    let Command {
+        cmd,
+        args,
+        env,
+        cwd,
+        ..
+    } = command;
+    let child = build(&cmd, &args, &env, cwd.as_deref())
+        .stderr(Stdio::piped())
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            sender.emit(UIMessage::CommandFailed(cmd, err.to_string()));
+            return;
+        }
+    };
+
+    // Wait in another thread so that the
+    // process is reaped without blocking the UI.
+    std::thread::spawn(move || match wait_with_stderr_tail(child) {
+        Ok(output) => {
+            if let Some(reason) = failure(&output) {
+                sender.emit(UIMessage::CommandFailed(cmd, reason));
+            }
+        }
+        Err(err) => sender.emit(UIMessage::CommandFailed(cmd, err.to_string())),
+    });
+}
+
+/// Run a command and insert its output,
+/// giving up if it takes too long.
+pub fn run_insert(command: CommandInsert, sender: Sender<UIMessage>) {
+    let child = match spawn_insert(&command) {
+        Ok(child) => child,
+        Err(err) => {
+            sender.emit(UIMessage::CommandFailed(command.insert, err.to_string()));
+            return;
+        }
+    };
+
+    std::thread::spawn(move || match wait_with_timeout(child, command.timeout) {
+        Ok(output) => {
+            let text = output_text(&output);
+            if !text.is_empty() {
+                sender.emit(UIMessage::Insert(text));
+            }
+        }
+        Err(reason) => sender.emit(UIMessage::CommandFailed(command.insert, reason)),
+    });
+}
+
+/// Run the scanner helper, reporting what it read (or why
+/// it failed) for the given scan. Gives the helper's pid.
+pub fn scan(command: CommandInsert, id: u32, sender: Sender<UIMessage>) -> Option<u32> {
+    let child = match spawn_insert(&command) {
+        Ok(child) => child,
+        Err(err) => {
+            sender.emit(UIMessage::Scanned(id, Err(err.to_string())));
+            return None;
+        }
+    };
+
+    let pid = child.id();
+    std::thread::spawn(move || {
+        let result = wait_with_timeout(child, command.timeout).map(|output| output_text(&output));
+        sender.emit(UIMessage::Scanned(id, result));
+    });
+    Some(pid)
+}
+
+fn spawn_insert(command: &CommandInsert) -> std::io::Result<Child> {
+    build(
+        &command.insert,
+        &command.args,
+        &command.env,
+        command.cwd.as_deref(),
+    )
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+}
+
+/// A command's output, without the trailing newline.
+fn output_text(output: &Output) -> String {
+    let text = String::from_utf8_lossy(&output.stdout);
+    text.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Run a script with the keyboard's state in the
+/// background, acting on what it asks for.
+pub fn run_script(script: Script, state: script::State, sender: Sender<UIMessage>) {
+    let Script {
+        script: path,
+        args,
+        timeout,
+        ..
+    } = script;
+    std::thread::spawn(move || {
+        let run = fs_err::read_to_string(expand(&path))
+            .map_err(|err| err.to_string())
+            .and_then(|source| script::run(&source, &args, &state, Duration::from_millis(timeout)));
+        match run {
+            Ok(effects) => {
+                for effect in effects {
+                    emit_effect(effect, &sender);
+                }
+            }
+            Err(reason) => sender.emit(UIMessage::CommandFailed(path, reason)),
+        }
+    });
+}
+
+/// Wait for a child that may run for a long time, keeping
+/// only the end of its error output rather than all of it.
+fn wait_with_stderr_tail(mut child: Child) -> io::Result<Output> {
+    let mut stderr = vec![];
+    if let Some(mut pipe) = child.stderr.take() {
+        let mut chunk = [0; 1024];
+        loop {
+            let read = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            stderr.extend_from_slice(&chunk[..read]);
+            if stderr.len() > MAX_STDERR {
+                stderr.drain(..stderr.len() - MAX_STDERR);
+            }
+        }
+    }
+    let status = child.wait()?;
+    Ok(Output {
+        status,
+        stdout: vec![],
+        stderr,
+    })
+}
+
+/// Send a signal to a command and anything it started,
+/// as commands are run in their own process group.
+pub fn signal(pid: u32, signal: libc::c_int) {
+    // SAFETY: Sending a signal has no memory safety concerns;
+    // at worst the processes have already exited.
+    unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+}
+
+/// Run a dynamic key's script for what the key should
+/// show and do. This blocks, so it should be called
+/// in another thread.
+pub fn dynamic_state(key: &DynamicKey) -> Result<dynamic::State, String> {
+    let child = build(&key.dynamic, &key.args, &HashMap::new(), None)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+    let output = wait_with_timeout(child, key.interval)?;
+    dynamic::State::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Wait for a child's output, giving up if it takes too long.
+/// This blocks, so it should be called in another thread.
+pub(crate) fn wait_with_timeout(child: Child, timeout: u64) -> Result<Output, String> {
+    let pid = child.id();
+
+    // Output is read in yet another thread,
+    // so that we can give up on it.
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+
+    match rx.recv_timeout(Duration::from_millis(timeout)) {
+        Ok(Ok(output)) => match failure(&output) {
+            Some(reason) => Err(reason),
+            None => Ok(output),
+        },
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => {
+            signal(pid, libc::SIGKILL);
+            Err("timed out".into())
+        }
+    }
+}
+
+/// Run a plugin action in the background,
+/// as plugins may block (e.g. on the network).
+pub fn run_plugin(plugins: Plugins, call: PluginAction, sender: Sender<UIMessage>) {
+    std::thread::spawn(move || match plugins.run(&call) {
+        Ok(effects) => {
+            for effect in effects {
+                emit_effect(effect, &sender);
+            }
+        }
+        Err(err) => sender.emit(UIMessage::CommandFailed(call.describe(), err.to_string())),
+    });
+}
+
+/// Have the UI carry out what a plugin or script asked for.
+fn emit_effect(effect: Effect, sender: &Sender<UIMessage>) {
+    match effect {
+        Effect::Insert(text) => sender.emit(UIMessage::Insert(text)),
+        Effect::Key(modifiers, key) => sender.emit(UIMessage::Chord(modifiers, key)),
+        Effect::Toast(text) => sender.emit(UIMessage::Toast(text)),
+        Effect::Click(button) => {
+            sender.emit(PointerMessage::Press(button).into());
+            sender.emit(PointerMessage::Release(button).into());
+        }
+        Effect::Pointer(msg) => sender.emit(msg.into()),
+        Effect::Layer(side, idx) => {
+            sender.emit(KeyMessage::Layer(side, idx).into());
+            sender.emit(UIMessage::UpdateLayout);
+        }
+    }
+}
+
+/// Fill in a template and insert it, in the background
+/// since reading the clipboard runs a command.
+pub fn insert_template(template: String, selection: Option<String>, sender: Sender<UIMessage>) {
+    std::thread::spawn(move || {
+        let text = template::expand(&template, selection.as_deref());
+        if !text.is_empty() {
+            sender.emit(UIMessage::Insert(text));
+        }
+    });
+}
+
+/// Fetch a secret and type it. The secret is read
+/// straight into memory that's wiped (see [`Secret`]),
+/// apart from the command's error output.
+/// It's given up on if it takes too long.
+pub fn fetch_secret(source: &SecretSource, sender: Sender<UIMessage>) {
+    let (cmd, args) = source.command();
+    let child = process::Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            sender.emit(UIMessage::CommandFailed(cmd.into(), err.to_string()));
+            return;
+        }
+    };
+
+    std::thread::spawn(move || {
+        let pid = child.id();
+        let deadline = Instant::now() + SECRET_TIMEOUT;
+        let left = || deadline.saturating_duration_since(Instant::now());
+
+        // Both pipes are read at once, so that
+        // the command can't block writing to either.
+        let stdout = child.stdout.take().expect("Stdout is piped");
+        let (secret_tx, secret_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = secret_tx.send(Secret::read(stdout));
+        });
+        let (output_tx, output_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = output_tx.send(wait_with_stderr_tail(child));
+        });
+
+        let finished = secret_rx
+            .recv_timeout(left())
+            .and_then(|secret| Ok((secret, output_rx.recv_timeout(left())?)));
+        let Ok((secret, output)) = finished else {
+            signal(pid, libc::SIGKILL);
+            sender.emit(UIMessage::CommandFailed(cmd.into(), "timed out".into()));
+            return;
+        };
+        let reason = match output {
+            Ok(output) => failure(&output),
+            Err(err) => Some(err.to_string()),
+        };
+        let message = match (reason, secret) {
+            (Some(reason), _) => UIMessage::CommandFailed(cmd.into(), reason),
+            (None, Err(err)) => UIMessage::CommandFailed(cmd.into(), err.to_string()),
+            (None, Ok(None)) => {
+                UIMessage::CommandFailed(cmd.into(), "secret isn't valid UTF-8".into())
+            }
+            (None, Ok(Some(secret))) => UIMessage::TypeSecret(secret),
+        };
+        sender.emit(message);
+    });
+}
+
+/// Recognize handwritten strokes in the background, offering
+/// the candidates once they're known, for the canvas' generation.
+pub fn recognize_handwriting(
+    def: &RecognizerDef,
+    strokes: Vec<Stroke>,
+    size: (f64, f64),
+    generation: u32,
+    sender: Sender<UIMessage>,
+) {
+    let recognizer = def.recognizer();
+    let name = def.name().to_string();
+    std::thread::spawn(move || match recognizer.recognize(&strokes, size) {
+        Ok(candidates) => sender.emit(UIMessage::HandwritingCandidates(generation, candidates)),
+        Err(err) => sender.emit(UIMessage::CommandFailed(name, err.to_string())),
+    });
+}
+
+/// Set up a command as a shell would run it,
+/// expanding `~` and environment variables. It gets
+/// its own process group, so it can be stopped along
+/// with anything it starts (see [`signal`]).
+pub(crate) fn build(
+    cmd: &str,
+    args: &[String],
+    env: &HashMap<String, String>,
+    cwd: Option<&str>,
+) -> process::Command {
+    let mut command = process::Command::new(expand(cmd));
+    command.process_group(0);
+    command.args(args.iter().map(|arg| expand(arg)));
+    command.envs(env.iter().map(|(key, val)| (key, expand(val))));
+    if let Some(cwd) = cwd {
+        command.current_dir(expand(cwd));
+    }
+    command
+}
+
+/// Expand a leading `~` and any `$VAR` or `${VAR}`.
+/// Unset variables expand to nothing, as in a shell,
+/// and `$$` is a literal `$`.
+fn expand(s: &str) -> String {
+    let s = match s.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", std::env::var("HOME").unwrap_or_default(), rest)
+        }
+        _ => s.to_string(),
+    };
+
+    let mut expanded = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' || chars.next_if_eq(&'$').is_some() {
+            expanded.push(c);
+            continue;
+        }
+        let name: String = if chars.next_if_eq(&'{').is_some() {
+            chars.by_ref().take_while(|c| *c != '}').collect()
+        } else {
+            std::iter::from_fn(|| chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_'))
+                .collect()
+        };
+        if name.is_empty() {
+            expanded.push('$');
+        } else {
+            expanded.push_str(&std::env::var(&name).unwrap_or_default());
+        }
+    }
+    expanded
+}
+
+/// Why a command failed, if it did:
+/// the last line of its error output, or its exit status.
+fn failure(output: &Output) -> Option<String> {
+    if output.status.success() {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = match stderr.lines().rfind(|line| !line.trim().is_empty()) {
+        Some(line) => line.trim().to_string(),
+        None => output.status.to_string(),
+    };
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_variables() {
+        let home = std::env::var("HOME").unwrap_or_default();
+        assert_eq!(expand("~/notes"), format!("{}/notes", home));
+        assert_eq!(expand("$HOME/notes"), format!("{}/notes", home));
+        assert_eq!(expand("${HOME}notes"), format!("{}notes", home));
+        assert_eq!(expand("~user"), "~user");
+    }
+
+    #[test]
+    fn escapes_dollars() {
+        assert_eq!(expand("$$HOME"), "$HOME");
+        assert_eq!(expand("cost: $$5"), "cost: $5");
+        assert_eq!(expand("$$$$"), "$$");
+        assert_eq!(expand("a $ b"), "a $ b");
+    }
+}
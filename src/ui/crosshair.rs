@@ -0,0 +1,102 @@
+//! A brief crosshair over the whole output,
+//! to help find the cursor after moving it.
+//!
+//! We only send relative movement, so we don't know where
+//! the cursor is. Instead its position is estimated from the
+//! movement sent, starting from the middle of the output and
+//! kept within it, so pushing the cursor into an edge lines
+//! the crosshair back up with it (pointer acceleration makes
+//! the estimate drift otherwise).
+//!
+//! The window is mapped once and takes no input, so it
+//! never gets in the way of clicks or taps; it's only
+//! drawn on while the crosshair is up.
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use gdk4::{cairo, prelude::SurfaceExt};
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+use relm4::gtk::{
+    self,
+    prelude::{DrawingAreaExtManual, GtkWindowExt, NativeExt, WidgetExt},
+};
+
+use super::timer::Timer;
+
+/// How long the crosshair stays up after the last movement.
+const SHOW_DURATION: Duration = Duration::from_millis(600);
+
+pub struct Crosshair {
+    area: gtk::DrawingArea,
+    hide_timer: Timer,
+
+    /// The estimated position, once there's been movement.
+    pos: Rc<Cell<Option<(f64, f64)>>>,
+    shown: Rc<Cell<bool>>,
+}
+impl Crosshair {
+    pub fn new() -> Self {
+        let window = gtk::Window::builder().css_classes(["crosshair"]).build();
+        window.init_layer_shell();
+        window.set_layer(Layer::Overlay);
+        window.set_keyboard_mode(KeyboardMode::None);
+        window.set_exclusive_zone(-1);
+        for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+            window.set_anchor(edge, true);
+        }
+        window.connect_realize(|window| {
+            if let Some(surface) = window.surface() {
+                surface.set_input_region(&cairo::Region::create());
+            }
+        });
+
+        let pos: Rc<Cell<Option<(f64, f64)>>> = Rc::default();
+        let shown: Rc<Cell<bool>> = Rc::default();
+        let area = gtk::DrawingArea::new();
+        let pos_cb = pos.clone();
+        let shown_cb = shown.clone();
+        area.set_draw_func(move |_, cr, w, h| {
+            let Some((x, y)) = pos_cb.get().filter(|_| shown_cb.get()) else {
+                return;
+            };
+            cr.set_source_rgba(0.17, 0.67, 0.39, 0.9);
+            cr.set_line_width(2.);
+            cr.move_to(x, 0.);
+            cr.line_to(x, h as f64);
+            cr.move_to(0., y);
+            cr.line_to(w as f64, y);
+            let _ = cr.stroke();
+            cr.arc(x, y, 16., 0., 2. * std::f64::consts::PI);
+            let _ = cr.stroke();
+        });
+        window.set_child(Some(&area));
+        window.set_visible(true);
+
+        Self {
+            area,
+            hide_timer: Timer::default(),
+            pos,
+            shown,
+        }
+    }
+
+    /// Show the crosshair, moved by the movement about to be
+    /// sent (`dy` positive upwards), hiding it again once
+    /// there's been no movement for a bit.
+    pub fn flash(&mut self, (dx, dy): (i32, i32)) {
+        let (w, h) = (self.area.width() as f64, self.area.height() as f64);
+        let (x, y) = self.pos.get().unwrap_or((w / 2., h / 2.));
+        let x = (x + dx as f64).clamp(0., w);
+        let y = (y - dy as f64).clamp(0., h);
+        self.pos.set(Some((x, y)));
+        self.shown.set(true);
+        self.area.queue_draw();
+
+        let area = self.area.clone();
+        let shown = self.shown.clone();
+        self.hide_timer.once(SHOW_DURATION, move || {
+            shown.set(false);
+            area.queue_draw();
+        });
+    }
+}
@@ -0,0 +1,202 @@
+//! Human-readable descriptions of what keys do,
+//! e.g. for tooltips and screen readers.
+
+use crate::{
+    capture::CaptureAction,
+    compositor::CompositorAction,
+    config::Config,
+    layout::{BasicKey, Command, KeyDef, Modifier, SwipeAction},
+    plugin::PluginAction,
+    transform::Transform,
+};
+
+use super::{
+    glyphs::{default_glyph, nudge_glyph, text_glyph},
+    swipe::Direction,
+};
+
+impl BasicKey {
+    /// Describe the key's tap, hold, and swipe actions,
+    /// one per line.
+    pub fn describe(&self) -> String {
+        let tap = with_mods(&self.modifiers, &self.glyph());
+        let mut lines = vec![format!("Tap: {}", tap), format!("Hold: repeat {}", tap)];
+        for dir in Direction::ALL {
+            if let Some(action) = self.dir_action(dir) {
+                lines.push(format!("{} {}", dir.arrow(), action.describe(self)));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// A label for screen readers: the glyph and swipe actions.
+    pub fn accessible_label(&self) -> String {
+        let mut parts = vec![with_mods(&self.modifiers, &self.glyph())];
+        for dir in Direction::ALL {
+            if let Some(action) = self.dir_action(dir) {
+                parts.push(format!("swipe {}: {}", dir.name(), action.describe(self)));
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+impl KeyDef {
+    /// The key's label and what it does,
+    /// e.g. its swipe actions, for the cheat sheet.
+    pub fn summary(&self, config: &Config) -> (String, Vec<String>) {
+        match self {
+            Self::Conditional(key) => key.key.summary(config),
+            Self::Paste(_) => ("📋".into(), vec!["Paste the clipboard".into()]),
+            Self::Capture(key) => (
+                key.capture.glyph().into(),
+                vec![key.capture.describe().into()],
+            ),
+            Self::Space(key) => {
+                let mut actions = vec!["Space".to_string()];
+                if key.drag {
+                    actions.push("Drag sideways to move the cursor".into());
+                }
+                if key.switcher {
+                    actions.push("Hold to switch language or profile".into());
+                }
+                ("␣".into(), actions)
+            }
+            Self::Dynamic(key) => ("…".into(), vec![format!("Show {}", key.dynamic)]),
+            Self::Basic(key) => {
+                let actions = Direction::ALL
+                    .into_iter()
+                    .filter_map(|dir| {
+                        let action = key.dir_action(dir).as_ref()?;
+                        Some(format!("{} {}", dir.arrow(), action.describe(key)))
+                    })
+                    .collect();
+                (with_mods(&key.modifiers, &key.glyph()), actions)
+            }
+            Self::Command(command) => (command.label.clone(), vec![format!("Run {}", command.cmd)]),
+            Self::CommandInsert(command) => (
+                command.label.clone(),
+                vec![format!("Insert output of {}", command.insert)],
+            ),
+            Self::Script(script) => (script.label.clone(), vec![format!("Run {}", script.script)]),
+            Self::Template(key) => (key.label.clone(), vec![format!("Insert {}", key.template)]),
+            Self::Text(key) => (
+                key.glyph(),
+                key.up
+                    .iter()
+                    .map(|up| format!("{} {}", Direction::Up.arrow(), text_glyph(up)))
+                    .collect(),
+            ),
+            Self::Zhuyin(key) => (key.zhuyin.to_string(), vec![]),
+            Self::Vim(key) => (
+                key.label.clone().unwrap_or_else(|| key.vim.to_string()),
+                vec![format!("vi {}", key.vim)],
+            ),
+            Self::Secret(key) => (key.label.clone(), vec!["Type secret".into()]),
+            Self::PointerButton(button) => (button.glyph().into(), vec!["Click".into()]),
+            Self::Setting(key) => (key.setting.glyph(config), vec![]),
+            Self::Compositor(key) => (key.compositor.glyph(), vec![key.compositor.describe()]),
+            Self::Plugin(key) => (key.label.clone(), vec![key.plugin.describe()]),
+            Self::Pointer => ("✱".into(), vec!["Drag to move the pointer".into()]),
+            Self::Touchpad => ("▭".into(), vec!["Touchpad".into()]),
+            Self::PointerNudge(dx, dy) => (
+                nudge_glyph(*dx, *dy).into(),
+                vec![format!("Move pointer {}, {}", dx, dy)],
+            ),
+            Self::Help => ("?".into(), vec!["Hold for this sheet".into()]),
+            Self::Handwriting => ("✍".into(), vec!["Handwriting".into()]),
+            Self::Scan => ("▦".into(), vec!["Scan a code".into()]),
+            Self::Selection => ("⌶".into(), vec!["Selection mode".into()]),
+            Self::PinPad => ("#".into(), vec!["PIN pad".into()]),
+        }
+    }
+}
+
+impl SwipeAction {
+    /// Describe the action as triggered from the provided key.
+    pub fn describe(&self, key: &BasicKey) -> String {
+        match self {
+            Self::Key(k) => default_glyph(k).to_string(),
+            Self::ModKey(k, mods) => with_mods(mods, default_glyph(k)),
+            Self::Layer(side, idx) => format!("Layer {} ({:?})", idx, side),
+            Self::Modified(modifier) => with_mods(std::slice::from_ref(modifier), &key.glyph()),
+            Self::Arrow => "Move cursor".into(),
+            Self::Scroll => "Scroll".into(),
+            Self::Select => "Select text".into(),
+            Self::Delete => "Delete text".into(),
+            Self::Command(Command { cmd, .. }) => format!("Run {}", cmd),
+            Self::HideKeyboard => "Hide keyboard".into(),
+            Self::Profile(name) => format!("Profile: {}", name),
+            Self::Settings => "Settings".into(),
+            Self::Launcher => "App launcher".into(),
+            Self::Transform(transform) => transform.describe(),
+            Self::Plugin(call) => call.describe(),
+            Self::Script(script) => format!("Run {}", script.script),
+            Self::Complete => "Complete URLs".into(),
+            Self::Language => "Next language".into(),
+            Self::Quit => "Quit".into(),
+            Self::Restart => "Restart".into(),
+            Self::Compositor(action) => action.describe(),
+        }
+    }
+}
+
+impl CompositorAction {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Overview => "Overview".into(),
+            Self::NextWorkspace => "Next workspace".into(),
+            Self::PrevWorkspace => "Previous workspace".into(),
+            Self::Workspace(n) => format!("Workspace {}", n),
+            Self::FocusWindow(dir) => format!("Focus window {}", dir.name()),
+            Self::MoveWindow(dir) => format!("Move window {}", dir.name()),
+            Self::Fullscreen => "Toggle fullscreen".into(),
+        }
+    }
+}
+
+impl PluginAction {
+    pub fn describe(&self) -> String {
+        format!("{}: {}", self.name, self.action)
+    }
+}
+
+impl CaptureAction {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Screenshot => "Take a screenshot",
+            Self::Area => "Screenshot an area",
+            Self::Record => "Start/stop recording the screen",
+        }
+    }
+}
+
+impl Transform {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Upper => "Uppercase selection".into(),
+            Self::Lower => "Lowercase selection".into(),
+            Self::Wrap(before, after) => format!("Wrap selection in {}…{}", before, after),
+            Self::UrlEncode => "URL-encode selection".into(),
+        }
+    }
+}
+
+impl Direction {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Down => "down",
+        }
+    }
+}
+
+fn with_mods(modifiers: &[Modifier], glyph: &str) -> String {
+    modifiers
+        .iter()
+        .map(|modifier| format!("{:?}+", modifier))
+        .chain(std::iter::once(glyph.to_string()))
+        .collect()
+}
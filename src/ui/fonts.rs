@@ -0,0 +1,71 @@
+//! Key label fonts, applied with CSS generated at runtime.
+
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::{
+    config::Config,
+    keyboard::Keyboard,
+    layout::{Font, KeyDef},
+};
+
+impl Font {
+    /// A CSS class for keys with this font.
+    pub fn class(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        format!("font-{:x}", hasher.finish())
+    }
+
+    fn declarations(&self) -> String {
+        let mut css = String::new();
+        if let Some(family) = &self.family {
+            css.push_str(&format!(
+                "font-family: \"{}\";",
+                family.replace('"', "\\\"")
+            ));
+        }
+        if let Some(weight) = self.weight {
+            css.push_str(&format!("font-weight: {};", weight));
+        }
+        if let Some(size) = self.size {
+            css.push_str(&format!("font-size: {}px;", size));
+        }
+        css
+    }
+}
+
+/// CSS for the configured label font,
+/// and for any keys that override it.
+pub fn css(config: &Config, keyboard: &Keyboard) -> String {
+    let default = Font {
+        family: config.label_font.clone(),
+        weight: config.label_weight,
+        size: config.label_size,
+    };
+    // Secondary labels keep their own size.
+    let mut css = format!(
+        "button label:not(.secondary) {{ {} }}",
+        default.declarations()
+    );
+
+    let fonts: HashSet<&Font> = keyboard
+        .left_layers()
+        .chain(keyboard.right_layers())
+        .flat_map(|layer| layer.rows().flatten())
+        .filter_map(|key| match key {
+            KeyDef::Basic(key) => key.font.as_ref(),
+            _ => None,
+        })
+        .collect();
+    for font in fonts {
+        css.push_str(&format!(
+            "button.{} label:not(.secondary) {{ {} }}",
+            font.class(),
+            font.declarations()
+        ));
+    }
+    css
+}
@@ -0,0 +1,91 @@
+//! Swipe gestures on the keyboard background,
+//! i.e. swipes that don't start on a key.
+
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{Cast, GestureDragExt, GestureExt, IsA, ObjectExt, WidgetExt},
+    },
+};
+use tracing::debug;
+
+use crate::layout::{BasicKey, Gesture, SwipeAction};
+
+use super::{
+    UIModel,
+    key::KeyButton,
+    keyboard::{handle_swipe_action_press, handle_swipe_action_release},
+    swipe::{Direction, did_gesture},
+};
+
+impl Gesture {
+    fn dir_action(&self, dir: Direction) -> &Option<SwipeAction> {
+        match dir {
+            Direction::Up => &self.up,
+            Direction::Right => &self.right,
+            Direction::Left => &self.left,
+            Direction::Down => &self.down,
+        }
+    }
+}
+
+/// Attach the background gestures to a keyboard window,
+/// returning the controllers so they can later be removed.
+pub fn attach(
+    window: &gtk::Window,
+    gestures: &[Gesture],
+    sender: &ComponentSender<UIModel>,
+) -> Vec<gtk::EventController> {
+    let mut controllers = vec![];
+    for gesture in gestures {
+        let drag = gtk::GestureDrag::builder()
+            .n_points(gesture.fingers.max(1))
+            .build();
+
+        // Deny the gesture if it starts on a key,
+        // so that it doesn't interfere with key swipes.
+        let window_cb = window.clone();
+        drag.connect_drag_begin(move |drag, x, y| {
+            if started_on_key(&window_cb, x, y) {
+                drag.set_state(gtk::EventSequenceState::Denied);
+            }
+        });
+
+        let gesture = gesture.clone();
+        let sender = sender.clone();
+        drag.connect_drag_end(move |_, dx, dy| {
+            let Some(dir) = did_gesture(dx, dy) else {
+                return;
+            };
+            if let Some(action) = gesture.dir_action(dir) {
+                debug!("[Gesture] {:?} -> {:?}", dir, action);
+
+                // Background gestures are one-shots, except that
+                // layers latch (until a gesture switches back).
+                // There's no key, which only `Modified` would
+                // use, and that's not allowed for gestures.
+                let key = BasicKey::default();
+                handle_swipe_action_press(&key, action, dir, sender.input_sender());
+                if !matches!(action, SwipeAction::Layer(..)) {
+                    handle_swipe_action_release(&key, action, dir, sender.input_sender());
+                }
+            }
+        });
+        window.add_controller(drag.clone());
+        controllers.push(drag.upcast());
+    }
+    controllers
+}
+
+/// Whether the point (relative to the widget) is on a key.
+pub fn started_on_key(widget: &impl IsA<gtk::Widget>, x: f64, y: f64) -> bool {
+    let mut widget = widget.pick(x, y, gtk::PickFlags::DEFAULT);
+    while let Some(w) = widget {
+        if w.is::<KeyButton>() || w.is::<gtk::ToggleButton>() {
+            return true;
+        }
+        widget = w.parent();
+    }
+    false
+}
@@ -1,6 +1,14 @@
 use evdev::Key;
+use gdk4::glib;
 
-use crate::{layout::BasicKey, pointer::PointerButton};
+use crate::{
+    capture::CaptureAction,
+    compositor::{CompositorAction, WindowDirection},
+    config::{Config, Setting, Theme},
+    layout::{BasicKey, Modifier, SwipeAction, TextKey},
+    pointer::PointerButton,
+    session::keymap,
+};
 
 impl BasicKey {
     pub fn glyph(&self) -> String {
@@ -8,6 +16,60 @@ impl BasicKey {
             .clone()
             .unwrap_or_else(|| default_glyph(&self.key).to_string())
     }
+
+    /// A smaller glyph shown alongside the main one: what the
+    /// swipe up types, or otherwise what Shift types. Letters
+    /// aren't given their capitals, as that goes without saying.
+    pub fn secondary_glyph(&self) -> Option<String> {
+        match &self.up {
+            Some(SwipeAction::Key(key)) => return Some(default_glyph(key).to_string()),
+            Some(SwipeAction::Modified(Modifier::Shift)) | None => {}
+            Some(_) => return None,
+        }
+        if self.label.is_some() || !self.modifiers.is_empty() {
+            return None;
+        }
+        let shifted = keymap().shifted_glyph(&self.key)?;
+        let is_capital = keymap()
+            .glyph(&self.key)
+            .is_some_and(|glyph| glyph.to_uppercase() == shifted);
+        (!is_capital).then(|| shifted.to_string())
+    }
+}
+
+impl TextKey {
+    pub fn glyph(&self) -> String {
+        self.label.clone().unwrap_or_else(|| text_glyph(&self.text))
+    }
+
+    pub fn secondary_glyph(&self) -> Option<String> {
+        self.up.as_deref().map(text_glyph)
+    }
+}
+
+/// Text as a key label. Text starting with a combining mark
+/// (e.g. a Devanagari vowel sign) is put on a dotted circle,
+/// as it would otherwise be shaped onto nothing.
+pub fn text_glyph(text: &str) -> String {
+    let starts_with_mark = text.chars().next().is_some_and(|c| {
+        // SAFETY: This only looks up the character's Unicode category.
+        unsafe { glib::ffi::g_unichar_ismark(c as u32) != 0 }
+    });
+    if starts_with_mark {
+        format!("◌{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+impl CaptureAction {
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            Self::Screenshot => "📷",
+            Self::Area => "⬚",
+            Self::Record => "⏺",
+        }
+    }
 }
 
 impl PointerButton {
@@ -20,8 +82,81 @@ impl PointerButton {
     }
 }
 
-/// Get a default glyph/character for a key.
-fn default_glyph(key: &Key) -> &'static str {
+impl CompositorAction {
+    pub fn glyph(&self) -> String {
+        match self {
+            Self::Overview => "⊞".into(),
+            Self::NextWorkspace => "⇥".into(),
+            Self::PrevWorkspace => "⇤".into(),
+            Self::Workspace(n) => n.to_string(),
+            Self::FocusWindow(dir) => match dir {
+                WindowDirection::Left => "⇐",
+                WindowDirection::Right => "⇒",
+                WindowDirection::Up => "⇑",
+                WindowDirection::Down => "⇓",
+            }
+            .into(),
+            Self::MoveWindow(dir) => match dir {
+                WindowDirection::Left => "⇚",
+                WindowDirection::Right => "⇛",
+                WindowDirection::Up => "⤊",
+                WindowDirection::Down => "⤋",
+            }
+            .into(),
+            Self::Fullscreen => "⛶".into(),
+        }
+    }
+}
+
+/// An arrow pointing in the direction of a pointer nudge.
+pub fn nudge_glyph(dx: i32, dy: i32) -> &'static str {
+    match (dx.signum(), dy.signum()) {
+        (0, -1) => "⇡",
+        (0, 1) => "⇣",
+        (-1, 0) => "⇠",
+        (1, 0) => "⇢",
+        (-1, -1) => "⇖",
+        (1, -1) => "⇗",
+        (-1, 1) => "⇙",
+        (1, 1) => "⇘",
+        _ => "·",
+    }
+}
+
+impl Setting {
+    /// Settings glyphs reflect the current config state.
+    pub fn glyph(&self, config: &Config) -> String {
+        match self {
+            Self::Theme => match config.theme {
+                Theme::Dark => "☾",
+                Theme::Light => "☀",
+                Theme::HighContrast => "◐",
+                Theme::LargePrint => "A",
+            }
+            .into(),
+            Self::ScaleUp => "+".into(),
+            Self::ScaleDown => "−".into(),
+            Self::Stylus => if config.stylus { "✎" } else { "☝" }.into(),
+            Self::Haptics => if config.haptics { "≋" } else { "≂" }.into(),
+            Self::Autocorrect => if config.autocorrect { "a✓" } else { "a✗" }.into(),
+            Self::SwapHalves => "⇄".into(),
+            Self::Mirror => if config.mirror { "◧" } else { "◨" }.into(),
+            Self::Launcher => "▦".into(),
+            Self::Close => "✕".into(),
+        }
+    }
+}
+
+/// Get a default glyph/character for a key: what it
+/// types in the keymap, if it's printable, or else a symbol.
+pub fn default_glyph(key: &Key) -> &'static str {
+    keymap()
+        .glyph(key)
+        .filter(|glyph| !glyph.trim().is_empty())
+        .unwrap_or_else(|| key_glyph(key))
+}
+
+fn key_glyph(key: &Key) -> &'static str {
     match *key {
         Key::KEY_ESC => "⎋",
         Key::KEY_1 => "1",
@@ -0,0 +1,148 @@
+//! A canvas to write on, in place of the right half.
+//! Once writing pauses the strokes are recognized,
+//! and the candidates offered in the suggestion bar.
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use gdk4::glib;
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{ButtonExt, DrawingAreaExtManual, GestureDragExt, WidgetExt},
+    },
+};
+
+use crate::handwriting::Stroke;
+
+use super::{UIMessage, UIModel, close_button};
+
+/// How long writing has to pause for before it's recognized.
+const RECOGNIZE_DELAY: Duration = Duration::from_millis(600);
+
+/// The strokes written so far, and the canvas they're drawn on.
+#[derive(Clone)]
+pub struct Canvas {
+    strokes: Rc<RefCell<Vec<Stroke>>>,
+    area: gtk::DrawingArea,
+
+    /// Bumped with each stroke, so that only
+    /// the last stroke's pause is recognized.
+    generation: Rc<RefCell<u32>>,
+}
+impl Canvas {
+    /// Start over, e.g. once a candidate is picked.
+    pub fn clear(&self) {
+        self.strokes.borrow_mut().clear();
+        *self.generation.borrow_mut() += 1;
+        self.area.queue_draw();
+    }
+
+    /// Which strokes these are, for telling if
+    /// they've changed since being recognized.
+    pub fn generation(&self) -> u32 {
+        *self.generation.borrow()
+    }
+}
+
+pub fn handwriting(
+    width: i32,
+    height: i32,
+    sender: &ComponentSender<UIModel>,
+) -> (gtk::Overlay, Canvas) {
+    let area = gtk::DrawingArea::builder()
+        .width_request(width)
+        .height_request(height)
+        .css_classes(["handwriting"])
+        .build();
+    let canvas = Canvas {
+        strokes: Rc::default(),
+        area: area.clone(),
+        generation: Rc::default(),
+    };
+
+    let strokes = canvas.strokes.clone();
+    area.set_draw_func(move |area, cr, _, _| {
+        let color = area.color();
+        cr.set_source_rgba(
+            color.red().into(),
+            color.green().into(),
+            color.blue().into(),
+            color.alpha().into(),
+        );
+        cr.set_line_width(4.);
+        cr.set_line_cap(gtk::cairo::LineCap::Round);
+        cr.set_line_join(gtk::cairo::LineJoin::Round);
+        for stroke in strokes.borrow().iter() {
+            let mut points = stroke.iter();
+            if let Some((x, y)) = points.next() {
+                cr.move_to(*x, *y);
+                // A dot still shows up as a point.
+                cr.line_to(*x, *y);
+            }
+            for (x, y) in points {
+                cr.line_to(*x, *y);
+            }
+            let _ = cr.stroke();
+        }
+    });
+
+    let drag = gtk::GestureDrag::new();
+    let canvas_cb = canvas.clone();
+    drag.connect_drag_begin(move |_, x, y| {
+        *canvas_cb.generation.borrow_mut() += 1;
+        canvas_cb.strokes.borrow_mut().push(vec![(x, y)]);
+        canvas_cb.area.queue_draw();
+    });
+    let canvas_cb = canvas.clone();
+    drag.connect_drag_update(move |drag, x, y| {
+        let (sx, sy) = drag.start_point().unwrap_or_default();
+        if let Some(stroke) = canvas_cb.strokes.borrow_mut().last_mut() {
+            stroke.push((sx + x, sy + y));
+        }
+        canvas_cb.area.queue_draw();
+    });
+    let canvas_cb = canvas.clone();
+    let sender_cb = sender.clone();
+    drag.connect_drag_end(move |_, _, _| {
+        let generation = *canvas_cb.generation.borrow();
+        let canvas = canvas_cb.clone();
+        let sender = sender_cb.clone();
+        glib::timeout_add_local_once(RECOGNIZE_DELAY, move || {
+            if *canvas.generation.borrow() != generation {
+                return;
+            }
+            let size = (canvas.area.width() as f64, canvas.area.height() as f64);
+            let strokes = canvas.strokes.borrow().clone();
+            sender.input(UIMessage::RecognizeHandwriting(strokes, size));
+        });
+    });
+    area.add_controller(drag);
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&area));
+
+    let sender_cb = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender_cb.input(UIMessage::ToggleHandwriting);
+    }));
+
+    let clear = gtk::Button::builder()
+        .label("⌫")
+        .halign(gtk::Align::Start)
+        .valign(gtk::Align::Start)
+        .css_classes(["panel-close"])
+        .build();
+    let canvas_cb = canvas.clone();
+    let sender_cb = sender.clone();
+    clear.connect_clicked(move |_| {
+        canvas_cb.clear();
+        sender_cb.input(UIMessage::HandwritingCandidates(
+            canvas_cb.generation(),
+            vec![],
+        ));
+    });
+    overlay.add_overlay(&clear);
+
+    (overlay, canvas)
+}
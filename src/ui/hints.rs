@@ -0,0 +1,92 @@
+//! While a modifier is toggled on one half, keys on the
+//! other half are annotated with what they'd do with it:
+//! the common shortcuts by name (e.g. "copy" for Ctrl+C),
+//! and otherwise the chord (e.g. "C-k").
+
+use relm4::gtk::{self, prelude::*};
+
+use super::{descendants, glyphs::default_glyph, key::KeyButton};
+use crate::{layout::Modifier, session::keymap};
+
+/// Common shortcuts, by what the key types (so that they
+/// follow the keymap, as apps do), with Ctrl or Alt alone.
+const CTRL_SHORTCUTS: &[(&str, &str)] = &[
+    ("a", "all"),
+    ("c", "copy"),
+    ("f", "find"),
+    ("n", "new"),
+    ("o", "open"),
+    ("p", "print"),
+    ("q", "quit"),
+    ("r", "reload"),
+    ("s", "save"),
+    ("t", "tab"),
+    ("v", "paste"),
+    ("w", "close"),
+    ("x", "cut"),
+    ("y", "redo"),
+    ("z", "undo"),
+];
+const ALT_SHORTCUTS: &[(&str, &str)] = &[("⇥", "switch"), ("←", "back"), ("→", "fwd")];
+
+/// The hint for a key (by evdev code) with the modifiers,
+/// if it types something to hint at.
+fn hint(modifiers: &[Modifier], code: u16) -> Option<String> {
+    let key = evdev::Key::new(code);
+    // Letters are wherever the keymap puts them, e.g. Q on AZERTY's A.
+    let glyph = keymap()
+        .glyph(&key)
+        .filter(|glyph| glyph.chars().all(char::is_alphabetic))
+        .unwrap_or_else(|| default_glyph(&key));
+    if glyph == "?" {
+        return None;
+    }
+    let shortcuts = match modifiers {
+        [Modifier::Ctrl] => CTRL_SHORTCUTS,
+        [Modifier::Alt] => ALT_SHORTCUTS,
+        _ => &[],
+    };
+    if let Some((_, name)) = shortcuts.iter().find(|(key, _)| *key == glyph) {
+        return Some(name.to_string());
+    }
+    let prefix: String = modifiers
+        .iter()
+        .map(|modifier| match modifier {
+            Modifier::Ctrl => "C-",
+            Modifier::Alt => "M-",
+            Modifier::Meta => "s-",
+            Modifier::Shift => "S-",
+        })
+        .collect();
+    Some(format!("{}{}", prefix, glyph))
+}
+
+/// Update the hints on a half's shown layer. Halves with
+/// a modifier toggled on aren't annotated, as that hand
+/// is busy holding it.
+pub fn update(layer: &gtk::Widget, modifiers: &[Modifier]) {
+    let mut holding = false;
+    let mut keys = vec![];
+    descendants(layer, &mut |widget| {
+        if let Some(key) = widget.downcast_ref::<KeyButton>() {
+            keys.push(key.clone());
+        } else if let Some(toggle) = widget.downcast_ref::<gtk::ToggleButton>() {
+            holding |= toggle.is_active() && toggle.has_css_class("modifier");
+        }
+    });
+    // Shift alone is already shown by the keys' labels.
+    let shifting = modifiers
+        .iter()
+        .all(|modifier| matches!(modifier, Modifier::Shift));
+    for key in keys {
+        let hint = if holding || shifting {
+            None
+        } else {
+            hint(modifiers, key.key_code() as u16)
+        };
+        if key.hint() != hint {
+            // The generated setter can't clear it.
+            key.set_property("hint", hint);
+        }
+    }
+}
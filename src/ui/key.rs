@@ -6,10 +6,20 @@
 //! - swipe (single direction, swipe-and-release)
 //! - swipe-and-hold (single direction)
 //! - drag (potentially back-and-forth)
+//! - dwell (hover for some time), if enabled
+//! - cancel (slide far off the key and release), if enabled
+//! - swipe preview (shown until release, taken back by
+//!   returning to the center), for swipes that have one
+//! - with a mouse or pen: swipe actions shown on hover,
+//!   and offered in a menu on right-click
+//!
+//! Taps fire on release, unless the key fires on touch-down.
 
 use std::{
+    cell::{Cell, RefCell},
+    f64::consts::PI,
     sync::{Arc, OnceLock, RwLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use arc_swap::ArcSwap;
@@ -18,18 +28,132 @@ use gtk::{glib, prelude::*, subclass::prelude::*};
 use relm4::gtk;
 use tracing::debug;
 
-use super::swipe::{did_swipe, did_swipe_increment};
+use super::{
+    swipe::{Direction, did_swipe, did_swipe_increment, near_center},
+    timer::Timer,
+};
 
 #[derive(Debug, Default, Properties)]
 #[properties(wrapper_type = KeyButton)]
 pub struct ButtonInner {
     #[property(get, set)]
     primary_content: Arc<RwLock<Option<String>>>,
+
+    /// A smaller label in the corner, e.g. the shifted symbol.
+    #[property(get, set)]
+    secondary_content: Arc<RwLock<Option<String>>>,
+
+    /// What the key does with the modifiers toggled
+    /// on the other half, e.g. "copy" for Ctrl.
+    #[property(get, set)]
+    hint: Arc<RwLock<Option<String>>>,
+
+    /// The evdev code of the key this types, for
+    /// those hints. `0` if it isn't a plain key.
+    #[property(get, set)]
+    key_code: Cell<u32>,
+
+    /// In stylus mode, pen presses don't trigger holds,
+    /// since pen pressure makes press durations unreliable.
+    #[property(get, set)]
+    stylus: Cell<bool>,
+
+    /// Highlight the key while hovered.
+    #[property(get, set)]
+    hover_highlight: Cell<bool>,
+
+    /// How long (ms) a key must be pressed in a non-swipe
+    /// to trigger hold-and-repeat.
+    #[property(get, set)]
+    hold_term: Cell<u64>,
+
+    /// How far (px) a swipe-hold moves between repeats.
+    /// `0` uses the default.
+    #[property(get, set)]
+    swipe_increment: Cell<f64>,
+
+    /// Swipes only register once the pointer
+    /// leaves the key's bounds...
+    #[property(get, set)]
+    dead_zone_bounds: Cell<bool>,
+
+    /// ...or this radius (px) around the key's center.
+    /// `0` disables the radius.
+    #[property(get, set)]
+    dead_zone_radius: Cell<f64>,
+
+    /// Sliding this far (px) off the key cancels the press.
+    /// `0` disables canceling.
+    #[property(get, set)]
+    cancel_distance: Cell<f64>,
+
+    /// Bitmask of directions (see [`Direction::bit`])
+    /// that have swipe actions. When canceling is enabled
+    /// slides in other directions aren't claimed as swipes,
+    /// so that they can be canceled.
+    #[property(get, set)]
+    swipe_dirs: Cell<u8>,
+
+    /// How long (ms) the pointer must rest on the key
+    /// to activate it. `0` disables dwelling.
+    #[property(get, set)]
+    dwell: Cell<u64>,
+
+    /// Fire as soon as the key is touched, rather than on release.
+    /// Such presses can't turn into swipes or be canceled.
+    #[property(get, set)]
+    press_on_down: Cell<bool>,
+
+    /// What kind of device the current/last interaction came from.
+    input_kind: Cell<InputKind>,
+
+    /// What each direction's swipe does (by [`Direction::as_value`]).
+    /// Swipes with a preview only fire once released, and are
+    /// taken back by returning to the center.
+    swipe_previews: RefCell<[Option<String>; 4]>,
+
+    /// The swipe being previewed, if any.
+    previewing: Cell<Option<Direction>>,
+
+    /// What each direction's swipe does, shown while
+    /// hovered and offered in the right-click menu.
+    swipe_labels: RefCell<[Option<String>; 4]>,
+
+    /// Whether a mouse or pen is hovering the key.
+    hovered: Cell<bool>,
+
+    /// Where the current interaction is at.
+    action_state: ActionState,
+
+    /// Dwell progress, from 0 to 1.
+    dwell_progress: Cell<f64>,
+    dwell_timer: Timer,
+
+    /// Fires the hold for the current press.
+    hold_timer: Timer,
 }
 
-/// How long a key must be pressed in a non-swipe
-/// to trigger hold-and-repeat.
-const HOLD_TERM: u64 = 500;
+/// The kind of device an interaction comes from,
+/// so behavior can be tuned for each.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InputKind {
+    #[default]
+    Touch,
+    Mouse,
+    Pen,
+}
+impl InputKind {
+    fn from_device(device: Option<gdk4::Device>) -> Self {
+        match device.map(|device| device.source()) {
+            Some(gdk4::InputSource::Pen) => Self::Pen,
+            Some(gdk4::InputSource::Touchscreen) | None => Self::Touch,
+            Some(_) => Self::Mouse,
+        }
+    }
+}
+
+/// How often (ms) dwell progress is updated.
+const DWELL_TICK: u64 = 30;
 
 #[glib::object_subclass]
 impl ObjectSubclass for ButtonInner {
@@ -44,12 +168,23 @@ impl ObjectSubclass for ButtonInner {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum KeyState {
     Idle,
     Unclaimed,
     Pressed,
-    Swiping { x: f64, y: f64 },
+    Swiping {
+        x: f64,
+        y: f64,
+    },
+
+    /// `armed` once it's gone far enough from
+    /// the center that returning takes it back.
+    Previewing {
+        dir: Direction,
+        armed: bool,
+    },
+    Canceled,
 }
 
 impl KeyState {
@@ -61,15 +196,134 @@ impl KeyState {
         matches!(self, KeyState::Unclaimed)
     }
 
-    fn last_swipe_offset(&self) -> Option<(f64, f64)> {
+    fn previewing(&self) -> Option<(Direction, bool)> {
         match self {
-            KeyState::Swiping { x, y } => Some((*x, *y)),
+            KeyState::Previewing { dir, armed } => Some((*dir, *armed)),
             _ => None,
         }
     }
+
+    /// The state after the drag moves, and what that does.
+    fn drag(self, point: DragPoint, rules: DragRules) -> (Self, Option<DragEffect>) {
+        let DragPoint { x, y, .. } = point;
+        let claims = |dir: &Direction| rules.claimed & dir.bit() != 0;
+        let previews = |dir: &Direction| rules.previewed & dir.bit() != 0;
+
+        // Check if the press should be canceled.
+        if self.can_press() && point.past_cancel_distance {
+            return (KeyState::Canceled, Some(DragEffect::Cancel));
+        }
+
+        // Check if we started a swipe.
+        let (swiped, dir) = did_swipe(x, y, rules.input_kind);
+        if swiped && !point.in_dead_zone && self.can_swipe() {
+            // Note we only claim this interaction as a swipe
+            // if a direction is detected, otherwise it's left
+            // unclaimed (likely to be treated as a tap).
+            return match dir.filter(claims) {
+                Some(dir) if previews(&dir) => (
+                    KeyState::Previewing { dir, armed: false },
+                    Some(DragEffect::Preview(dir)),
+                ),
+                Some(dir) => (KeyState::Swiping { x, y }, Some(DragEffect::Swipe(dir))),
+                None => (self, None),
+            };
+        }
+
+        match self {
+            // Follow a previewed swipe, which is
+            // taken back by returning to the center.
+            KeyState::Previewing { dir, armed } => {
+                let centered = near_center(x, y, rules.input_kind);
+                if armed && centered {
+                    return (KeyState::Canceled, Some(DragEffect::TakeBack));
+                }
+                let (_, moved_to) = did_swipe(x, y, rules.input_kind);
+                let moved_to = moved_to
+                    .filter(|dir| claims(dir) && previews(dir))
+                    .unwrap_or(dir);
+                let state = KeyState::Previewing {
+                    dir: moved_to,
+                    armed: armed || !centered,
+                };
+                let effect = (moved_to != dir).then_some(DragEffect::Preview(moved_to));
+                (state, effect)
+            }
+
+            // Or check if we're incrementing a swipe (swipe-hold).
+            KeyState::Swiping {
+                x: last_x,
+                y: last_y,
+            } => match did_swipe_increment((x, y), (last_x, last_y), rules.swipe_increment) {
+                (true, dir) => (KeyState::Swiping { x, y }, dir.map(DragEffect::Repeat)),
+                (false, _) => (self, None),
+            },
+            _ => (self, None),
+        }
+    }
+
+    /// What ending the drag does.
+    fn end(self) -> DragEnd {
+        match self {
+            KeyState::Idle => DragEnd::Ignored,
+            KeyState::Canceled => DragEnd::Canceled,
+            state => DragEnd::Released {
+                swipe: state.previewing().map(|(dir, _)| dir),
+                tap: state.can_press(),
+            },
+        }
+    }
 }
 
-#[derive(Clone)]
+/// What the key's settings allow a drag to do.
+#[derive(Debug, Default, Clone, Copy)]
+struct DragRules {
+    input_kind: InputKind,
+
+    /// Bitmasks of directions (see [`Direction::bit`])
+    /// whose swipes are claimed, and those previewed.
+    claimed: u8,
+    previewed: u8,
+
+    /// See [`ButtonInner::swipe_increment`].
+    swipe_increment: f64,
+}
+
+/// Where a drag is, as an offset from where it started.
+#[derive(Debug, Default, Clone, Copy)]
+struct DragPoint {
+    x: f64,
+    y: f64,
+    in_dead_zone: bool,
+    past_cancel_distance: bool,
+}
+
+/// What a drag does, besides changing the key's state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DragEffect {
+    Cancel,
+    Swipe(Direction),
+    /// A swipe-hold moved far enough to repeat.
+    Repeat(Direction),
+    /// Show the preview of a swipe, or switch to it.
+    Preview(Direction),
+    /// A previewed swipe returned to the center.
+    TakeBack,
+}
+
+/// What ending a drag does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DragEnd {
+    /// Already let go of (see [`KeyButton::release_held`]).
+    Ignored,
+    /// Never fired anything, so there's nothing to release.
+    Canceled,
+    /// Fire the previewed swipe, if any, or a tap if nothing
+    /// else fired, then release whatever the key fired.
+    Released { swipe: Option<Direction>, tap: bool },
+}
+
+#[derive(Debug, Clone)]
 struct ActionState {
     state: Arc<ArcSwap<KeyState>>,
     last_position: Arc<ArcSwap<(f64, f64)>>,
@@ -83,6 +337,10 @@ impl Default for ActionState {
     }
 }
 impl ActionState {
+    fn get(&self) -> KeyState {
+        **self.state.load()
+    }
+
     fn set(&self, state: KeyState) {
         self.state.store(Arc::new(state));
     }
@@ -91,10 +349,6 @@ impl ActionState {
         self.state.load().can_press()
     }
 
-    fn can_swipe(&self) -> bool {
-        self.state.load().can_swipe()
-    }
-
     fn set_pos(&self, pos: (f64, f64)) {
         self.last_position.store(Arc::new(pos));
     }
@@ -103,14 +357,31 @@ impl ActionState {
         **self.last_position.load()
     }
 
-    fn last_swipe_offset(&self) -> Option<(f64, f64)> {
-        self.state.load().last_swipe_offset()
+    /// Whether the key has fired something
+    /// that it hasn't yet released.
+    fn is_held(&self) -> bool {
+        matches!(
+            **self.state.load(),
+            KeyState::Pressed | KeyState::Swiping { .. }
+        )
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(**self.state.load(), KeyState::Idle)
     }
 
     fn reset(&self) {
         self.set(KeyState::Idle);
         self.set_pos((0., 0.));
     }
+
+    /// Reset as if the touch had ended, returning
+    /// whether there was something held to release.
+    fn let_go(&self) -> bool {
+        let held = self.is_held();
+        self.reset();
+        held
+    }
 }
 
 #[glib::derived_properties]
@@ -119,26 +390,61 @@ impl ObjectImpl for ButtonInner {
         self.parent_constructed();
         let obj = self.obj();
 
+        // Keys act on the focused window,
+        // so they should never take focus themselves.
+        obj.set_focusable(false);
+        obj.set_focus_on_click(false);
+
+        // Don't keep timers running while hidden.
+        obj.connect_unmap(|obj| {
+            obj.cancel_hold();
+            obj.stop_dwell();
+        });
+
         obj.connect_primary_content_notify(|obj| {
             obj.update_view();
         });
+        obj.connect_secondary_content_notify(|obj| {
+            obj.update_view();
+        });
+        obj.connect_hint_notify(|obj| {
+            obj.update_view();
+        });
 
-        let action_state = ActionState::default();
+        let action_state = self.action_state.clone();
 
         let gesture = gtk::GestureDrag::new();
         let weak_ref = self.downgrade();
+        let obj_cb = obj.clone();
         let state = action_state.clone();
-        gesture.connect_drag_begin(move |_gesture, _x, _y| {
-            debug!("[Interaction Start]");
+        gesture.connect_drag_begin(move |gesture, _x, _y| {
+            let input_kind = InputKind::from_device(gesture.device());
+            debug!("[Interaction Start] {:?}", input_kind);
+            obj_cb.imp().input_kind.set(input_kind);
             state.set(KeyState::Unclaimed);
+            obj_cb.add_css_class("pressed");
+
+            if obj_cb.press_on_down() {
+                debug!("  [Press]");
+                state.set(KeyState::Pressed);
+                obj_cb.emit_by_name::<()>("tap-pressed", &[]);
+                return;
+            }
 
             let weak_ref = weak_ref.clone();
             let state = state.clone();
-            glib::timeout_add_once(Duration::from_millis(HOLD_TERM), move || {
+            if obj_cb.stylus() && input_kind == InputKind::Pen {
+                return;
+            }
+
+            let hold_term = Duration::from_millis(obj_cb.hold_term());
+            obj_cb.imp().hold_timer.once(hold_term, move || {
+                let Some(obj) = weak_ref.upgrade() else {
+                    return;
+                };
                 if state.can_press() {
                     debug!("  [Hold]");
                     state.set(KeyState::Pressed);
-                    let obj = weak_ref.upgrade().unwrap();
                     obj.obj().emit_by_name::<()>("tap-pressed", &[]);
                 }
             });
@@ -155,51 +461,90 @@ impl ObjectImpl for ButtonInner {
                 obj_cb.emit_by_name::<()>("freemove", &[&delta_x, &delta_y, &x, &y]);
                 state.set_pos((x, y));
 
-                // Check if we started a swipe.
-                let (did_swipe, dir) = did_swipe(x, y);
-                if did_swipe && state.can_swipe() {
-                    debug!("  [Swipe] offset={:?},{:?}", x, y);
-
-                    if let Some(dir) = dir {
-                        // Note we only claim this interaction as a swipe
-                        // if a direction is detected, otherwise it's left
-                        // unclaimed (likely to be treated as a tap).
-                        state.set(KeyState::Swiping { x, y });
-
+                let (sx, sy) = gesture.start_point().unwrap_or_default();
+                let point = DragPoint {
+                    x,
+                    y,
+                    in_dead_zone: obj_cb.in_dead_zone(sx + x, sy + y),
+                    past_cancel_distance: obj_cb.past_cancel_distance(sx + x, sy + y),
+                };
+                let (next, effect) = state.get().drag(point, obj_cb.drag_rules());
+                state.set(next);
+                match effect {
+                    Some(DragEffect::Cancel) => {
+                        debug!("  [Cancel]");
+                        obj_cb.add_css_class("canceled");
+                    }
+                    Some(DragEffect::Swipe(dir)) => {
                         debug!("  [Swipe] direction={:?}", dir);
+                        obj_cb.set_swipe_class(Some(dir));
                         obj_cb.emit_by_name::<()>("swipe-pressed", &[&dir.as_value()]);
-                    } else {
-                        debug!("  [Swipe] no direction");
                     }
-
-                // Otherwise check if we're incrementing a swipe (swipe-hold).
-                } else if let Some(last) = state.last_swipe_offset() {
-                    if let (true, dir) = did_swipe_increment((x, y), last) {
-                        state.set(KeyState::Swiping { x, y });
-                        if let Some(dir) = dir {
-                            obj_cb.emit_by_name::<()>("swipe-repeated", &[&dir.as_value()]);
-                        }
+                    Some(DragEffect::Repeat(dir)) => {
+                        obj_cb.set_swipe_class(Some(dir));
+                        obj_cb.emit_by_name::<()>("swipe-repeated", &[&dir.as_value()]);
+                    }
+                    Some(DragEffect::Preview(dir)) => {
+                        debug!("  [Swipe] previewing={:?}", dir);
+                        obj_cb.set_swipe_class(Some(dir));
+                        obj_cb.show_swipe_preview(Some(dir));
                     }
+                    Some(DragEffect::TakeBack) => {
+                        debug!("  [Swipe] Taken back");
+                        obj_cb.show_swipe_preview(None);
+                        obj_cb.set_swipe_class(None);
+                        obj_cb.add_css_class("canceled");
+                    }
+                    None => {}
                 }
             }
         });
 
         let obj_cb = obj.clone();
-        let state = action_state.clone();
         gesture.connect_drag_end(move |_gesture, _x, _y| {
-            // If this hasn't yet been claimed as a swipe or a hold
-            // then treat it as a tap.
-            if state.can_press() {
-                debug!("  [Tap]");
-                state.set(KeyState::Pressed);
-                obj_cb.emit_by_name::<()>("tap-pressed", &[]);
+            obj_cb.end_drag();
+        });
+        obj.add_controller(gesture);
+
+        // Swipes are awkward with a mouse,
+        // so they're offered in a menu instead.
+        let menu = gtk::GestureClick::new();
+        menu.set_button(gdk4::BUTTON_SECONDARY);
+        let obj_cb = obj.clone();
+        menu.connect_pressed(move |gesture, _, _, _| {
+            if InputKind::from_device(gesture.device()) != InputKind::Touch {
+                obj_cb.show_swipe_menu();
             }
+        });
+        obj.add_controller(menu);
 
-            debug!("  [Release]");
-            state.reset();
-            obj_cb.emit_by_name::<()>("released", &[]);
+        let motion = gtk::EventControllerMotion::new();
+        let obj_cb = obj.clone();
+        motion.connect_enter(move |motion, _, _| {
+            if obj_cb.hover_highlight() {
+                obj_cb.add_css_class("hovered");
+            }
+
+            // Touches also "hover" while they're down.
+            let input_kind = InputKind::from_device(motion.current_event_device());
+            let hovering = matches!(input_kind, InputKind::Mouse | InputKind::Pen);
+            if hovering && obj_cb.dwell() > 0 {
+                obj_cb.start_dwell();
+            }
+            if hovering && obj_cb.has_swipe_labels() {
+                obj_cb.imp().hovered.set(true);
+                obj_cb.update_view();
+            }
         });
-        obj.add_controller(gesture);
+        let obj_cb = obj.clone();
+        motion.connect_leave(move |_| {
+            obj_cb.remove_css_class("hovered");
+            obj_cb.stop_dwell();
+            if obj_cb.imp().hovered.replace(false) {
+                obj_cb.update_view();
+            }
+        });
+        obj.add_controller(motion);
     }
 
     fn signals() -> &'static [Signal] {
@@ -222,13 +567,37 @@ impl ObjectImpl for ButtonInner {
     }
 
     fn dispose(&self) {
-        if let Some(child) = self.obj().first_child() {
+        while let Some(child) = self.obj().first_child() {
             child.unparent();
         }
     }
 }
 
-impl WidgetImpl for ButtonInner {}
+impl WidgetImpl for ButtonInner {
+    fn snapshot(&self, snapshot: &gtk::Snapshot) {
+        self.parent_snapshot(snapshot);
+
+        // Draw the dwell progress as an arc.
+        let progress = self.dwell_progress.get();
+        if progress > 0. {
+            let obj = self.obj();
+            let (w, h) = (obj.width() as f64, obj.height() as f64);
+            let bounds = gtk::graphene::Rect::new(0., 0., w as f32, h as f32);
+            let cr = snapshot.append_cairo(&bounds);
+            let start = -PI / 2.;
+            cr.set_source_rgba(0.17, 0.67, 0.39, 0.9);
+            cr.set_line_width(3.);
+            cr.arc(
+                w / 2.,
+                h / 2.,
+                w.min(h) / 2. - 3.,
+                start,
+                start + 2. * PI * progress,
+            );
+            let _ = cr.stroke();
+        }
+    }
+}
 
 glib::wrapper! {
     pub struct KeyButton(ObjectSubclass<ButtonInner>)
@@ -237,6 +606,178 @@ glib::wrapper! {
 }
 
 impl KeyButton {
+    /// What kind of device the current/last interaction came from.
+    pub fn input_kind(&self) -> InputKind {
+        self.imp().input_kind.get()
+    }
+
+    /// Whether a point (relative to the key)
+    /// is within the swipe dead zone.
+    fn in_dead_zone(&self, x: f64, y: f64) -> bool {
+        let (w, h) = (self.width() as f64, self.height() as f64);
+        if self.dead_zone_bounds() && (0. ..=w).contains(&x) && (0. ..=h).contains(&y) {
+            return true;
+        }
+        let radius = self.dead_zone_radius();
+        radius > 0. && (x - w / 2.).hypot(y - h / 2.) < radius
+    }
+
+    /// Whether a point (relative to the key) is
+    /// far enough off the key to cancel the press.
+    fn past_cancel_distance(&self, x: f64, y: f64) -> bool {
+        let distance = self.cancel_distance();
+        if distance <= 0. {
+            return false;
+        }
+        let (w, h) = (self.width() as f64, self.height() as f64);
+        let dx = (-x).max(x - w).max(0.);
+        let dy = (-y).max(y - h).max(0.);
+        dx.hypot(dy) > distance
+    }
+
+    /// Let go of whatever the key is holding down (e.g. with a
+    /// swipe-hold) as if it were released, e.g. as the keyboard is
+    /// hidden, after which the touch may never be seen to end.
+    pub fn release_held(&self) {
+        let state = &self.imp().action_state;
+        if state.is_idle() {
+            return;
+        }
+        let held = state.let_go();
+        self.cancel_hold();
+        self.remove_css_class("pressed");
+        self.remove_css_class("canceled");
+        self.set_swipe_class(None);
+        if self.imp().previewing.get().is_some() {
+            self.show_swipe_preview(None);
+        }
+        if held {
+            debug!("  [Release] Let go of");
+            self.emit_by_name::<()>("released", &[]);
+        }
+    }
+
+    /// Let go of whatever keys within a widget are holding down.
+    pub fn release_held_within(widget: &gtk::Widget) {
+        super::descendants(widget, &mut |widget| {
+            if let Some(key) = widget.downcast_ref::<KeyButton>() {
+                key.release_held();
+            }
+        });
+    }
+
+    /// Finish the current interaction as its touch ends,
+    /// firing a tap or previewed swipe and releasing it.
+    fn end_drag(&self) {
+        let state = &self.imp().action_state;
+        let (swipe, tap) = match state.get().end() {
+            DragEnd::Ignored => return,
+            DragEnd::Canceled => {
+                debug!("  [Canceled]");
+                state.reset();
+                self.remove_css_class("pressed");
+                self.remove_css_class("canceled");
+                return;
+            }
+            DragEnd::Released { swipe, tap } => (swipe, tap),
+        };
+
+        // Previewed swipes fire once they're let go.
+        if let Some(dir) = swipe {
+            debug!("  [Swipe] direction={:?}", dir);
+            self.show_swipe_preview(None);
+            self.emit_by_name::<()>("swipe-pressed", &[&dir.as_value()]);
+        }
+
+        // If this hasn't yet been claimed as a swipe or a hold
+        // then treat it as a tap.
+        if tap {
+            debug!("  [Tap]");
+            state.set(KeyState::Pressed);
+            self.emit_by_name::<()>("tap-pressed", &[]);
+        }
+
+        debug!("  [Release]");
+        state.reset();
+        self.remove_css_class("pressed");
+        self.set_swipe_class(None);
+        self.emit_by_name::<()>("released", &[]);
+    }
+
+    /// Preview what a swipe in this direction does while
+    /// swiping, so it fires on release rather than right away.
+    pub fn set_swipe_preview(&self, dir: Direction, preview: Option<String>) {
+        self.imp().swipe_previews.borrow_mut()[dir.as_value() as usize] = preview;
+    }
+
+    fn has_swipe_preview(&self, dir: Direction) -> bool {
+        self.imp().swipe_previews.borrow()[dir.as_value() as usize].is_some()
+    }
+
+    fn show_swipe_preview(&self, dir: Option<Direction>) {
+        self.imp().previewing.set(dir);
+        self.update_view();
+    }
+
+    /// Whether a swipe in this direction is claimed.
+    fn claims_swipe(&self, dir: Direction) -> bool {
+        self.cancel_distance() <= 0. || self.swipe_dirs() & dir.bit() != 0
+    }
+
+    /// What the key's settings currently allow a drag to do.
+    fn drag_rules(&self) -> DragRules {
+        let dirs = |f: &dyn Fn(Direction) -> bool| {
+            Direction::ALL
+                .into_iter()
+                .filter(|dir| f(*dir))
+                .fold(0, |bits, dir| bits | dir.bit())
+        };
+        DragRules {
+            input_kind: self.input_kind(),
+            claimed: dirs(&|dir| self.claims_swipe(dir)),
+            previewed: dirs(&|dir| self.has_swipe_preview(dir)),
+            swipe_increment: self.swipe_increment(),
+        }
+    }
+
+    /// Start counting down to activate the key by dwelling.
+    fn start_dwell(&self) {
+        self.stop_dwell();
+
+        let started = Instant::now();
+        let dwell = Duration::from_millis(self.dwell());
+        let obj = self.downgrade();
+        let tick = Duration::from_millis(DWELL_TICK);
+        self.imp().dwell_timer.repeat(tick, move || {
+            let Some(obj) = obj.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let progress = started.elapsed().as_secs_f64() / dwell.as_secs_f64();
+            if progress >= 1. {
+                debug!("  [Dwell]");
+                obj.imp().dwell_progress.set(0.);
+                obj.queue_draw();
+                obj.emit_by_name::<()>("tap-pressed", &[]);
+                obj.emit_by_name::<()>("released", &[]);
+                glib::ControlFlow::Break
+            } else {
+                obj.imp().dwell_progress.set(progress);
+                obj.queue_draw();
+                glib::ControlFlow::Continue
+            }
+        });
+    }
+
+    fn cancel_hold(&self) {
+        self.imp().hold_timer.stop();
+    }
+
+    fn stop_dwell(&self) {
+        self.imp().dwell_timer.stop();
+        self.imp().dwell_progress.set(0.);
+        self.queue_draw();
+    }
+
     pub fn update_view(&self) {
         let primary_content = self.primary_content();
 
@@ -252,12 +793,130 @@ impl KeyButton {
             }
         }
 
-        // Remove existing content.
-        if let Some(child) = self.first_child() {
+        let content = gtk::Overlay::new();
+        content.set_child(Some(&layout));
+        if let Some(secondary_content) = self.secondary_content() {
+            let secondary_content = gtk::Label::new(Some(secondary_content.as_str()));
+            secondary_content.add_css_class("secondary");
+            secondary_content.set_halign(gtk::Align::End);
+            secondary_content.set_valign(gtk::Align::Start);
+            content.add_overlay(&secondary_content);
+        }
+        if self.imp().hovered.get() && self.imp().previewing.get().is_none() {
+            let labels = self.imp().swipe_labels.borrow();
+            for dir in Direction::ALL {
+                let Some(label) = &labels[dir.as_value() as usize] else {
+                    continue;
+                };
+                let (halign, valign) = match dir {
+                    Direction::Up => (gtk::Align::Center, gtk::Align::Start),
+                    Direction::Down => (gtk::Align::Center, gtk::Align::End),
+                    Direction::Left => (gtk::Align::Start, gtk::Align::Center),
+                    Direction::Right => (gtk::Align::End, gtk::Align::Center),
+                };
+                let label = gtk::Label::new(Some(label));
+                label.add_css_class("swipe-label");
+                label.set_halign(halign);
+                label.set_valign(valign);
+                content.add_overlay(&label);
+            }
+        }
+        let previews = self.imp().swipe_previews.borrow();
+        let preview = self
+            .imp()
+            .previewing
+            .get()
+            .and_then(|dir| Some((dir, previews[dir.as_value() as usize].as_ref()?)));
+        if let Some((dir, preview)) = preview {
+            let preview = gtk::Label::new(Some(&format!("{} {}", dir.arrow(), preview)));
+            preview.add_css_class("swipe-preview");
+            preview.set_halign(gtk::Align::Center);
+            preview.set_valign(gtk::Align::Start);
+            content.add_overlay(&preview);
+        }
+        if let Some(hint) = self.hint() {
+            let hint = gtk::Label::new(Some(hint.as_str()));
+            hint.add_css_class("hint");
+            hint.set_halign(gtk::Align::Center);
+            hint.set_valign(gtk::Align::End);
+            content.add_overlay(&hint);
+        }
+
+        // Remove existing content, which is kept
+        // first, before any open menu.
+        if let Some(child) = self
+            .first_child()
+            .filter(|child| child.is::<gtk::Overlay>())
+        {
             child.unparent();
         }
 
-        layout.set_parent(self);
+        content.insert_after(self, gtk::Widget::NONE);
+    }
+
+    /// Label what a swipe in this direction does,
+    /// for hovering and the right-click menu.
+    pub fn set_swipe_label(&self, dir: Direction, label: Option<String>) {
+        self.imp().swipe_labels.borrow_mut()[dir.as_value() as usize] = label;
+    }
+
+    fn has_swipe_labels(&self) -> bool {
+        self.imp().swipe_labels.borrow().iter().any(Option::is_some)
+    }
+
+    /// Offer the swipe actions in a menu, firing
+    /// the picked one as if it were swiped.
+    fn show_swipe_menu(&self) {
+        if !self.has_swipe_labels() {
+            return;
+        }
+        let items = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let popover = gtk::Popover::new();
+        let labels = self.imp().swipe_labels.borrow();
+        for dir in Direction::ALL {
+            let Some(label) = &labels[dir.as_value() as usize] else {
+                continue;
+            };
+            let item = gtk::Button::with_label(&format!("{} {}", dir.arrow(), label));
+            item.set_focusable(false);
+            item.set_focus_on_click(false);
+            item.add_css_class("flat");
+            let obj = self.downgrade();
+            let popover_cb = popover.downgrade();
+            item.connect_clicked(move |_| {
+                if let Some(popover) = popover_cb.upgrade() {
+                    popover.popdown();
+                }
+                let Some(obj) = obj.upgrade() else {
+                    return;
+                };
+                debug!("  [Menu] direction={:?}", dir);
+                obj.emit_by_name::<()>("swipe-pressed", &[&dir.as_value()]);
+                obj.emit_by_name::<()>("released", &[]);
+            });
+            items.append(&item);
+        }
+        popover.set_child(Some(&items));
+        popover.set_has_arrow(false);
+        popover.set_parent(self);
+        popover.connect_closed(|popover| {
+            // It can't be unparented while it's closing.
+            let popover = popover.clone();
+            glib::idle_add_local_once(move || popover.unparent());
+        });
+        popover.popup();
+    }
+
+    /// Expose the current swipe direction as a CSS class
+    /// (e.g. `.swiping-up`) so themes can provide
+    /// directional feedback. `None` clears it.
+    fn set_swipe_class(&self, dir: Option<Direction>) {
+        for class in Direction::ALL.map(|dir| dir.css_class()) {
+            self.remove_css_class(class);
+        }
+        if let Some(dir) = dir {
+            self.add_css_class(dir.css_class());
+        }
     }
 }
 impl Default for KeyButton {
@@ -265,3 +924,266 @@ impl Default for KeyButton {
         glib::Object::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// A key in some state, and how many times it's been released.
+    fn key(state: KeyState) -> (KeyButton, Rc<Cell<usize>>) {
+        let key = KeyButton::default();
+        key.imp().action_state.set(state);
+        let released = Rc::new(Cell::new(0));
+        let released_cb = released.clone();
+        key.connect_local("released", false, move |_| {
+            released_cb.set(released_cb.get() + 1);
+            None
+        });
+        (key, released)
+    }
+
+    /// Rules for a key claiming swipes in every direction.
+    fn rules() -> DragRules {
+        DragRules {
+            claimed: 0b1111,
+            ..Default::default()
+        }
+    }
+
+    fn at(x: f64, y: f64) -> DragPoint {
+        DragPoint {
+            x,
+            y,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn small_moves_stay_unclaimed() {
+        let state = KeyState::Unclaimed;
+        assert_eq!(state.drag(at(2., -2.), rules()), (state, None));
+        assert_eq!(
+            state.end(),
+            DragEnd::Released {
+                swipe: None,
+                tap: true
+            }
+        );
+    }
+
+    #[test]
+    fn swipes_fire_outside_the_dead_zone() {
+        let state = KeyState::Unclaimed;
+        let point = DragPoint {
+            in_dead_zone: true,
+            ..at(0., -20.)
+        };
+        assert_eq!(state.drag(point, rules()), (state, None));
+        assert_eq!(
+            state.drag(at(0., -20.), rules()),
+            (
+                KeyState::Swiping { x: 0., y: -20. },
+                Some(DragEffect::Swipe(Direction::Up))
+            )
+        );
+    }
+
+    #[test]
+    fn unclaimed_swipes_are_left_as_taps() {
+        let state = KeyState::Unclaimed;
+        let rules = DragRules {
+            claimed: Direction::Up.bit(),
+            ..rules()
+        };
+        assert_eq!(state.drag(at(20., 0.), rules), (state, None));
+
+        // Diagonals have no direction.
+        assert_eq!(state.drag(at(20., 20.), rules), (state, None));
+    }
+
+    #[test]
+    fn sliding_off_cancels() {
+        let point = DragPoint {
+            past_cancel_distance: true,
+            ..at(0., -40.)
+        };
+        assert_eq!(
+            KeyState::Unclaimed.drag(point, rules()),
+            (KeyState::Canceled, Some(DragEffect::Cancel))
+        );
+        assert_eq!(
+            KeyState::Canceled.drag(at(0., -20.), rules()),
+            (KeyState::Canceled, None)
+        );
+        assert_eq!(KeyState::Canceled.end(), DragEnd::Canceled);
+
+        // Holds have already fired, so they aren't canceled.
+        assert_eq!(
+            KeyState::Pressed.drag(point, rules()),
+            (KeyState::Pressed, None)
+        );
+    }
+
+    #[test]
+    fn swipe_holds_repeat_by_increments() {
+        let state = KeyState::Swiping { x: 0., y: -10. };
+        assert_eq!(state.drag(at(0., -12.), rules()), (state, None));
+        assert_eq!(
+            state.drag(at(0., -20.), rules()),
+            (
+                KeyState::Swiping { x: 0., y: -20. },
+                Some(DragEffect::Repeat(Direction::Up))
+            )
+        );
+        let rules = DragRules {
+            swipe_increment: 20.,
+            ..rules()
+        };
+        assert_eq!(state.drag(at(0., -20.), rules), (state, None));
+    }
+
+    #[test]
+    fn previewed_swipes_follow_the_drag() {
+        let rules = DragRules {
+            previewed: Direction::Up.bit() | Direction::Down.bit(),
+            ..rules()
+        };
+        let (state, effect) = KeyState::Unclaimed.drag(at(0., -20.), rules);
+        assert_eq!(
+            (state, effect),
+            (
+                KeyState::Previewing {
+                    dir: Direction::Up,
+                    armed: false
+                },
+                Some(DragEffect::Preview(Direction::Up))
+            )
+        );
+
+        // Returning before going far enough doesn't take it back.
+        assert_eq!(state.drag(at(0., -5.), rules), (state, None));
+
+        let (state, effect) = state.drag(at(0., -30.), rules);
+        let armed = KeyState::Previewing {
+            dir: Direction::Up,
+            armed: true,
+        };
+        assert_eq!((state, effect), (armed, None));
+
+        // Only to other previewed directions.
+        assert_eq!(state.drag(at(30., 0.), rules), (armed, None));
+        assert_eq!(
+            state.drag(at(0., 30.), rules),
+            (
+                KeyState::Previewing {
+                    dir: Direction::Down,
+                    armed: true
+                },
+                Some(DragEffect::Preview(Direction::Down))
+            )
+        );
+
+        assert_eq!(
+            state.drag(at(0., -5.), rules),
+            (KeyState::Canceled, Some(DragEffect::TakeBack))
+        );
+        assert_eq!(
+            state.end(),
+            DragEnd::Released {
+                swipe: Some(Direction::Up),
+                tap: false
+            }
+        );
+    }
+
+    #[test]
+    fn ending_releases_what_fired() {
+        assert_eq!(KeyState::Idle.end(), DragEnd::Ignored);
+        for state in [KeyState::Pressed, KeyState::Swiping { x: 0., y: -20. }] {
+            assert_eq!(
+                state.end(),
+                DragEnd::Released {
+                    swipe: None,
+                    tap: false
+                }
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn swipe_hold_is_released_once() {
+        gtk::test_synced(|| {
+            let (key, released) = key(KeyState::Swiping { x: 0., y: -30. });
+            key.release_held();
+            key.end_drag();
+            assert_eq!(released.get(), 1);
+            assert!(key.imp().action_state.is_idle());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn hold_is_released_once() {
+        gtk::test_synced(|| {
+            let (key, released) = key(KeyState::Pressed);
+            key.release_held();
+            key.release_held();
+            assert_eq!(released.get(), 1);
+        });
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn nothing_held_is_not_released() {
+        gtk::test_synced(|| {
+            for state in [KeyState::Idle, KeyState::Unclaimed, KeyState::Canceled] {
+                let (key, released) = key(state);
+                key.release_held();
+                key.end_drag();
+                assert_eq!(released.get(), 0);
+                assert!(key.imp().action_state.is_idle());
+            }
+        });
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn previewed_swipe_is_dropped() {
+        gtk::test_synced(|| {
+            // Previews fire on release, so nothing was held yet.
+            let (key, released) = key(KeyState::Previewing {
+                dir: Direction::Up,
+                armed: true,
+            });
+            let swiped = Rc::new(Cell::new(false));
+            let swiped_cb = swiped.clone();
+            key.connect_local("swipe-pressed", false, move |_| {
+                swiped_cb.set(true);
+                None
+            });
+            key.release_held();
+            key.end_drag();
+            assert_eq!(released.get(), 0);
+            assert!(!swiped.get());
+            assert!(key.imp().action_state.is_idle());
+        });
+    }
+
+    #[test]
+    #[ignore = "needs a display"]
+    fn keys_within_are_released() {
+        gtk::test_synced(|| {
+            let (held, held_released) = key(KeyState::Pressed);
+            let (idle, idle_released) = key(KeyState::Idle);
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            row.append(&held);
+            row.append(&idle);
+            KeyButton::release_held_within(row.upcast_ref());
+            assert_eq!(held_released.get(), 1);
+            assert_eq!(idle_released.get(), 0);
+        });
+    }
+}
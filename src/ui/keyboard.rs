@@ -1,33 +1,68 @@
-use std::sync::Arc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
 
 use arc_swap::ArcSwapOption;
 use gdk4::{
-    glib::value::FromValue,
+    glib::{self, value::FromValue},
     prelude::{Cast, ObjectExt},
 };
 use relm4::{
-    ComponentSender, RelmWidgetExt,
+    RelmWidgetExt, Sender,
     gtk::{
-        self,
-        prelude::{BoxExt, GestureDragExt, ToggleButtonExt, WidgetExt},
+        self, gio,
+        prelude::{
+            AccessibleExtManual, BoxExt, GestureDragExt, GestureExt, ToggleButtonExt, WidgetExt,
+        },
     },
 };
-use tracing::debug;
+use tracing::{debug, error, warn};
 
 use crate::{
+    config::{Activation, Config, DeadZone},
+    dynamic,
     keyboard::{KeyMessage, KeyType},
-    layout::{BasicKey, Command, KeyDef, Layer, Modifier, SwipeAction},
+    layout::{
+        BasicKey, CompositorKey, DynamicKey, KeyContext, KeyDef, Layer, Modifier, PluginKey,
+        SecretKey, SettingKey, SpaceKey, SwipeAction,
+    },
     pointer::PointerMessage,
+    vim,
 };
 
-use super::{UIMessage, UIModel, key::KeyButton, swipe::Direction};
+use super::{
+    UIMessage, commands,
+    gestures::started_on_key,
+    glyphs::nudge_glyph,
+    key::KeyButton,
+    swipe::{Direction, did_gesture},
+    timer::Timer,
+};
 
 const KEY_SPACING: i32 = 2;
-const KEY_SIZE: i32 = 42;
+pub const KEY_SIZE: i32 = 42;
 const KB_PADDING: i32 = 24;
 
+/// The pill trigger's size: thin so it covers little,
+/// but wide enough to be easy to hit.
+const PILL_WIDTH: i32 = 160;
+const PILL_HEIGHT: i32 = 12;
+
+/// How long (ms) a secret key waits for its confirmation tap.
+const CONFIRM_TIMEOUT: u64 = 3000;
+
+/// How often (ms) a held nudge key repeats.
+const NUDGE_REPEAT_INTERVAL: u64 = 40;
+
+/// How long a dynamic key's command gets to take
+/// effect before the key is refreshed to show it.
+const DYNAMIC_ACTION_DELAY: Duration = Duration::from_millis(250);
+
 impl BasicKey {
-    fn dir_action(&self, dir: Direction) -> &Option<SwipeAction> {
+    pub fn dir_action(&self, dir: Direction) -> &Option<SwipeAction> {
         match dir {
             Direction::Up => &self.up,
             Direction::Right => &self.right,
@@ -38,20 +73,268 @@ impl BasicKey {
 }
 
 impl KeyDef {
-    fn render(&self, size: i32, sender: &ComponentSender<UIModel>) -> gtk::Widget {
+    fn render(
+        &self,
+        size: i32,
+        config: &Config,
+        context: &KeyContext,
+        sender: &Sender<UIMessage>,
+    ) -> gtk::Widget {
         match self {
-            KeyDef::Basic(key) => key.render(size, sender),
-            KeyDef::Command(Command { label, cmd, args }) => {
-                let button = KeyButton::default();
-                button.set_primary_content(label.as_str());
-                button.set_width_request(size);
-                button.set_height_request(size);
-
-                let cmd = cmd.clone();
-                let args = args.clone();
+            KeyDef::Conditional(key) => {
+                let widget = key.key.render(size, config, context, sender);
+                if !key.conditions.met(context) {
+                    if key.conditions.hide {
+                        widget.set_visible(false);
+                    } else {
+                        widget.set_sensitive(false);
+                        widget.add_css_class("disabled");
+                    }
+                }
+                widget
+            }
+            KeyDef::Basic(key) => key.render(size, config, context, sender),
+            KeyDef::Command(command) => {
+                let button = key_button(command.label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                let command = command.clone();
+                let sender_cb = sender.clone();
+                button.connect("released", true, move |_| {
+                    sender_cb.emit(UIMessage::Command(command.clone()));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::CommandInsert(command) => {
+                let button = key_button(command.label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                let command = command.clone();
+                let sender_cb = sender.clone();
+                button.connect("released", true, move |_| {
+                    sender_cb.emit(UIMessage::CommandInsert(command.clone()));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Template(template) => {
+                let button = key_button(template.label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                let template = template.template.clone();
+                let sender_cb = sender.clone();
+                button.connect("released", true, move |_| {
+                    sender_cb.emit(UIMessage::Template(template.clone()));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Script(script) => {
+                let button = key_button(script.label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                let script = script.clone();
+                let sender_cb = sender.clone();
+                button.connect("released", true, move |_| {
+                    sender_cb.emit(UIMessage::Script(script.clone()));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Paste(key) => {
+                let button = key_button("📋", size, size, config);
+                button.add_css_class("restricted");
+                if let Some(clipboard) = &context.clipboard {
+                    button.set_secondary_content(clipboard.as_str());
+                }
+
+                let options = key.paste.clone();
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Paste(options.clone()));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Capture(key) => {
+                let action = key.capture;
+                let button = key_button(action.glyph(), size, size, config);
+                button.add_css_class("restricted");
+
+                // On release, so the keyboard can be hidden
+                // for screenshots without a press underway.
+                let sender_cb = sender.clone();
+                button.connect("released", true, move |_| {
+                    sender_cb.emit(UIMessage::Capture(action));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Space(key) => key.render(size, config, sender),
+            KeyDef::Dynamic(key) => {
+                let button = key_button("…", size, size, config);
+                button.add_css_class("restricted");
+                let state: Rc<RefCell<dynamic::State>> = Rc::default();
+
+                // Refresh right away when shown, as it may
+                // be out of date, and then periodically until hidden.
+                let refresh = Timer::default();
+                let key_cb = key.clone();
+                let state_cb = state.clone();
+                let refresh_cb = refresh.clone();
+                button.connect_map(move |button| {
+                    refresh_dynamic(button, &key_cb, &state_cb);
+                    let button = button.downgrade();
+                    let key = key_cb.clone();
+                    let state = state_cb.clone();
+                    let interval = Duration::from_millis(key.interval);
+                    refresh_cb.repeat(interval, move || {
+                        let Some(button) = button.upgrade() else {
+                            return glib::ControlFlow::Break;
+                        };
+                        refresh_dynamic(&button, &key, &state);
+                        glib::ControlFlow::Continue
+                    });
+                });
+                button.connect_unmap(move |_| {
+                    refresh.stop();
+                });
+
+                let run = {
+                    let key = key.clone();
+                    let state = state.clone();
+                    let sender = sender.clone();
+                    move |button: &KeyButton, dir: Option<Direction>| {
+                        let command = {
+                            let state = state.borrow();
+                            let action = match dir {
+                                None => &state.tap,
+                                Some(Direction::Up) => &state.up,
+                                Some(Direction::Down) => &state.down,
+                                Some(Direction::Left) => &state.left,
+                                Some(Direction::Right) => &state.right,
+                            };
+                            dynamic::State::command(action)
+                        };
+                        if let Some(command) = command {
+                            sender.emit(UIMessage::Command(command));
+                            let button = button.downgrade();
+                            let key = key.clone();
+                            let state = state.clone();
+                            glib::timeout_add_local_once(DYNAMIC_ACTION_DELAY, move || {
+                                if let Some(button) = button.upgrade() {
+                                    refresh_dynamic(&button, &key, &state);
+                                }
+                            });
+                        }
+                    }
+                };
+                let run = Rc::new(run);
+
+                let run_cb = run.clone();
+                button.connect_local("tap-pressed", true, move |args| {
+                    let button = args[0].get::<KeyButton>().ok()?;
+                    run_cb(&button, None);
+                    None
+                });
+                button.connect_local("swipe-pressed", true, move |args| {
+                    let button = args[0].get::<KeyButton>().ok()?;
+                    let dir: Direction = unsafe { Direction::from_value(&args[1]) };
+                    run(&button, Some(dir));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Text(key) => {
+                let button = key_button(&key.glyph(), size, size, config);
+                if let Some(glyph) = key.secondary_glyph() {
+                    button.set_secondary_content(glyph);
+                }
+
+                let text = key.text.clone();
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Insert(text.clone()));
+                    None
+                });
+
+                if let Some(up) = key.up.clone() {
+                    let sender_cb = sender.clone();
+                    button.connect("swipe-pressed", true, move |args| {
+                        let dir: Direction = unsafe { Direction::from_value(&args[1]) };
+                        if matches!(dir, Direction::Up) {
+                            sender_cb.emit(UIMessage::Insert(up.clone()));
+                        }
+                        None
+                    });
+                }
+
+                button.upcast()
+            }
+            KeyDef::Zhuyin(key) => {
+                let c = key.zhuyin;
+                let button = key_button(&c.to_string(), size, size, config);
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Zhuyin(c));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Vim(key) => {
+                let label = key.label.clone().unwrap_or_else(|| match key.vim {
+                    vim::REDO => "⌃R".into(),
+                    c => c.to_string(),
+                });
+                let button = key_button(&label, size, size, config);
+
+                let c = key.vim;
                 let sender_cb = sender.clone();
                 button.connect("released", true, move |_| {
-                    sender_cb.input(UIMessage::Command(cmd.clone(), args.clone()));
+                    sender_cb.emit(UIMessage::Vim(c));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Secret(SecretKey {
+                secret,
+                label,
+                confirm,
+            }) => {
+                let button = key_button(label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                // If confirmation is required, the first tap arms the key
+                // and a second tap before the timeout types the secret.
+                let armed = Timer::default();
+
+                let secret = secret.clone();
+                let confirm = *confirm;
+                let sender_cb = sender.clone();
+                button.connect_local("released", true, move |args| {
+                    let button = args[0].get::<KeyButton>().unwrap();
+                    if confirm && !armed.is_pending() {
+                        button.add_css_class("confirming");
+                        let timeout = Duration::from_millis(CONFIRM_TIMEOUT);
+                        armed.once(timeout, move || button.remove_css_class("confirming"));
+                        return None;
+                    }
+
+                    if armed.stop() {
+                        button.remove_css_class("confirming");
+                    }
+                    sender_cb.emit(UIMessage::FetchSecret(secret.clone()));
                     None
                 });
 
@@ -59,20 +342,55 @@ impl KeyDef {
             }
             KeyDef::PointerButton(key) => {
                 let key = *key;
-                let button = KeyButton::default();
-                button.set_primary_content(key.glyph());
-                button.set_width_request(size);
-                button.set_height_request(size);
+                let button = key_button(key.glyph(), size, size, config);
 
                 let sender_cb = sender.clone();
                 button.connect("tap-pressed", true, move |_| {
-                    sender_cb.input(PointerMessage::Press(key).into());
+                    sender_cb.emit(PointerMessage::Press(key).into());
                     None
                 });
 
                 let sender_cb = sender.clone();
                 button.connect("released", true, move |_| {
-                    sender_cb.input(PointerMessage::Release(key).into());
+                    sender_cb.emit(PointerMessage::Release(key).into());
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Setting(SettingKey { setting }) => {
+                let setting = *setting;
+                let button = key_button(&setting.glyph(config), size, size, config);
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Setting(setting));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Compositor(CompositorKey { compositor }) => {
+                let action = *compositor;
+                let button = key_button(&action.glyph(), size, size, config);
+                button.add_css_class("restricted");
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Compositor(action));
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Plugin(PluginKey { plugin, label }) => {
+                let button = key_button(label.as_str(), size, size, config);
+                button.add_css_class("restricted");
+
+                let call = plugin.clone();
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::Plugin(call.clone()));
                     None
                 });
 
@@ -80,10 +398,7 @@ impl KeyDef {
             }
             KeyDef::Pointer => {
                 let glyph = "✱";
-                let button = KeyButton::default();
-                button.set_primary_content(glyph);
-                button.set_width_request(size);
-                button.set_height_request(size);
+                let button = key_button(glyph, size, size, config);
 
                 // We scale the pointer movement exponentially
                 // based on distance from the drag start, such that
@@ -91,90 +406,381 @@ impl KeyDef {
                 // cover more ground.
                 let base_scale = 2.;
                 let offset_exp = 1. / 3.;
+
+                // Whether the mouse layer was switched to for this drag.
+                let dragging: Rc<Cell<bool>> = Rc::default();
+
                 let sender_cb = sender.clone();
-                button.connect("freemove", true, move |args| {
+                let dragging_cb = dragging.clone();
+                button.connect_local("freemove", true, move |args| {
                     let dx = args[1].get::<f64>().unwrap();
                     let dy = args[2].get::<f64>().unwrap();
                     let ox = args[3].get::<f64>().unwrap().abs().powf(offset_exp);
                     let oy = args[4].get::<f64>().unwrap().abs().powf(offset_exp);
                     let dx = (dx * ox * base_scale).round() as i32;
                     let dy = (dy * oy * base_scale).round() as i32;
-                    sender_cb.input(PointerMessage::Move(dx, dy).into());
-                    sender_cb.input(KeyMessage::MouseLayer(true).into());
-                    sender_cb.input(UIMessage::UpdateLayout);
+                    sender_cb.emit(PointerMessage::Move(dx, dy).into());
+                    if !dragging_cb.replace(true) {
+                        sender_cb.emit(KeyMessage::MouseLayer(true).into());
+                        sender_cb.emit(UIMessage::UpdateLayout);
+                    }
+                    None
+                });
+
+                let sender_cb = sender.clone();
+                button.connect_local("released", true, move |_| {
+                    if dragging.replace(false) {
+                        sender_cb.emit(KeyMessage::MouseLayer(false).into());
+                        sender_cb.emit(UIMessage::UpdateLayout);
+                    }
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Touchpad => {
+                let button = key_button("▭", size, size, config);
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::ToggleTouchpad);
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Handwriting => {
+                let button = key_button("✍", size, size, config);
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::ToggleHandwriting);
                     None
                 });
 
+                button.upcast()
+            }
+            KeyDef::PinPad => {
+                let button = key_button("#", size, size, config);
+
+                let sender_cb = sender.clone();
+                button.connect("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::TogglePinPad);
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Scan => {
+                let button = key_button("▦", size, size, config);
+                button.add_css_class("restricted");
+
                 let sender_cb = sender.clone();
                 button.connect("released", true, move |_| {
-                    sender_cb.input(KeyMessage::MouseLayer(false).into());
-                    sender_cb.input(UIMessage::UpdateLayout);
+                    sender_cb.emit(UIMessage::Scan);
+                    None
+                });
+
+                button.upcast()
+            }
+            KeyDef::Selection => {
+                // Re-rendered as selection mode changes
+                // (see `KeyContext`), so keep the state.
+                let toggle = gtk::ToggleButton::builder()
+                    .label("⌶")
+                    .width_request(size)
+                    .height_request(size)
+                    .focus_on_click(false)
+                    .active(context.selecting)
+                    .build();
+
+                let sender_cb = sender.clone();
+                toggle.connect_toggled(move |btn| {
+                    sender_cb.emit(KeyMessage::Selecting(btn.is_active()).into());
+                });
+
+                toggle.upcast()
+            }
+            KeyDef::PointerNudge(dx, dy) => {
+                let (dx, dy) = (*dx, *dy);
+                let button = key_button(nudge_glyph(dx, dy), size, size, config);
+
+                // Taps nudge once; holds keep nudging until released.
+                let repeat = Timer::default();
+
+                let sender_cb = sender.clone();
+                let repeat_cb = repeat.clone();
+                let button_weak = button.downgrade();
+                button.connect_local("tap-pressed", true, move |_| {
+                    sender_cb.emit(UIMessage::PointerNudge(dx, dy));
+
+                    let sender_cb = sender_cb.clone();
+                    let button_weak = button_weak.clone();
+                    let interval = Duration::from_millis(NUDGE_REPEAT_INTERVAL);
+                    repeat_cb.repeat(interval, move || {
+                        // Stop with the key, e.g. if it's been
+                        // re-rendered or dropped while held.
+                        let shown = button_weak
+                            .upgrade()
+                            .is_some_and(|button| button.is_mapped());
+                        if !shown {
+                            return glib::ControlFlow::Break;
+                        }
+                        sender_cb.emit(UIMessage::PointerNudge(dx, dy));
+                        glib::ControlFlow::Continue
+                    });
                     None
                 });
 
+                let repeat_cb = repeat.clone();
+                button.connect_local("released", true, move |_| {
+                    repeat_cb.stop();
+                    None
+                });
+
+                // A hidden key won't see the release.
+                button.connect_unmap(move |_| {
+                    repeat.stop();
+                });
+
                 button.upcast()
             }
+            KeyDef::Help => {
+                let button = key_button("?", size, size, config);
+
+                // Only a long-press opens the sheet,
+                // so it isn't opened by accident.
+                let hold = Timer::default();
+
+                let sender_cb = sender.clone();
+                let hold_cb = hold.clone();
+                let hold_term = Duration::from_millis(config.hold_term);
+                button.connect_local("tap-pressed", true, move |_| {
+                    let sender_cb = sender_cb.clone();
+                    hold_cb.once(hold_term, move || {
+                        sender_cb.emit(UIMessage::ToggleCheatSheet);
+                    });
+                    None
+                });
+
+                let sender_cb = sender.clone();
+                button.connect_local("released", true, move |_| {
+                    if hold.stop() {
+                        sender_cb.emit(UIMessage::Toast("Hold for the cheat sheet".into()));
+                    }
+                    None
+                });
+
+                button.upcast()
+            }
+        }
+    }
+}
+
+impl SpaceKey {
+    fn render(&self, size: i32, config: &Config, sender: &Sender<UIMessage>) -> gtk::Widget {
+        let width = i32::from(self.space) * size;
+        let button = key_button("␣", width, size, config);
+        let space = evdev::Key::KEY_SPACE.code();
+
+        // Sideways swipes move the cursor, and keep
+        // moving it with each increment after.
+        if self.drag {
+            for signal in ["swipe-pressed", "swipe-repeated"] {
+                let sender_cb = sender.clone();
+                button.connect(signal, true, move |args| {
+                    let dir: Direction = unsafe { Direction::from_value(&args[1]) };
+                    if !dir.is_vertical() {
+                        let key: evdev::Key = dir.into();
+                        send_key(key.code(), &sender_cb);
+                    }
+                    None
+                });
+            }
         }
+
+        if !self.switcher {
+            let sender_cb = sender.clone();
+            button.connect("tap-pressed", true, move |_| {
+                sender_cb.emit(KeyMessage::ButtonPress(space).into());
+                None
+            });
+            let sender_cb = sender.clone();
+            button.connect("released", true, move |_| {
+                sender_cb.emit(KeyMessage::ButtonRelease(space).into());
+                None
+            });
+            return button.upcast();
+        }
+
+        // A tap is pressed and released at once, whereas
+        // a long-press is still held once the press is handled.
+        let pending: Rc<Cell<bool>> = Rc::default();
+
+        let sender_cb = sender.clone();
+        let pending_cb = pending.clone();
+        button.connect_local("tap-pressed", true, move |_| {
+            pending_cb.set(true);
+            let sender_cb = sender_cb.clone();
+            let pending_cb = pending_cb.clone();
+            glib::idle_add_local_once(move || {
+                if pending_cb.replace(false) {
+                    sender_cb.emit(UIMessage::ToggleSwitcher);
+                }
+            });
+            None
+        });
+
+        let sender_cb = sender.clone();
+        button.connect_local("released", true, move |_| {
+            if pending.replace(false) {
+                send_key(space, &sender_cb);
+            }
+            None
+        });
+
+        button.upcast()
     }
 }
 
 impl BasicKey {
-    pub fn render(&self, size: i32, sender: &ComponentSender<UIModel>) -> gtk::Widget {
-        let key = self.clone();
+    pub fn render(
+        &self,
+        size: i32,
+        config: &Config,
+        context: &KeyContext,
+        sender: &Sender<UIMessage>,
+    ) -> gtk::Widget {
+        // Shared between the signal handlers below.
+        let key = Arc::new(self.clone());
         let glyph = key.glyph();
         let scan_code = key.key.code();
         let width = (key.width() * f32::from(size as u16)).round() as i32;
 
-        match KeyType::from(key.key) {
+        let widget: gtk::Widget = match KeyType::from(key.key) {
             KeyType::Mod => {
                 let toggle = gtk::ToggleButton::builder()
                     .label(glyph)
                     .width_request(width)
                     .height_request(size)
+                    .focus_on_click(false)
                     .build();
+                toggle.add_css_class("modifier");
 
                 let button_sender = sender.clone();
                 toggle.connect_toggled(move |btn| {
                     if btn.is_active() {
-                        button_sender.input(KeyMessage::ModPress(scan_code).into());
+                        button_sender.emit(KeyMessage::ModPress(scan_code).into());
                     } else {
-                        button_sender.input(KeyMessage::ModRelease(scan_code).into());
+                        button_sender.emit(KeyMessage::ModRelease(scan_code).into());
                     }
                 });
 
                 toggle.upcast()
             }
             KeyType::Lock => {
+                // Layers are re-rendered as locks change
+                // (see `KeyContext`), so keep the state.
                 let toggle = gtk::ToggleButton::builder()
                     .label(glyph)
                     .width_request(width)
                     .height_request(size)
+                    .focus_on_click(false)
+                    .active(context.locks.contains(&scan_code))
                     .build();
 
                 let button_sender = sender.clone();
                 toggle.connect_toggled(move |btn| {
                     if btn.is_active() {
-                        button_sender.input(KeyMessage::LockPress(scan_code).into());
+                        button_sender.emit(KeyMessage::LockPress(scan_code).into());
                     } else {
-                        button_sender.input(KeyMessage::LockRelease(scan_code).into());
+                        button_sender.emit(KeyMessage::LockRelease(scan_code).into());
                     }
                 });
 
                 toggle.upcast()
             }
             KeyType::Normal => {
-                let button = KeyButton::default();
-                button.set_primary_content(glyph);
-                button.set_width_request(width);
-                button.set_height_request(size);
+                let button = key_button(&glyph, width, size, config);
+                if let Some(glyph) = key.secondary_glyph() {
+                    button.set_secondary_content(glyph);
+                }
+                if key.modifiers.is_empty() {
+                    button.set_key_code(u32::from(scan_code));
+                }
+                if let Some(hold) = key.hold {
+                    button.set_hold_term(hold);
+                }
+                if let Some(increment) = key.increment {
+                    button.set_swipe_increment(increment);
+                }
+
+                // Canceling only applies to regular keys;
+                // e.g. the pointer key needs to be dragged far.
+                if let Some(distance) = config.cancel_distance {
+                    let swipe_dirs = Direction::ALL
+                        .into_iter()
+                        .filter(|dir| key.dir_action(*dir).is_some())
+                        .fold(0, |mask, dir| mask | dir.bit());
+                    button.set_cancel_distance(distance);
+                    button.set_swipe_dirs(swipe_dirs);
+                }
+
+                // Keys with swipe actions have to wait and
+                // see whether they're swiped, so they still
+                // fire on release.
+                let has_swipes = Direction::ALL
+                    .into_iter()
+                    .any(|dir| key.dir_action(dir).is_some());
+                button.set_press_on_down(config.activate == Activation::Press && !has_swipes);
+
+                if config.swipe_preview {
+                    for dir in Direction::ALL {
+                        let preview = key
+                            .dir_action(dir)
+                            .as_ref()
+                            .filter(|action| action.fires_once())
+                            .map(|action| action.describe(&key));
+                        button.set_swipe_preview(dir, preview);
+                    }
+                }
+
+                // Tooltips are only shown on hover,
+                // i.e. with a mouse or stylus.
+                button.set_tooltip_text(Some(&key.describe()));
+                button
+                    .update_property(&[gtk::accessible::Property::Label(&key.accessible_label())]);
+                for dir in Direction::ALL {
+                    let label = key
+                        .dir_action(dir)
+                        .as_ref()
+                        .map(|action| action.describe(&key));
+                    button.set_swipe_label(dir, label);
+                }
+
+                // Resolve the modifier codes once, rather than per press.
+                let mod_codes: Arc<[u16]> = key.modifiers.iter().map(Modifier::code).collect();
+
+                // Connected first, so that the press goes to the
+                // other display, and switched back once released.
+                if let Some(display) = &key.display {
+                    for signal in ["tap-pressed", "swipe-pressed"] {
+                        let sender_cb = sender.clone();
+                        let display = display.clone();
+                        button.connect(signal, true, move |_| {
+                            sender_cb.emit(KeyMessage::Display(Some(display.clone())).into());
+                            None
+                        });
+                    }
+                }
 
                 let sender_cb = sender.clone();
-                let modifiers = key.modifiers.clone();
+                let mod_codes_cb = mod_codes.clone();
                 button.connect("tap-pressed", true, move |_| {
-                    for modifier in &modifiers {
-                        sender_cb.input(KeyMessage::ModPress(modifier.code()).into());
+                    for code in mod_codes_cb.iter() {
+                        sender_cb.emit(KeyMessage::ModPress(*code).into());
                     }
-                    sender_cb.input(KeyMessage::ButtonPress(scan_code).into());
+                    sender_cb.emit(KeyMessage::ButtonPress(scan_code).into());
                     None
                 });
 
@@ -197,8 +803,21 @@ impl BasicKey {
                 let key_cb = key.clone();
                 let state_cb = state.clone();
                 let sender_cb = sender.clone();
+                let axis_lock = config.scroll_axis_lock;
                 button.connect("swipe-repeated", true, move |args| {
                     let dir: Direction = unsafe { Direction::from_value(&args[1]) };
+
+                    // Keep scrolling on the axis it started on,
+                    // ignoring increments that drift onto the other axis.
+                    let last = state_cb.load();
+                    if let Some(last) = last.as_deref().filter(|_| axis_lock) {
+                        let scrolling =
+                            matches!(key_cb.dir_action(*last), Some(SwipeAction::Scroll));
+                        if scrolling && last.is_vertical() != dir.is_vertical() {
+                            return None;
+                        }
+                    }
+
                     let action = key_cb.dir_action(dir);
                     if let Some(action) = action {
                         debug!("  [Swipe] Repeated: {:?} -> {:?}", dir, action);
@@ -211,7 +830,6 @@ impl BasicKey {
                 let key_cb = key.clone();
                 let sender_cb = sender.clone();
                 let state_cb = state.clone();
-                let modifiers = key.modifiers.clone();
                 button.connect("released", true, move |_| {
                     if let Some(dir) = state_cb.swap(None) {
                         let action = key_cb.dir_action(*dir);
@@ -220,40 +838,135 @@ impl BasicKey {
                             handle_swipe_action_release(&key_cb, action, *dir, &sender_cb);
                         }
                     } else {
-                        sender_cb.input(KeyMessage::ButtonRelease(scan_code).into());
-                        for modifier in &modifiers {
-                            sender_cb.input(KeyMessage::ModRelease(modifier.code()).into());
+                        sender_cb.emit(KeyMessage::ButtonRelease(scan_code).into());
+                        for code in mod_codes.iter() {
+                            sender_cb.emit(KeyMessage::ModRelease(*code).into());
                         }
                     }
                     None
                 });
+                // Scroll swipes follow the drag, rather than
+                // scrolling a set amount per increment.
+                let scrolls = |vertical: bool| {
+                    Direction::ALL
+                        .into_iter()
+                        .filter(|dir| dir.is_vertical() == vertical)
+                        .any(|dir| matches!(key.dir_action(dir), Some(SwipeAction::Scroll)))
+                };
+                let axes = (scrolls(false), scrolls(true));
+                if axes.0 || axes.1 {
+                    let key_cb = key.clone();
+                    let state_cb = state.clone();
+                    let sender_cb = sender.clone();
+                    let speed = config.scroll_speed;
+
+                    // Scroll not yet sent, as only whole units are.
+                    let unsent: Rc<Cell<(f64, f64)>> = Rc::default();
+                    button.connect_local("freemove", true, move |args| {
+                        let Some(dir) = state_cb.load_full() else {
+                            unsent.set((0., 0.));
+                            return None;
+                        };
+                        if !matches!(key_cb.dir_action(*dir), Some(SwipeAction::Scroll)) {
+                            return None;
+                        }
+                        let (x_axis, y_axis) = if axis_lock {
+                            (!dir.is_vertical(), dir.is_vertical())
+                        } else {
+                            axes
+                        };
+                        let dx = args[1].get::<f64>().unwrap();
+                        let dy = args[2].get::<f64>().unwrap();
+                        let (unsent_x, unsent_y) = unsent.get();
+                        let x = if x_axis { unsent_x + dx * speed } else { 0. };
+                        let y = if y_axis { unsent_y + dy * speed } else { 0. };
+                        unsent.set((x.fract(), y.fract()));
+                        let (x, y) = (x.trunc() as i32, y.trunc() as i32);
+                        if (x, y) != (0, 0) {
+                            sender_cb.emit(PointerMessage::ScrollBy(x, y).into());
+                        }
+                        None
+                    });
+                }
+
+                if key.display.is_some() {
+                    let sender_cb = sender.clone();
+                    button.connect("released", true, move |_| {
+                        sender_cb.emit(KeyMessage::Display(None).into());
+                        None
+                    });
+                }
 
                 button.upcast()
             }
+        };
+        if let Some(font) = &self.font {
+            widget.add_css_class(&font.class());
         }
+        widget
     }
 }
 
 impl Layer {
-    pub fn render(&self, sender: ComponentSender<UIModel>) -> gtk::Overlay {
+    /// Render the layer within `max_size` (width, height).
+    /// If its widest row doesn't fit, its keys are shrunk to fit.
+    /// If its rows are too tall, they're split into pages which
+    /// are switched between by swiping up or down on the layer background.
+    pub fn render(
+        &self,
+        size: i32,
+        max_size: Option<(i32, i32)>,
+        config: &Config,
+        context: &KeyContext,
+        sender: &Sender<UIMessage>,
+    ) -> gtk::Overlay {
         let overlay = gtk::Overlay::new();
+        if let Some(class) = self.accent_class() {
+            overlay.add_css_class(&class);
+        }
 
         let container = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .build();
 
-        for row in self.rows() {
-            let row_container = gtk::Box::builder()
-                .orientation(gtk::Orientation::Horizontal)
-                .build();
+        let size = match max_size {
+            Some((width, _)) => size.min(self.fit_size(width)),
+            None => size,
+        };
+        let rows: Vec<gtk::Box> = self
+            .rows()
+            .map(|row| {
+                let row_container = gtk::Box::builder()
+                    .orientation(gtk::Orientation::Horizontal)
+                    .build();
 
-            row.iter().for_each(|key| {
-                let button = key.render(KEY_SIZE, &sender);
-                button.set_margin_all(KEY_SPACING);
-                row_container.append(&button);
-            });
+                let mut buttons: Vec<_> = row
+                    .iter()
+                    .map(|key| key.render(size, config, context, sender))
+                    .collect();
+                if config.mirror {
+                    buttons.reverse();
+                }
+                for button in buttons {
+                    button.set_margin_all(KEY_SPACING);
+                    row_container.append(&button);
+                }
+                row_container
+            })
+            .collect();
 
-            container.append(&row_container);
+        let row_height = size + KEY_SPACING * 2;
+        let per_page = max_size
+            .map(|(_, height)| ((height - KB_PADDING * 2) / row_height).max(1) as usize)
+            .unwrap_or(usize::MAX);
+        if rows.len() > per_page {
+            let pages = paginate(&rows, per_page);
+            container.append(&pages);
+            add_page_controls(&overlay, &pages, rows.len().div_ceil(per_page));
+        } else {
+            for row in &rows {
+                container.append(row);
+            }
         }
 
         // Add a invisible swipe area on the left half of each keyboard half.
@@ -269,7 +982,7 @@ impl Layer {
         let sender_cb = sender.clone();
         drag.connect_drag_update(move |_, _, y| {
             let change = if y > 0. { -1 } else { 1 };
-            sender_cb.input(UIMessage::FadeKeyboard(change));
+            sender_cb.emit(UIMessage::FadeKeyboard(change));
         });
         drag_handle.add_controller(drag);
 
@@ -283,36 +996,163 @@ impl Layer {
         overlay.set_child(Some(&container));
         overlay
     }
+
+    /// The largest key size at which every row fits in `width`.
+    fn fit_size(&self, width: i32) -> i32 {
+        let width = width - KB_PADDING * 2;
+        self.rows()
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                let units: f32 = row.iter().map(KeyDef::width).sum();
+                let spacing = row.len() as i32 * KEY_SPACING * 2;
+                ((width - spacing) as f32 / units).floor() as i32
+            })
+            .min()
+            .unwrap_or(i32::MAX)
+            .max(1)
+    }
+}
+
+/// Split rows into pages of a stack.
+fn paginate(rows: &[gtk::Box], per_page: usize) -> gtk::Stack {
+    let pages = gtk::Stack::new();
+    pages.set_transition_type(gtk::StackTransitionType::SlideUpDown);
+    for (i, chunk) in rows.chunks(per_page).enumerate() {
+        let page = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        page.set_valign(gtk::Align::Start);
+        for row in chunk {
+            page.append(row);
+        }
+        pages.add_named(&page, Some(&i.to_string()));
+    }
+    pages
 }
 
-fn send_key(key: u16, sender: &ComponentSender<UIModel>) {
-    sender.input(KeyMessage::ButtonPress(key).into());
-    sender.input(KeyMessage::ButtonRelease(key).into());
+/// Show which page is shown, and switch pages
+/// with vertical swipes on the layer background.
+fn add_page_controls(overlay: &gtk::Overlay, pages: &gtk::Stack, count: usize) {
+    let indicator = gtk::Label::new(Some(&format!("1/{}", count)));
+    indicator.add_css_class("page-indicator");
+    indicator.set_halign(gtk::Align::End);
+    indicator.set_valign(gtk::Align::Start);
+    indicator.set_can_target(false);
+    overlay.add_overlay(&indicator);
+
+    let current = Rc::new(Cell::new(0));
+    let drag = gtk::GestureDrag::new();
+    let overlay_cb = overlay.clone();
+    drag.connect_drag_begin(move |drag, x, y| {
+        if started_on_key(&overlay_cb, x, y) {
+            drag.set_state(gtk::EventSequenceState::Denied);
+        }
+    });
+    let pages = pages.clone();
+    drag.connect_drag_end(move |_, dx, dy| {
+        let page = match did_gesture(dx, dy) {
+            Some(Direction::Up) => (current.get() + 1).min(count - 1),
+            Some(Direction::Down) => current.get().saturating_sub(1),
+            _ => return,
+        };
+        current.set(page);
+        pages.set_visible_child_name(&page.to_string());
+        indicator.set_label(&format!("{}/{}", page + 1, count));
+    });
+    overlay.add_controller(drag);
 }
 
-fn send_mod_key(modifier: u16, key: u16, sender: &ComponentSender<UIModel>) {
-    sender.input(KeyMessage::ModPress(modifier).into());
-    sender.input(KeyMessage::ButtonPress(key).into());
-    sender.input(KeyMessage::ButtonRelease(key).into());
-    sender.input(KeyMessage::ModRelease(modifier).into());
+/// A thin pill to show the keyboard with,
+/// by tapping or swiping up.
+pub fn pill_trigger(config: &Config, sender: &Sender<UIMessage>) -> gtk::Widget {
+    let button = key_button(" ", PILL_WIDTH, PILL_HEIGHT, config);
+    button.set_css_classes(&["trigger", "pill"]);
+    button.update_property(&[gtk::accessible::Property::Label("Show keyboard")]);
+
+    let sender_cb = sender.clone();
+    button.connect("tap-pressed", true, move |_| {
+        sender_cb.emit(UIMessage::ShowKeyboard);
+        None
+    });
+    let sender_cb = sender.clone();
+    button.connect("swipe-pressed", true, move |args| {
+        let dir: Direction = unsafe { Direction::from_value(&args[1]) };
+        if matches!(dir, Direction::Up) {
+            sender_cb.emit(UIMessage::ShowKeyboard);
+        }
+        None
+    });
+    button.upcast()
 }
 
-fn send_mods_key(modifiers: Vec<u16>, key: u16, sender: &ComponentSender<UIModel>) {
+/// Update a dynamic key from its script, in the background.
+fn refresh_dynamic(button: &KeyButton, key: &DynamicKey, state: &Rc<RefCell<dynamic::State>>) {
+    let button = button.downgrade();
+    let key = key.clone();
+    let state = state.clone();
+    glib::spawn_future_local(async move {
+        let cmd = key.dynamic.clone();
+        let result = gio::spawn_blocking(move || commands::dynamic_state(&key)).await;
+        let Some(button) = button.upgrade() else {
+            return;
+        };
+        match result {
+            Ok(Ok(new)) => {
+                button.set_primary_content(new.label.as_str());
+                button.update_property(&[gtk::accessible::Property::Label(&new.label)]);
+                *state.borrow_mut() = new;
+            }
+            Ok(Err(reason)) => warn!("Dynamic key `{}` failed: {}", cmd, reason),
+            Err(_) => error!("Dynamic key `{}` panicked", cmd),
+        }
+    });
+}
+
+/// Create a key button, with behavior set per the config.
+fn key_button(glyph: &str, width: i32, height: i32, config: &Config) -> KeyButton {
+    let button = KeyButton::default();
+    button.set_primary_content(glyph);
+    button.set_width_request(width);
+    button.set_height_request(height);
+    button.set_hold_term(config.hold_term);
+    match config.swipe_dead_zone {
+        Some(DeadZone::Key) => button.set_dead_zone_bounds(true),
+        Some(DeadZone::Radius(radius)) => button.set_dead_zone_radius(radius),
+        None => {}
+    }
+    button.set_stylus(config.stylus);
+    button.set_hover_highlight(config.stylus && config.hover_highlight);
+    button.set_dwell(config.dwell.unwrap_or(0));
+    button.update_property(&[gtk::accessible::Property::Label(glyph)]);
+    button
+}
+
+pub fn send_key(key: u16, sender: &Sender<UIMessage>) {
+    sender.emit(KeyMessage::ButtonPress(key).into());
+    sender.emit(KeyMessage::ButtonRelease(key).into());
+}
+
+fn send_mod_key(modifier: u16, key: u16, sender: &Sender<UIMessage>) {
+    sender.emit(KeyMessage::ModPress(modifier).into());
+    sender.emit(KeyMessage::ButtonPress(key).into());
+    sender.emit(KeyMessage::ButtonRelease(key).into());
+    sender.emit(KeyMessage::ModRelease(modifier).into());
+}
+
+pub fn send_mods_key(modifiers: Vec<u16>, key: u16, sender: &Sender<UIMessage>) {
     for modifier in &modifiers {
-        sender.input(KeyMessage::ModPress(*modifier).into());
+        sender.emit(KeyMessage::ModPress(*modifier).into());
     }
-    sender.input(KeyMessage::ButtonPress(key).into());
-    sender.input(KeyMessage::ButtonRelease(key).into());
+    sender.emit(KeyMessage::ButtonPress(key).into());
+    sender.emit(KeyMessage::ButtonRelease(key).into());
     for modifier in &modifiers {
-        sender.input(KeyMessage::ModRelease(*modifier).into());
+        sender.emit(KeyMessage::ModRelease(*modifier).into());
     }
 }
 
-fn handle_swipe_action_press(
+pub fn handle_swipe_action_press(
     key_def: &BasicKey,
     action: &SwipeAction,
     dir: Direction,
-    sender: &ComponentSender<UIModel>,
+    sender: &Sender<UIMessage>,
 ) {
     let scan_code = key_def.key.code();
 
@@ -328,8 +1168,8 @@ fn handle_swipe_action_press(
             send_mod_key(modifier.code(), scan_code, sender);
         }
         SwipeAction::Layer(side, idx) => {
-            sender.input(KeyMessage::Layer(*side, *idx).into());
-            sender.input(UIMessage::UpdateLayout);
+            sender.emit(KeyMessage::Layer(*side, *idx).into());
+            sender.emit(UIMessage::UpdateLayout);
         }
         SwipeAction::Arrow => {
             let key: evdev::Key = dir.into();
@@ -343,19 +1183,31 @@ fn handle_swipe_action_press(
             let key: evdev::Key = dir.into();
             send_mod_key(Modifier::Shift.code(), key.code(), sender);
         }
-        SwipeAction::Scroll => {
-            let msg = match dir {
-                Direction::Up => PointerMessage::ScrollUp,
-                Direction::Right => PointerMessage::ScrollRight,
-                Direction::Left => PointerMessage::ScrollLeft,
-                Direction::Down => PointerMessage::ScrollDown,
-            };
-            sender.input(msg.into());
+        SwipeAction::Command(command) => {
+            sender.emit(UIMessage::Command(command.clone()));
         }
-        SwipeAction::Command(Command { cmd, args, .. }) => {
-            sender.input(UIMessage::Command(cmd.clone(), args.clone()));
+        SwipeAction::Compositor(action) => {
+            sender.emit(UIMessage::Compositor(*action));
         }
-        SwipeAction::HideKeyboard => {
+        SwipeAction::Plugin(call) => {
+            sender.emit(UIMessage::Plugin(call.clone()));
+        }
+        SwipeAction::Script(script) => {
+            sender.emit(UIMessage::Script(script.clone()));
+        }
+        // Scrolling follows the drag instead (see `BasicKey::render`).
+        SwipeAction::Scroll => {}
+        // Transformed once the swipe is done, as the
+        // selection it acts on is replaced by it.
+        SwipeAction::Transform(_)
+        | SwipeAction::HideKeyboard
+        | SwipeAction::Profile(_)
+        | SwipeAction::Settings
+        | SwipeAction::Launcher
+        | SwipeAction::Complete
+        | SwipeAction::Language
+        | SwipeAction::Quit
+        | SwipeAction::Restart => {
             // Trigger this on release,
             // otherwise the keyboard is hidden
             // before release is triggered, which
@@ -368,27 +1220,27 @@ fn handle_swipe_action_repeat(
     key_def: &BasicKey,
     action: &SwipeAction,
     dir: Direction,
-    sender: &ComponentSender<UIModel>,
+    sender: &Sender<UIMessage>,
 ) {
     match action {
-        SwipeAction::Scroll | SwipeAction::Delete | SwipeAction::Select | SwipeAction::Arrow => {
+        SwipeAction::Delete | SwipeAction::Select | SwipeAction::Arrow => {
             handle_swipe_action_press(key_def, action, dir, sender)
         }
         _ => {}
     }
 }
 
-fn handle_swipe_action_release(
+pub fn handle_swipe_action_release(
     _key_def: &BasicKey,
     action: &SwipeAction,
     _dir: Direction,
-    sender: &ComponentSender<UIModel>,
+    sender: &Sender<UIMessage>,
 ) {
     match action {
         // Swipe-releasing is only relevant for the layer swipe action.
         SwipeAction::Layer(side, _) => {
-            sender.input(KeyMessage::Layer(*side, 0).into());
-            sender.input(UIMessage::UpdateLayout);
+            sender.emit(KeyMessage::Layer(*side, 0).into());
+            sender.emit(UIMessage::UpdateLayout);
         }
 
         // TODO the downside with this approach, which doesn't
@@ -396,11 +1248,37 @@ fn handle_swipe_action_release(
         // still delete one character on an empty selection;
         // i.e. normal backspace behavior.
         SwipeAction::Delete => {
+            sender.emit(KeyMessage::RememberDeleted.into());
             send_key(evdev::Key::KEY_BACKSPACE.code(), sender);
+            sender.emit(UIMessage::OfferUndoDelete);
         }
 
+        SwipeAction::Transform(transform) => {
+            sender.emit(KeyMessage::Transform(transform.clone()).into());
+        }
         SwipeAction::HideKeyboard => {
-            sender.input(UIMessage::HideKeyboard);
+            sender.emit(UIMessage::HideKeyboard);
+        }
+        SwipeAction::Profile(name) => {
+            sender.emit(UIMessage::Profile(name.clone()));
+        }
+        SwipeAction::Settings => {
+            sender.emit(UIMessage::ToggleSettings);
+        }
+        SwipeAction::Launcher => {
+            sender.emit(UIMessage::ToggleLauncher);
+        }
+        SwipeAction::Complete => {
+            sender.emit(UIMessage::ToggleCompletion);
+        }
+        SwipeAction::Language => {
+            sender.emit(UIMessage::NextLanguage);
+        }
+        SwipeAction::Quit => {
+            sender.emit(UIMessage::Quit);
+        }
+        SwipeAction::Restart => {
+            sender.emit(UIMessage::Restart);
         }
         _ => (),
     }
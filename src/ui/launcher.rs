@@ -0,0 +1,76 @@
+//! A launcher for installed applications,
+//! read from their `.desktop` entries.
+
+use gdk4::prelude::DisplayExt;
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self, gio,
+        prelude::{AccessibleExtManual, AppInfoExt, ButtonExt, WidgetExt},
+    },
+};
+
+use super::{UIMessage, UIModel, close_button};
+
+pub fn launcher(
+    width: i32,
+    height: i32,
+    icon_size: i32,
+    sender: &ComponentSender<UIModel>,
+) -> gtk::Overlay {
+    let grid = gtk::FlowBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .homogeneous(true)
+        .css_classes(["launcher"])
+        .build();
+
+    let mut apps = gio::AppInfo::all();
+    apps.retain(|app| app.should_show());
+    apps.sort_by_key(|app| app.display_name().to_lowercase());
+    for app in apps {
+        let name = app.display_name();
+        let icon = match app.icon() {
+            Some(icon) => gtk::Image::from_gicon(&icon),
+            None => gtk::Image::from_icon_name("application-x-executable"),
+        };
+        icon.set_pixel_size(icon_size);
+
+        let button = gtk::Button::builder()
+            .child(&icon)
+            .tooltip_text(name.as_str())
+            .css_classes(["launcher-app"])
+            .build();
+        button.update_property(&[gtk::accessible::Property::Label(&name)]);
+
+        let sender = sender.clone();
+        button.connect_clicked(move |button| {
+            // Launch via the display so the app
+            // gets activated/focused when it opens.
+            let context = button.display().app_launch_context();
+            if let Err(err) = app.launch(&[], Some(&context)) {
+                sender.input(UIMessage::CommandFailed(
+                    app.display_name().into(),
+                    err.to_string(),
+                ));
+            }
+            sender.input(UIMessage::ToggleLauncher);
+        });
+        grid.insert(&button, -1);
+    }
+
+    let scroll = gtk::ScrolledWindow::builder()
+        .child(&grid)
+        .width_request(width)
+        .height_request(height)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .build();
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&scroll));
+
+    let sender = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender.input(UIMessage::ToggleLauncher);
+    }));
+    overlay
+}
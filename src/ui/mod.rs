@@ -1,34 +1,299 @@
+mod accents;
+mod blur;
+mod cheatsheet;
+pub(crate) mod commands;
+mod crosshair;
+mod describe;
+mod fonts;
+mod gestures;
 mod glyphs;
+mod handwriting;
+mod hints;
 mod key;
-mod keyboard;
+pub mod keyboard;
+mod launcher;
+mod output;
+mod pinpad;
+mod reserve;
+mod suggestions;
 mod swipe;
+mod switcher;
+mod timer;
+mod toast;
+mod touch_lock;
+mod touchpad;
+mod user_style;
 
-use std::process::Command;
+use std::{
+    cell::Cell,
+    io::Write,
+    os::unix::{net::UnixStream, process::CommandExt},
+    path::{Path, PathBuf},
+    process::Command,
+    rc::Rc,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
 use gdk4::glib::{self, object::ObjectExt};
-use gtk::prelude::{ApplicationExt, GtkWindowExt, WidgetExt};
+use gdk4::prelude::{MonitorExt, SurfaceExt};
+use gtk::prelude::{
+    AccessibleExt, ApplicationExt, BoxExt, ButtonExt, Cast, EventControllerExt, GtkWindowExt,
+    NativeExt, WidgetExt, WidgetExtManual,
+};
 use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
 use relm4::{
     ComponentParts, ComponentSender, SimpleComponent,
-    gtk::{self, prelude::GtkApplicationExt},
+    gtk::{
+        self, gio,
+        prelude::{GtkApplicationExt, MemoryMonitorExt},
+    },
 };
+use tracing::{debug, error};
 
 use crate::{
+    calculator,
+    capture::{self, CaptureAction, Recording},
+    clipboard,
+    completion::{self, Completer},
+    compositor::{Compositor, CompositorAction},
+    config::{
+        Config, FocusMode, Language, LockBehavior, Setting, ShellLayer, ShowOnFocus, Theme,
+        TriggerStyle,
+    },
+    events::{Broadcaster, EVENTS_OFF, Event, Status},
+    fullscreen,
+    handwriting::Stroke,
+    haptics, hardware,
+    interaction::{self, Interaction},
+    ipc,
     keyboard::{KeyMessage, Keyboard},
-    layout::TriggerKey,
-    pointer::{Pointer, PointerMessage},
+    layout::{
+        self, CommandInsert, KeyContext, Layout, Modifier, Newlines, PasteOptions, Side, TriggerKey,
+    },
+    locale, lock,
+    plugin::{PluginAction, Plugins},
+    pointer::{Pointer, PointerError, PointerMessage, Scrolling},
+    power,
+    prediction::Dictionary,
+    script,
+    secret::{Secret, SecretSource},
+    session::{PURPOSE_PASSWORD, PURPOSE_PIN, PURPOSE_URL, keymap},
+    shortcut,
+    state::State,
+    transliterate::{self, Transliterator},
+    typing,
+    ui::keyboard::KEY_SIZE,
+    vim::Vim,
+    zhuyin::{Composer, Table},
 };
 
+use crosshair::Crosshair;
+use key::KeyButton;
+use reserve::Reservation;
+use suggestions::Suggestions;
+use timer::Timer;
+use toast::Toast;
+use user_style::UserStyle;
+
+/// The stack child name for a panel shown in place of the right half.
+const PANEL: &str = "panel";
+
+/// Shown when text can't be inserted directly.
+const NO_INPUT_METHOD: &str = "Inserting text isn't supported by the compositor";
+
+/// How many characters of the clipboard paste keys show.
+const CLIPBOARD_PREVIEW: usize = 12;
+
+/// How many completions are shown at once.
+const MAX_COMPLETIONS: usize = 4;
+
+/// How long to wait after a key is released for the
+/// text field to report its new text, before completing.
+const COMPLETION_DELAY: Duration = Duration::from_millis(50);
+
+/// How long to wait for the keyboard to be
+/// hidden before taking a screenshot.
+const SCREENSHOT_DELAY: Duration = Duration::from_millis(300);
+
+/// How long layers have to stay switched to before they're
+/// saved, so that passing through one (e.g. while swiping)
+/// isn't written to disk.
+const LAYER_SAVE_DELAY: Duration = Duration::from_secs(2);
+
 pub struct UIModel {
     /// We use two windows, one for each half of the keyboard.
     /// This lets input in the area between the two halves pass through.
     window: (gtk::Window, gtk::Window),
 
+    /// If set, reserves the keyboard's height
+    /// across the bottom while it's shown.
+    reservation: Option<Reservation>,
+
+    /// All layers of each half, with the active one visible.
+    slots: (gtk::Stack, gtk::Stack),
+
+    suggestions: Suggestions,
+    toast: Toast,
+
+    /// Shows the vi mode and any pending
+    /// count or operator while on a vim layer.
+    mode_indicator: gtk::Label,
+    vim: Vim,
+
+    /// The layers to go back to when leaving the vim layer.
+    vim_return: Option<(usize, usize)>,
+
+    /// The Zhuyin syllable being composed, the table of its
+    /// characters, loaded the first time it's used, and a key
+    /// whose press was taken for composing, so its release is too.
+    zhuyin: Composer,
+    zhuyin_table: Option<Table>,
+    zhuyin_swallowed: Option<u16>,
+
+    /// The Latin sequence being transliterated, the active
+    /// language's table, if it transliterates, and a key whose
+    /// press was taken for it, so its release is too.
+    transliterator: Transliterator,
+    transliteration: Option<transliterate::Table>,
+    transliteration_swallowed: Option<u16>,
+
     trigger: gtk::Window,
     keyboard: Keyboard,
     pointer: Pointer,
-    left: Vec<gtk::Overlay>,
-    right: Vec<gtk::Overlay>,
+    config: Config,
+    compositor: Compositor,
+    plugins: Plugins,
+    events: Broadcaster,
+
+    /// Provides the theme CSS, which is
+    /// replaced when the theme changes.
+    theme: gtk::CssProvider,
+
+    /// The user's own CSS, reloaded on changes.
+    user_style: UserStyle,
+
+    /// Controllers for the background gestures.
+    gestures: Vec<gtk::EventController>,
+
+    /// feedbackd, while haptics are on.
+    haptics: Option<gio::DBusProxy>,
+
+    /// Shown briefly after moving the pointer,
+    /// once it's first needed.
+    crosshair: Option<Crosshair>,
+
+    /// Watches for the session being locked.
+    _lock_watch: Option<gio::DBusProxy>,
+
+    /// Watches the battery and power profile, and whether
+    /// to save power because of them.
+    _power_watch: Option<power::Watch>,
+    saving_power: bool,
+
+    /// Listens for the global shortcut.
+    _shortcut: Option<gio::DBusProxy>,
+
+    /// Whether the session is locked, and if so
+    /// whether the keyboard was shown before locking.
+    locked: Option<bool>,
+
+    /// Cover the halves while they're locked against touches.
+    touch_shields: (gtk::Box, gtk::Box),
+    touch_locked: bool,
+
+    /// Signals when the system is low on memory.
+    _memory_monitor: gio::MemoryMonitor,
+
+    /// Whether completion was turned on with the `Complete` action.
+    /// It's also on for URL fields regardless.
+    complete: bool,
+
+    /// Loaded the first time completion is used.
+    completer: Option<Completer>,
+
+    /// The active language's word list, loaded
+    /// the first time it's used.
+    dictionary: Option<Dictionary>,
+
+    /// Whether a fullscreen window is hiding the keyboard,
+    /// and if so whether the keyboard was shown before.
+    fullscreen: Option<bool>,
+
+    /// When a physical keyboard was last typed on,
+    /// while that's hiding the trigger.
+    typed_at: Option<Instant>,
+
+    /// The focused app's id, for key conditions, and
+    /// the state they were last rendered for.
+    focused_app: Option<String>,
+    key_context: KeyContext,
+
+    /// The focused window, and the one focused when the keyboard
+    /// was shown, which hides it on closing with `hide_on_close`.
+    focused_window: Option<u32>,
+    shown_over: Option<u32>,
+
+    /// The clipboard's text, if watching it for paste keys.
+    clipboard: Option<String>,
+    /// Whether the clipboard is being watched.
+    watching_clipboard: bool,
+
+    /// Whether the screen was last touched or clicked,
+    /// if watching for that to decide on showing.
+    last_interaction: Option<Interaction>,
+
+    /// If set, a panel is shown in place of the right half.
+    panel: Option<(Panel, gtk::Overlay)>,
+
+    /// The handwriting panel's canvas, while it's shown.
+    canvas: Option<handwriting::Canvas>,
+
+    /// Whether the PIN pad was shown for a PIN field,
+    /// so it's hidden again once that's unfocused.
+    pin_pad_auto: bool,
+
+    /// Whether the PIN pad was closed in the focused field,
+    /// so it isn't shown for it again.
+    pin_pad_dismissed: bool,
+
+    /// The id of the scan underway, if any, and
+    /// the last id given out. Results of other
+    /// (i.e. cancelled) scans are dropped.
+    scanning: Option<u32>,
+    scans: u32,
+    /// The scanner helper's pid, to stop it if the scan is cancelled.
+    scanner: Option<u32>,
+    recording: Option<Recording>,
+
+    /// The layers last shown, to detect layer changes.
+    shown: Option<(usize, usize)>,
+
+    /// Periodically flushes coalesced pointer movement,
+    /// while there's movement.
+    pointer_tick: Timer,
+
+    /// Saves the state once layer changes have settled.
+    layer_save: Timer,
+
+    /// Whether a layout update is waiting for the next frame.
+    layout_pending: Rc<Cell<bool>>,
+
+    /// The layout switched to at runtime, if any,
+    /// and what was last saved to restore on launch.
+    layout_path: Option<PathBuf>,
+    state: State,
+}
+
+/// Alternatives to the right half of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Panel {
+    Touchpad,
+    Launcher,
+    CheatSheet,
+    Handwriting,
+    PinPad,
+    Switcher,
 }
 
 #[derive(Debug)]
@@ -39,29 +304,266 @@ pub enum UIMessage {
     /// Pass message to the pointer.
     Pointer(PointerMessage),
 
+    /// Nudge the pointer by a fixed amount,
+    /// scaled by any held modifiers.
+    PointerNudge(i32, i32),
+
     /// Execute a command.
-    Command(String, Vec<String>),
+    Command(layout::Command),
+
+    /// Run a command and insert its output.
+    CommandInsert(CommandInsert),
+
+    /// Fill in a template and insert it.
+    Template(String),
+
+    /// A vi command from a vim layer.
+    Vim(char),
+
+    /// A Zhuyin symbol to compose.
+    Zhuyin(char),
+
+    /// Insert a character for the composed Zhuyin syllable.
+    ZhuyinCommit(String),
+
+    /// Run a plugin action.
+    Plugin(PluginAction),
+
+    /// Run a script with the keyboard's state.
+    Script(layout::Script),
+
+    /// Insert text.
+    Insert(String),
+
+    /// Press a key with modifiers.
+    Chord(Vec<Modifier>, evdev::Key),
+
+    /// Fetch a secret and type it.
+    FetchSecret(SecretSource),
+
+    /// Type a fetched secret.
+    TypeSecret(Secret),
+
+    /// A command failed to start or exited with an error.
+    CommandFailed(String, String),
+
+    /// Trigger a compositor action.
+    Compositor(CompositorAction),
+
+    /// Briefly show a message over the keyboard.
+    Toast(String),
 
     /// Update displayed layouts.
+    /// Updates are coalesced to one per frame.
     UpdateLayout,
 
+    /// Render coalesced layout updates, on a frame tick.
+    RenderKeyboard,
+
+    /// Save the state, once layer changes have settled.
+    SaveState,
+
+    /// Re-render all layers, e.g. when
+    /// the keyboard moves to another output.
+    RenderLayers,
+
     /// Hide the keyboard.
     HideKeyboard,
 
     /// Show the keyboard.
     ShowKeyboard,
 
+    /// Show the keyboard if it's hidden, otherwise hide it.
+    ToggleKeyboard,
+
     /// Change the keyboard opacity.
     FadeKeyboard(i8),
 
+    /// Open or close the settings layer.
+    ToggleSettings,
+
+    /// Switch to the mouse layer or back,
+    /// showing the keyboard if it's hidden.
+    ToggleMouseLayer,
+
+    /// Show or hide the touchpad.
+    ToggleTouchpad,
+
+    /// Show or hide the app launcher.
+    ToggleLauncher,
+
+    /// Show or hide the cheat sheet of the active layers.
+    ToggleCheatSheet,
+
+    /// Show or hide the handwriting panel.
+    ToggleHandwriting,
+
+    /// Show or hide the PIN pad.
+    TogglePinPad,
+
+    /// Lock or unlock the keyboard against touches.
+    TouchLock(bool),
+    ToggleTouchLock,
+
+    /// Show or hide the language and profile switcher.
+    ToggleSwitcher,
+
+    /// Recognize strokes drawn on a canvas of the given size.
+    RecognizeHandwriting(Vec<Stroke>, (f64, f64)),
+
+    /// Offer what the handwriting could be,
+    /// for the canvas' generation when it was recognized.
+    HandwritingCandidates(u32, Vec<String>),
+
+    /// Insert a handwriting candidate and start over.
+    HandwritingCommit(String),
+
+    /// Start scanning a code, or cancel the scan underway.
+    Scan,
+
+    /// What a scan read, or why it failed.
+    Scanned(u32, Result<String, String>),
+
+    /// Change a setting.
+    Setting(Setting),
+
+    /// Switch to the named profile.
+    Profile(String),
+
+    /// Switch the theme.
+    Theme(Theme),
+
+    /// Offer to undo the last delete swipe.
+    OfferUndoDelete,
+
+    /// Turn completing URLs and paths on or off.
+    ToggleCompletion,
+
+    /// Switch to the next language.
+    NextLanguage,
+
+    /// Switch to the previous language.
+    PreviousLanguage,
+
+    /// Switch to the named language.
+    Language(String),
+
+    /// Show completions for the word before the cursor.
+    UpdateCompletions,
+
+    /// Show the result of the expression before the cursor.
+    UpdateCalculation,
+
+    /// Replace the text before the cursor (its length
+    /// in bytes) with a completion or result.
+    Complete(usize, String),
+
+    /// Completions for a path (what was typed) were read.
+    PathCompletions(String, Vec<String>),
+
+    /// Undo the last delete swipe.
+    UndoDelete,
+
+    /// The session was locked or unlocked.
+    SessionLocked(bool),
+
+    /// Whether to save power, e.g. as the battery is low.
+    SavePower(bool),
+
+    /// A fullscreen window was focused (with its app id),
+    /// or no longer is.
+    Fullscreen(Option<String>),
+
+    /// A window (with its id) was focused.
+    FocusedWindow(u32),
+
+    /// The focused window (with its id) closed or was minimized.
+    FocusedWindowGone(u32),
+
+    /// Another app (with its id) was focused.
+    FocusedApp(Option<String>),
+
+    /// The clipboard changed, to this text if it's text.
+    Clipboard(Option<String>),
+
+    /// Paste the clipboard.
+    Paste(PasteOptions),
+
+    /// Take a screenshot, or start or stop recording.
+    Capture(CaptureAction),
+
+    /// A screenshot was saved (to this URI) or cancelled.
+    Captured(Option<String>),
+
+    /// A recording is underway.
+    Recording(Recording),
+
+    /// The compositor connection has events waiting,
+    /// e.g. a text field being focused.
+    PollInput,
+
+    /// A touchscreen or pointing device was used.
+    Interaction(Interaction),
+
+    /// A physical keyboard was typed on.
+    HardwareTyping,
+
+    /// Typing on physical keyboards may have stopped.
+    TypingIdle,
+
+    /// Report the regions the keyboard occupies.
+    QueryGeometry(mpsc::Sender<String>),
+
+    /// Report the keyboard's state, as JSON.
+    QueryStatus(mpsc::Sender<String>),
+
+    /// Stream events to an IPC client.
+    Subscribe(UnixStream),
+
+    /// The user's CSS file changed.
+    ReloadStyle,
+
+    /// The system is low on memory.
+    LowMemory,
+
     /// Quit the application.
     Quit,
+
+    /// Restart the application,
+    /// re-executing it with the same arguments.
+    Restart,
 }
 impl From<KeyMessage> for UIMessage {
     fn from(value: KeyMessage) -> Self {
         Self::Keyboard(value)
     }
 }
+impl UIMessage {
+    /// Whether this is disabled while the session is locked,
+    /// i.e. it could run commands or reveal secrets.
+    fn is_restricted(&self) -> bool {
+        matches!(
+            self,
+            Self::Command(_)
+                | Self::CommandInsert(_)
+                | Self::Template(_)
+                | Self::Plugin(_)
+                | Self::Script(_)
+                | Self::FetchSecret(_)
+                | Self::RecognizeHandwriting(..)
+                | Self::Scan
+                | Self::Paste(_)
+                | Self::Capture(_)
+                | Self::ToggleLauncher
+                | Self::Setting(Setting::Launcher)
+                | Self::ToggleCompletion
+                | Self::Compositor(_)
+                | Self::Quit
+                | Self::Restart
+        )
+    }
+}
+
 impl From<PointerMessage> for UIMessage {
     fn from(value: PointerMessage) -> Self {
         Self::Pointer(value)
@@ -69,7 +571,7 @@ impl From<PointerMessage> for UIMessage {
 }
 
 impl SimpleComponent for UIModel {
-    type Init = (Keyboard, Pointer);
+    type Init = (Keyboard, Pointer, Config, State);
 
     type Input = UIMessage;
     type Output = ();
@@ -90,15 +592,14 @@ impl SimpleComponent for UIModel {
         window: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let (keyboard, pointer) = handle;
+        let (keyboard, pointer, config, state) = handle;
 
         // The main window hosts the button
         // to show the keyboard.
-        let trigger = setup_trigger_key(keyboard.trigger_key(), sender.clone());
-        window.init_layer_shell();
-        window.set_layer(Layer::Overlay);
-        window.set_keyboard_mode(KeyboardMode::None);
-        window.set_anchor(Edge::Right, true);
+        let trigger = setup_trigger_key(keyboard.trigger_key(), &config, sender.clone());
+        setup_layer_shell(&window, "trigger", &config);
+        // The pill is centered, between the halves.
+        window.set_anchor(Edge::Right, config.trigger == TriggerStyle::Corner);
         window.set_anchor(Edge::Bottom, true);
         window.set_child(Some(&trigger));
         window.set_visible(false);
@@ -108,27 +609,183 @@ impl SimpleComponent for UIModel {
             gtk::Window::builder().build(),
             gtk::Window::builder().build(),
         );
-        setup_window(&mut left, true);
-        setup_window(&mut right, false);
+        setup_window(&mut left, true, &config);
+        setup_window(&mut right, false, &config);
 
-        let left_halves: Vec<_> = keyboard
-            .left_layers()
-            .map(|layer| layer.render(sender.clone()))
-            .collect();
-        let right_halves: Vec<_> = keyboard
-            .right_layers()
-            .map(|layer| layer.render(sender.clone()))
-            .collect();
+        let slots = (layer_stack(), layer_stack());
+        let suggestions = Suggestions::new(sender.input_sender().clone());
+        suggestions.set_language(config.active_language().map(|lang| lang.name.as_str()));
+        let left_content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        left_content.append(suggestions.widget());
+        left_content.append(&slots.0);
+        let toast = Toast::new();
+        let touch_shields = (touch_lock::shield(), touch_lock::shield());
+        let left_overlay = gtk::Overlay::new();
+        left_overlay.set_child(Some(&left_content));
+        left_overlay.add_overlay(&touch_shields.0);
+        left_overlay.add_overlay(toast.widget());
+        let mode_indicator = gtk::Label::builder()
+            .css_classes(["mode-indicator"])
+            .halign(gtk::Align::Start)
+            .valign(gtk::Align::Start)
+            .can_target(false)
+            .visible(false)
+            .build();
+        left_overlay.add_overlay(&mode_indicator);
+        left.set_child(Some(&left_overlay));
+        let right_overlay = gtk::Overlay::new();
+        right_overlay.set_child(Some(&slots.1));
+        right_overlay.add_overlay(&touch_shields.1);
+        right.set_child(Some(&right_overlay));
+        touch_lock::watch((&left, &right), &sender);
+
+        let theme = gtk::CssProvider::new();
+        gtk::style_context_add_provider_for_display(
+            &window.display(),
+            &theme,
+            gtk::STYLE_PROVIDER_PRIORITY_USER + 1,
+        );
+
+        let user_style = UserStyle::new(
+            &window.display(),
+            &config.style_path(),
+            sender.input_sender().clone(),
+        );
+
+        let lock_watch = lock::watch(sender.input_sender().clone())
+            .inspect_err(|err| error!("Failed to watch for session lock: {}", err))
+            .ok();
+        let power_watch = config
+            .power_saving
+            .enabled()
+            .then(|| {
+                power::watch(config.power_saving, sender.input_sender().clone())
+                    .inspect_err(|err| error!("Failed to watch the battery: {}", err))
+                    .ok()
+            })
+            .flatten();
+        let shortcut = config.shortcut.as_ref().and_then(|trigger| {
+            shortcut::bind(trigger, sender.input_sender().clone())
+                .inspect_err(|err| error!("Failed to bind the global shortcut: {}", err))
+                .ok()
+        });
+
+        let memory_monitor = gio::MemoryMonitor::dup_default();
+        let sender_cb = sender.clone();
+        memory_monitor.connect_low_memory_warning(move |_, level| {
+            debug!("Low memory warning: {:?}", level);
+            sender_cb.input(UIMessage::LowMemory);
+        });
+
+        let reservation = config.reserve_space.then(|| Reservation::new(&config));
 
-        let model = UIModel {
+        let transliteration = config
+            .active_language()
+            .and_then(|lang| lang.transliterate.as_ref())
+            .map(transliterate::Table::load);
+
+        let mut model = UIModel {
             pointer,
             keyboard,
+            config,
+            compositor: Compositor::detect(),
+            plugins: Plugins::default(),
+            events: Broadcaster::default(),
+            theme,
+            user_style,
+            gestures: vec![],
+            haptics: None,
             trigger: window,
             window: (left, right),
-            left: left_halves,
-            right: right_halves,
+            reservation,
+            slots,
+            suggestions,
+            toast,
+            mode_indicator,
+            vim: Vim::default(),
+            vim_return: None,
+            zhuyin: Composer::default(),
+            zhuyin_table: None,
+            zhuyin_swallowed: None,
+            transliterator: Transliterator::default(),
+            transliteration,
+            transliteration_swallowed: None,
+            crosshair: None,
+            _lock_watch: lock_watch,
+            _power_watch: power_watch,
+            saving_power: false,
+            _shortcut: shortcut,
+            locked: None,
+            touch_shields,
+            touch_locked: false,
+            _memory_monitor: memory_monitor,
+            complete: false,
+            completer: None,
+            dictionary: None,
+            fullscreen: None,
+            typed_at: None,
+            focused_window: None,
+            shown_over: None,
+            last_interaction: None,
+            focused_app: None,
+            key_context: KeyContext::default(),
+            clipboard: None,
+            watching_clipboard: false,
+            panel: None,
+            canvas: None,
+            pin_pad_auto: false,
+            pin_pad_dismissed: false,
+            scanning: None,
+            recording: None,
+            scans: 0,
+            scanner: None,
+            shown: None,
+            pointer_tick: Timer::default(),
+            layer_save: Timer::default(),
+            layout_pending: Rc::default(),
+            layout_path: state.layout.clone(),
+            state,
         };
-        model.render_keyboard();
+        model.restore_state(&sender);
+        model.update_haptics();
+        model.apply_theme();
+        model.attach_gestures(&sender);
+        if model.config.blur {
+            model.request_blur(&sender);
+        }
+        model.render_keyboard(&sender);
+
+        ipc::listen(sender.input_sender().clone());
+        fullscreen::watch(sender.input_sender().clone());
+        if model.config.hide_trigger_typing.is_some() {
+            typing::watch(sender.input_sender().clone());
+        }
+        if !model.config.hardware_keys.is_empty() {
+            hardware::watch(
+                model.config.hardware_keys.clone(),
+                sender.input_sender().clone(),
+            );
+        }
+        model
+            .keyboard
+            .set_release_on_hide(model.config.release_on_hide);
+        if model.config.show_on_focus != ShowOnFocus::Never {
+            model.keyboard.set_watch_focus(true);
+            if let Some(fd) = model.keyboard.connection_fd() {
+                let sender = sender.clone();
+                glib::unix_fd_add_local(fd, glib::IOCondition::IN, move |_, condition| {
+                    if condition.contains(glib::IOCondition::HUP) {
+                        return glib::ControlFlow::Break;
+                    }
+                    sender.input(UIMessage::PollInput);
+                    glib::ControlFlow::Continue
+                });
+            }
+        }
+        model.watch_clipboard(&sender);
+        if model.config.show_on_focus == ShowOnFocus::Touch {
+            interaction::watch(sender.input_sender().clone());
+        }
 
         // Then we manually add our two windows
         // to the application.
@@ -139,48 +796,603 @@ impl SimpleComponent for UIModel {
         model.window.1.present();
         model.window.0.set_visible(false);
         model.window.1.set_visible(false);
+        model.keyboard.suspend();
+
+        // Key sizes depend on the output.
+        if let Some(surface) = model.window.0.surface() {
+            let sender = sender.clone();
+            surface.connect_enter_monitor(move |_, _| {
+                sender.input(UIMessage::RenderLayers);
+            });
+        }
 
         ComponentParts { model, widgets: () }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>) {
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>) {
+        if self.restricted() && msg.is_restricted() {
+            debug!("Ignoring while locked: {:?}", msg);
+            self.toast.show("Not available while locked");
+            return;
+        }
+        let pressed = matches!(
+            msg,
+            UIMessage::Keyboard(
+                KeyMessage::ButtonPress(_) | KeyMessage::ModPress(_) | KeyMessage::LockPress(_)
+            )
+        );
+        if let Some(feedback) = self.haptics.as_ref().filter(|_| pressed) {
+            haptics::key_press(feedback);
+        }
         match msg {
+            UIMessage::Keyboard(msg) if self.is_composing_key(&msg) => {
+                self.compose_key(msg, &sender);
+            }
+            UIMessage::Keyboard(msg) if self.is_transliterated_key(&msg) => {
+                self.transliterate_key(msg, &sender);
+            }
             UIMessage::Keyboard(msg) => {
+                let released = matches!(msg, KeyMessage::ButtonRelease(_));
+                let key_event = match msg {
+                    KeyMessage::ButtonPress(code) => Some((code, true)),
+                    KeyMessage::ButtonRelease(code) => Some((code, false)),
+                    _ => None,
+                };
+                let mods_changed =
+                    matches!(msg, KeyMessage::ModPress(_) | KeyMessage::ModRelease(_));
+                let locks_changed =
+                    matches!(msg, KeyMessage::LockPress(_) | KeyMessage::LockRelease(_));
+                let selection_changed = matches!(msg, KeyMessage::Selecting(_));
+                let layer_changed = matches!(msg, KeyMessage::Layer(..));
+                if key_event == Some((evdev::Key::KEY_SPACE.code(), true)) {
+                    self.autocorrect();
+                }
+                let was_vim = self.keyboard.vim_active();
+                let layers = self.keyboard.layer;
                 self.keyboard.handle(msg);
+                if layer_changed && !was_vim && self.keyboard.vim_active() {
+                    self.vim_return = Some(layers);
+                }
+                if mods_changed {
+                    self.update_hints();
+                }
+                if selection_changed {
+                    self.update_mode_indicator();
+                }
+                if layer_changed {
+                    let sender = sender.clone();
+                    self.layer_save.once(LAYER_SAVE_DELAY, move || {
+                        sender.input(UIMessage::SaveState);
+                    });
+                }
+                // Keys may be conditional on locks.
+                if locks_changed {
+                    sender.input(UIMessage::UpdateLayout);
+                }
+                if let Some((code, pressed)) = key_event {
+                    let key = evdev::Key::new(code);
+                    self.events
+                        .send(Event::Key(key, pressed), self.config.event_keys);
+                }
+                if mods_changed && self.events.has_subscribers() {
+                    let modifiers = self.active_modifiers();
+                    self.events
+                        .send(Event::Modifiers(&modifiers), self.config.event_keys);
+                }
+                // Decide now, as a held layer may be released by then.
+                let update = if self.calculating() {
+                    Some(UIMessage::UpdateCalculation)
+                } else if self.completing() {
+                    Some(UIMessage::UpdateCompletions)
+                } else {
+                    None
+                };
+                if let Some(update) = update.filter(|_| released) {
+                    glib::timeout_add_local_once(COMPLETION_DELAY, move || {
+                        sender.input(update);
+                    });
+                }
+            }
+            UIMessage::Pointer(PointerMessage::Flush) => {
+                // Stop ticking once movement has stopped.
+                if !self.pointer.has_pending() {
+                    self.pointer_tick.stop();
+                } else if self.config.crosshair && self.driving_pointer() {
+                    let movement = self.pointer.pending();
+                    self.crosshair
+                        .get_or_insert_with(Crosshair::new)
+                        .flash(movement);
+                }
+                self.handle_pointer(PointerMessage::Flush);
             }
             UIMessage::Pointer(msg) => {
-                self.pointer.handle(msg).unwrap();
+                self.handle_pointer(msg);
+                if self.pointer.has_pending() && !self.pointer_tick.is_pending() {
+                    self.start_pointer_tick(&sender);
+                }
+            }
+            UIMessage::PointerNudge(dx, dy) => {
+                let scale = self.nudge_scale();
+
+                // Pointer movement is positive upwards.
+                sender.input(PointerMessage::Move(dx * scale, -dy * scale).into());
+            }
+            UIMessage::Command(command) => {
+                commands::run(command, sender.input_sender().clone());
+            }
+            UIMessage::CommandInsert(command) => {
+                if self.keyboard.has_input_method() {
+                    commands::run_insert(command, sender.input_sender().clone());
+                } else {
+                    self.toast.show(NO_INPUT_METHOD);
+                }
+            }
+            UIMessage::Template(template) => {
+                if self.keyboard.has_input_method() {
+                    self.keyboard.refresh();
+                    let selection = self
+                        .keyboard
+                        .surrounding_text()
+                        .and_then(|text| text.selection())
+                        .map(str::to_string);
+                    commands::insert_template(template, selection, sender.input_sender().clone());
+                } else {
+                    self.toast.show(NO_INPUT_METHOD);
+                }
+            }
+            UIMessage::Vim(c) => {
+                let output = self.vim.handle(c);
+                for (modifiers, key) in output.chords {
+                    let modifiers = modifiers.iter().map(Modifier::code).collect();
+                    keyboard::send_mods_key(modifiers, key.code(), sender.input_sender());
+                }
+                if !output.text.is_empty() {
+                    sender.input(UIMessage::Insert(output.text));
+                }
+                if output.insert {
+                    let (left, right) = self.vim_return.take().unwrap_or_default();
+                    sender.input(KeyMessage::Layer(Side::Left, left).into());
+                    sender.input(KeyMessage::Layer(Side::Right, right).into());
+                    sender.input(UIMessage::UpdateLayout);
+                }
+                self.update_mode_indicator();
+            }
+            UIMessage::Zhuyin(c) => {
+                if self.keyboard.has_input_method() {
+                    self.zhuyin.push(c);
+                    self.update_zhuyin(&sender);
+                } else {
+                    self.toast.show(NO_INPUT_METHOD);
+                }
+            }
+            UIMessage::ZhuyinCommit(text) => {
+                self.commit_zhuyin(text);
+            }
+            UIMessage::Plugin(call) => {
+                commands::run_plugin(self.plugins.clone(), call, sender.input_sender().clone());
+            }
+            UIMessage::Script(script) => {
+                self.keyboard.refresh();
+                // Scripts aren't trusted with passwords.
+                let secret = self.keyboard.is_secret_field();
+                let state = script::State {
+                    layers: self.keyboard.layer,
+                    modifiers: self.active_modifiers(),
+                    text: self
+                        .keyboard
+                        .surrounding_text()
+                        .filter(|_| !secret)
+                        .cloned(),
+                };
+                commands::run_script(script, state, sender.input_sender().clone());
+            }
+            UIMessage::Chord(modifiers, key) => {
+                let modifiers = modifiers.iter().map(Modifier::code).collect();
+                keyboard::send_mods_key(modifiers, key.code(), &sender);
+            }
+            UIMessage::Insert(text) => {
+                if self.keyboard.has_input_method() {
+                    self.keyboard.handle(KeyMessage::Commit(text));
+                } else {
+                    self.toast.show(NO_INPUT_METHOD);
+                }
             }
-            UIMessage::Command(cmd, args) => {
-                #[allow(clippy::zombie_processes)]
-                Command::new(cmd)
-                    .args(args)
-                    .spawn()
-                    .expect("Command failed to start");
+            UIMessage::FetchSecret(source) => {
+                commands::fetch_secret(&source, sender.input_sender().clone());
             }
+            UIMessage::TypeSecret(secret) => {
+                self.keyboard.handle(KeyMessage::TypeSecret(secret));
+            }
+            UIMessage::CommandFailed(cmd, reason) => {
+                error!("Command `{}` failed: {}", cmd, reason);
+                self.toast
+                    .show(&format!("{} failed: {}", cmd, truncate(&reason, 80)));
+            }
+            UIMessage::Toast(text) => {
+                self.toast.show(&text);
+            }
+            UIMessage::Compositor(action) => match self.compositor.command(action) {
+                Some((cmd, args)) => {
+                    sender.input(UIMessage::Command(layout::Command::new(cmd, args)))
+                }
+                None => match action.shortcut().filter(|_| !self.compositor.has_ipc()) {
+                    Some((modifiers, key)) => {
+                        let modifiers = modifiers.iter().map(Modifier::code).collect();
+                        keyboard::send_mods_key(modifiers, key.code(), sender.input_sender());
+                    }
+                    None => {
+                        error!("{:?} isn't supported on this compositor", action);
+                        self.toast.show("Not supported on this compositor");
+                    }
+                },
+            },
             UIMessage::UpdateLayout => {
-                self.render_keyboard();
+                // Frame ticks only happen while the window is shown.
+                if !self.window.0.is_visible() {
+                    self.render_keyboard(&sender);
+                } else if !self.layout_pending.replace(true) {
+                    let pending = self.layout_pending.clone();
+                    let sender = sender.clone();
+                    self.window.0.add_tick_callback(move |_, _| {
+                        pending.set(false);
+                        sender.input(UIMessage::RenderKeyboard);
+                        glib::ControlFlow::Break
+                    });
+                }
+            }
+            UIMessage::RenderKeyboard => {
+                self.render_keyboard(&sender);
+            }
+            UIMessage::SaveState => {
+                self.save_state();
+            }
+            UIMessage::RenderLayers => {
+                self.clear_layers();
+                self.render_keyboard(&sender);
             }
             UIMessage::HideKeyboard => {
                 self.hide_keyboard();
             }
             UIMessage::ShowKeyboard => {
-                self.show_keyboard();
+                self.show_keyboard(&sender);
+            }
+            UIMessage::ToggleKeyboard => {
+                if self.window.0.is_visible() {
+                    self.hide_keyboard();
+                } else {
+                    self.show_keyboard(&sender);
+                }
             }
             UIMessage::FadeKeyboard(change) => {
                 self.fade_keyboard(change);
             }
+            UIMessage::ToggleSettings => {
+                let open = self.keyboard.settings_open();
+                self.keyboard.handle(KeyMessage::SettingsLayer(!open));
+                self.render_keyboard(&sender);
+            }
+            UIMessage::ToggleMouseLayer => {
+                let active = self.keyboard.mouse_layer_active();
+                self.keyboard.handle(KeyMessage::MouseLayer(!active));
+                if self.window.0.is_visible() {
+                    self.render_keyboard(&sender);
+                } else {
+                    self.show_keyboard(&sender);
+                }
+            }
+            UIMessage::ToggleTouchpad => {
+                self.toggle_panel(Panel::Touchpad, &sender);
+            }
+            UIMessage::ToggleLauncher => {
+                self.toggle_panel(Panel::Launcher, &sender);
+            }
+            UIMessage::ToggleCheatSheet => {
+                self.toggle_panel(Panel::CheatSheet, &sender);
+            }
+            UIMessage::TouchLock(locked) => {
+                self.set_touch_lock(locked, &sender);
+            }
+            UIMessage::ToggleTouchLock => {
+                self.set_touch_lock(!self.touch_locked, &sender);
+            }
+            UIMessage::ToggleSwitcher => {
+                self.toggle_panel(Panel::Switcher, &sender);
+            }
+            UIMessage::TogglePinPad => {
+                self.pin_pad_auto = false;
+                self.pin_pad_dismissed = matches!(self.panel, Some((Panel::PinPad, _)));
+                self.toggle_panel(Panel::PinPad, &sender);
+            }
+            UIMessage::ToggleHandwriting => {
+                self.toggle_panel(Panel::Handwriting, &sender);
+                if self.canvas.is_none() {
+                    self.suggestions.clear();
+                    self.show_candidates(&[]);
+                }
+            }
+            UIMessage::RecognizeHandwriting(strokes, size) => match &self.config.handwriting {
+                Some(def) => {
+                    let generation = self.canvas.as_ref().map(handwriting::Canvas::generation);
+                    commands::recognize_handwriting(
+                        def,
+                        strokes,
+                        size,
+                        generation.unwrap_or_default(),
+                        sender.input_sender().clone(),
+                    );
+                }
+                None => self.toast.show("No handwriting recognizer is configured"),
+            },
+            UIMessage::HandwritingCandidates(generation, candidates) => {
+                // The panel may have closed, or more been written
+                // (or the canvas cleared), while recognizing.
+                let current = self.canvas.as_ref().map(handwriting::Canvas::generation);
+                if current != Some(generation) {
+                    return;
+                }
+                self.suggestions.clear();
+                let candidates: Vec<_> = candidates.into_iter().take(MAX_COMPLETIONS).collect();
+                self.show_candidates(&candidates);
+                for candidate in candidates {
+                    let label = truncate(&candidate, 32);
+                    let sender = sender.clone();
+                    self.suggestions.add(&label, move || {
+                        sender.input(UIMessage::HandwritingCommit(candidate.clone()));
+                    });
+                }
+            }
+            UIMessage::Scan => {
+                self.toggle_scan(&sender);
+            }
+            UIMessage::Scanned(id, result) => {
+                // Otherwise the scan was cancelled.
+                if self.scanning == Some(id) {
+                    self.scanning = None;
+                    self.scanner = None;
+                    self.suggestions.clear();
+                    match result {
+                        Ok(text) if text.is_empty() => self.toast.show("Nothing scanned"),
+                        Ok(text) => sender.input(UIMessage::Insert(text)),
+                        Err(reason) => {
+                            let cmd = self.config.scanner.as_ref().map(|s| s.cmd.clone());
+                            sender.input(UIMessage::CommandFailed(cmd.unwrap_or_default(), reason));
+                        }
+                    }
+                }
+            }
+            UIMessage::Capture(action) => {
+                self.capture(action, &sender);
+            }
+            UIMessage::Captured(uri) => {
+                self.show_keyboard(&sender);
+                if uri.is_some() {
+                    self.toast.show("Screenshot saved");
+                }
+            }
+            UIMessage::Recording(recording) => {
+                self.recording = Some(recording);
+                self.toast.show("Recording, tap ⏺ to stop");
+            }
+            UIMessage::HandwritingCommit(text) => {
+                if let Some(canvas) = &self.canvas {
+                    canvas.clear();
+                }
+                self.suggestions.clear();
+                self.show_candidates(&[]);
+                sender.input(UIMessage::Insert(text));
+            }
+            UIMessage::Setting(setting) => {
+                self.change_setting(setting, &sender);
+            }
+            UIMessage::Profile(name) => {
+                self.use_profile(&name, &sender);
+            }
+            UIMessage::OfferUndoDelete => {
+                self.suggestions.clear();
+                // Without the input method the text can't be put back.
+                let deleted = self
+                    .keyboard
+                    .deleted()
+                    .filter(|_| self.keyboard.has_input_method());
+                if let Some(deleted) = deleted {
+                    let label = format!("↶ {}", truncate(deleted, 16));
+                    let sender = sender.clone();
+                    self.suggestions
+                        .add(&label, move || sender.input(UIMessage::UndoDelete));
+                }
+            }
+            UIMessage::ToggleCompletion => {
+                self.complete = !self.complete;
+                if self.complete {
+                    self.toast.show("Completion on");
+                    sender.input(UIMessage::UpdateCompletions);
+                } else {
+                    self.toast.show("Completion off");
+                    self.suggestions.clear();
+                }
+            }
+            UIMessage::NextLanguage => {
+                let Some(language) = self.config.next_language().cloned() else {
+                    self.toast.show("No languages are configured");
+                    return;
+                };
+                self.use_language(language, &sender);
+            }
+            UIMessage::PreviousLanguage => {
+                let Some(language) = self.config.previous_language().cloned() else {
+                    self.toast.show("No languages are configured");
+                    return;
+                };
+                self.use_language(language, &sender);
+            }
+            UIMessage::Language(name) => {
+                let Some(language) = self.config.use_language(&name).cloned() else {
+                    error!("No language named: {}", name);
+                    self.toast.show(&format!("No language named {}", name));
+                    return;
+                };
+                self.use_language(language, &sender);
+            }
+            UIMessage::UpdateCompletions => {
+                self.update_completions(&sender);
+            }
+            UIMessage::PathCompletions(path, completions) => {
+                // What's typed may have moved on since.
+                self.keyboard.refresh();
+                let current = self
+                    .keyboard
+                    .surrounding_text()
+                    .and_then(completion::current_word);
+                if self.completing() && current == Some(path.as_str()) {
+                    self.suggestions.clear();
+                    self.offer_completions(path.len(), completions, &sender);
+                }
+            }
+            UIMessage::UpdateCalculation => {
+                self.update_calculation(&sender);
+            }
+            UIMessage::Complete(len, text) => {
+                self.keyboard
+                    .handle(KeyMessage::ReplaceBefore(len as u32, text));
+                self.suggestions.clear();
+            }
+            UIMessage::UndoDelete => {
+                self.keyboard.handle(KeyMessage::UndoDelete);
+                self.suggestions.clear();
+            }
+            UIMessage::Theme(theme) => {
+                self.config.set_theme(theme);
+                self.save_state();
+                self.apply_theme();
+                self.clear_layers();
+                self.render_keyboard(&sender);
+            }
+            UIMessage::SessionLocked(locked) => {
+                self.set_locked(locked, &sender);
+            }
+            UIMessage::SavePower(save) => {
+                self.save_power(save);
+            }
+            UIMessage::Fullscreen(app_id) => {
+                self.set_fullscreen(app_id, &sender);
+            }
+            UIMessage::PollInput => {
+                if self.keyboard.poll() {
+                    self.pin_pad_dismissed = false;
+                    self.cancel_zhuyin();
+                    self.cancel_transliteration();
+                    self.text_field_focused(&sender);
+                }
+                if self.window.0.is_visible() {
+                    self.update_pin_pad(&sender);
+                }
+            }
+            UIMessage::Interaction(interaction) => {
+                self.last_interaction = Some(interaction);
+            }
+            UIMessage::Clipboard(text) => {
+                self.clipboard = text;
+                if self.window.0.is_visible() {
+                    sender.input(UIMessage::UpdateLayout);
+                }
+            }
+            UIMessage::Paste(options) => match &self.clipboard {
+                None => self.toast.show("Nothing to paste"),
+                Some(text) if text.chars().count() > options.limit => {
+                    self.toast.show("The clipboard is too long to paste");
+                }
+                Some(text) => {
+                    sender.input(UIMessage::Insert(options.newlines.apply(text)));
+                }
+            },
+            UIMessage::FocusedApp(app_id) => {
+                self.focused_app = app_id;
+                if self.window.0.is_visible() {
+                    sender.input(UIMessage::UpdateLayout);
+                }
+            }
+            UIMessage::FocusedWindow(id) => {
+                self.focused_window = Some(id);
+            }
+            UIMessage::FocusedWindowGone(id) => {
+                self.focused_window = None;
+                let shown_over = self.shown_over == Some(id);
+                if self.config.hide_on_close && shown_over && self.window.0.is_visible() {
+                    self.hide_keyboard();
+                }
+            }
+            UIMessage::HardwareTyping => {
+                if let Some(delay) = self.config.hide_trigger_typing {
+                    self.typed_at = Some(Instant::now());
+                    self.trigger.set_visible(false);
+                    let sender = sender.clone();
+                    glib::timeout_add_local_once(Duration::from_millis(delay), move || {
+                        sender.input(UIMessage::TypingIdle);
+                    });
+                }
+            }
+            UIMessage::TypingIdle => {
+                let idle = self.config.hide_trigger_typing.is_some_and(|delay| {
+                    self.typed_at
+                        .is_some_and(|at| at.elapsed() >= Duration::from_millis(delay))
+                });
+                if idle {
+                    self.typed_at = None;
+                    let hidden = self.window.0.is_visible() || self.fullscreen.is_some();
+                    self.trigger.set_visible(!hidden);
+                }
+            }
+            UIMessage::QueryGeometry(reply) => {
+                let _ = reply.send(self.geometry());
+            }
+            UIMessage::QueryStatus(reply) => {
+                let _ = reply.send(self.status().to_json());
+            }
+            UIMessage::Subscribe(mut stream) => {
+                if self.config.events {
+                    self.events.subscribe(stream);
+                    let status = self.status();
+                    self.events
+                        .send(Event::Status(&status), self.config.event_keys);
+                } else {
+                    let _ = writeln!(stream, "error: {}", EVENTS_OFF);
+                }
+            }
+            UIMessage::ReloadStyle => {
+                self.user_style.reload();
+            }
+            UIMessage::LowMemory => {
+                self.trim_layers();
+            }
             UIMessage::Quit => {
+                self.save_state();
                 self.keyboard.destroy();
+                ipc::cleanup();
                 relm4::main_application().quit();
             }
+            UIMessage::Restart => {
+                // Our connections close on exec, so nothing
+                // is torn down in case restarting fails.
+                self.release_held_keys();
+                self.save_state();
+                let err = restart();
+                error!("Failed to restart: {}", err);
+                self.toast.show(&format!("Failed to restart: {}", err));
+            }
         }
     }
 }
 
-fn setup_trigger_key(trigger_key: &TriggerKey, sender: ComponentSender<UIModel>) -> gtk::Widget {
+fn setup_trigger_key(
+    trigger_key: &TriggerKey,
+    config: &Config,
+    sender: ComponentSender<UIModel>,
+) -> gtk::Widget {
+    if config.trigger == TriggerStyle::Pill {
+        return keyboard::pill_trigger(config, sender.input_sender());
+    }
     let trigger = trigger_key.as_key();
-    let trigger = trigger.render(8, &sender);
+    let trigger = trigger.render(8, config, &KeyContext::default(), sender.input_sender());
     trigger.set_css_classes(&["trigger"]);
 
     let sender_cb = sender.clone();
@@ -193,16 +1405,141 @@ fn setup_trigger_key(trigger_key: &TriggerKey, sender: ComponentSender<UIModel>)
     trigger
 }
 
-/// Setup the window for a half of the keyboard.
-fn setup_window(window: &mut gtk::Window, is_left: bool) {
+/// Replace the current process with a fresh instance,
+/// which will reconnect to Wayland and the uinput device.
+/// Only returns if that failed.
+fn restart() -> std::io::Error {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return err,
+    };
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    Command::new(exe).args(args).exec()
+}
+
+/// A small button for the top corner of a panel,
+/// calling `on_close` when clicked.
+fn close_button(on_close: impl Fn() + 'static) -> gtk::Button {
+    let close = gtk::Button::builder()
+        .label("✕")
+        .halign(gtk::Align::End)
+        .valign(gtk::Align::Start)
+        .css_classes(["panel-close"])
+        .build();
+    close.connect_clicked(move |_| on_close());
+    close
+}
+
+/// Holds the layers of a half. Each layer
+/// keeps its own size rather than the largest's.
+fn layer_stack() -> gtk::Stack {
+    let stack = gtk::Stack::new();
+    stack.set_hhomogeneous(false);
+    stack.set_vhomogeneous(false);
+    stack
+}
+
+/// Call `f` on every widget under this one.
+fn descendants(widget: &gtk::Widget, f: &mut impl FnMut(&gtk::Widget)) {
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        f(&widget);
+        descendants(&widget, f);
+        child = widget.next_sibling();
+    }
+}
+
+/// Remove rendered layers from a half's stack,
+/// except for those to keep.
+fn drop_layers(stack: &gtk::Stack, keep: &[usize]) {
+    let keep: Vec<String> = keep.iter().map(|layer| layer_name(*layer)).collect();
+    let mut child = stack.first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        let name = stack.page(&widget).name();
+        if name.is_some_and(|name| name.starts_with("layer-") && !keep.contains(&name.to_string()))
+        {
+            stack.remove(&widget);
+        }
+    }
+}
+
+/// Show a layer in a half's stack,
+/// rendering it first if it hasn't been yet.
+fn show_layer(stack: &gtk::Stack, layer: usize, render: impl FnOnce() -> Option<gtk::Overlay>) {
+    let name = layer_name(layer);
+    if stack.child_by_name(&name).is_none() {
+        match render() {
+            Some(widget) => {
+                stack.add_named(&widget, Some(&name));
+            }
+            None => return,
+        }
+    }
+    stack.set_visible_child_name(&name);
+}
+
+/// Size the window to its current content. Layers can differ
+/// in size (e.g. a two-row nav layer), and otherwise the window
+/// would only ever grow to fit the largest layer shown so far.
+fn fit_to_content(window: &gtk::Window) {
+    window.set_default_size(1, 1);
+    window.queue_resize();
+}
+
+fn layer_name(layer: usize) -> String {
+    format!("layer-{}", layer)
+}
+
+/// Shorten text for display, marking if it was cut off.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() > max_chars {
+        let text: String = text.chars().take(max_chars).collect();
+        format!("{}…", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Make the window a layer surface, per the config.
+fn setup_layer_shell(window: &gtk::Window, name: &str, config: &Config) {
     window.init_layer_shell();
-    window.set_layer(Layer::Overlay);
-    window.set_keyboard_mode(KeyboardMode::None);
+    window.set_namespace(&namespace(config, name));
+    window.set_layer(match config.layer {
+        ShellLayer::Overlay => Layer::Overlay,
+        ShellLayer::Top => Layer::Top,
+    });
+    window.set_keyboard_mode(match config.focus {
+        FocusMode::None => KeyboardMode::None,
+        FocusMode::OnDemand => KeyboardMode::OnDemand,
+    });
+}
+
+/// The layer-shell namespace for one of the windows,
+/// which compositors can use to apply rules to it.
+fn namespace(config: &Config, name: &str) -> String {
+    format!("{}-{}", config.namespace, name)
+}
+
+/// Setup the window for a half of the keyboard.
+fn setup_window(window: &mut gtk::Window, is_left: bool, config: &Config) {
+    setup_layer_shell(window, if is_left { "left" } else { "right" }, config);
     window.set_opacity(0.8);
 
+    // Space is reserved separately, so the
+    // halves shouldn't be pushed above it.
+    if config.reserve_space {
+        window.set_exclusive_zone(-1);
+    }
+
+    anchor_window(window, is_left != config.swap_halves);
+}
+
+/// Anchor a half to the bottom left or right corner.
+fn anchor_window(window: &gtk::Window, on_left: bool) {
     let anchors = [
-        (Edge::Left, is_left),
-        (Edge::Right, !is_left),
+        (Edge::Left, on_left),
+        (Edge::Right, !on_left),
         (Edge::Top, false),
         (Edge::Bottom, true),
     ];
@@ -212,29 +1549,1213 @@ fn setup_window(window: &mut gtk::Window, is_left: bool) {
 }
 
 impl UIModel {
-    fn render_keyboard(&self) {
+    /// Drop all rendered layers, e.g. when settings that
+    /// affect their rendering change. Layers are rendered
+    /// again as they're shown.
+    fn clear_layers(&mut self) {
+        drop_layers(&self.slots.0, &[]);
+        drop_layers(&self.slots.1, &[]);
+    }
+
+    /// Drop rendered layers other than the base and active ones,
+    /// to free memory when the system is low on it.
+    fn trim_layers(&mut self) {
+        let (left, right) = self.keyboard.layer;
+        drop_layers(&self.slots.0, &[0, left]);
+        drop_layers(&self.slots.1, &[0, right]);
+    }
+
+    /// The key size in logical pixels.
+    fn key_size(&self) -> i32 {
+        let px_per_mm = output::monitor(&self.window.0)
+            .as_ref()
+            .and_then(output::px_per_mm);
+        let size = match (self.config.key_size_mm, px_per_mm) {
+            (Some(mm), Some(px_per_mm)) => mm as f64 * px_per_mm,
+            _ => KEY_SIZE as f64,
+        };
+        (size * self.config.key_scale() as f64).round() as i32
+    }
+
+    /// The key size for a side, as scaled by the layout.
+    fn side_key_size(&self, side: Side) -> i32 {
+        let scale = self.keyboard.side_scale(side);
+        (self.key_size() as f32 * scale).round() as i32
+    }
+
+    /// Ask the compositor to blur what's behind the keyboard.
+    fn request_blur(&self, sender: &ComponentSender<Self>) {
+        if self.compositor == Compositor::Kde {
+            for window in [&self.window.0, &self.window.1] {
+                window.connect_map(|window| {
+                    if let Err(err) = blur::kde(window) {
+                        error!("Failed to blur the keyboard: {}", err);
+                    }
+                });
+            }
+            return;
+        }
+        let commands: Vec<_> = ["left", "right"]
+            .into_iter()
+            .flat_map(|name| {
+                self.compositor
+                    .blur_commands(&namespace(&self.config, name))
+            })
+            .collect();
+        if commands.is_empty() {
+            error!("Blur isn't supported on this compositor");
+            self.toast.show("Blur isn't supported on this compositor");
+        }
+        for (cmd, args) in commands {
+            sender.input(UIMessage::Command(layout::Command::new(cmd, args)));
+        }
+    }
+
+    /// The regions the visible windows occupy, one per line
+    /// as `<name> <x>,<y> <w>x<h>`, in logical pixels.
+    fn geometry(&self) -> String {
+        let windows = [
+            ("left", &self.window.0),
+            ("right", &self.window.1),
+            ("trigger", &self.trigger),
+        ];
+        let mut regions = String::new();
+        for (name, window) in windows {
+            if !window.is_visible() {
+                continue;
+            }
+            let Some(monitor) = output::monitor(window) else {
+                continue;
+            };
+            // Windows are anchored to the bottom,
+            // and to the left or right edge.
+            let area = monitor.geometry();
+            let (width, height) = (window.width(), window.height());
+            let x = if window.is_anchor(Edge::Left) {
+                area.x()
+            } else {
+                area.x() + area.width() - width
+            };
+            let y = area.y() + area.height() - height;
+            regions.push_str(&format!("{} {},{} {}x{}\n", name, x, y, width, height));
+        }
+        regions
+    }
+
+    /// The widest a layer can be before its keys are shrunk,
+    /// i.e. half the screen, and the tallest it can be
+    /// before it's split into pages.
+    fn max_layer_size(&self) -> Option<(i32, i32)> {
+        let geometry = output::monitor(&self.window.0)?.geometry();
+        let height = geometry.height() as f32 * self.config.max_height;
+        Some((geometry.width() / 2, height.round() as i32))
+    }
+
+    /// Turn off animations and word predictions to save power,
+    /// or back on. The word list is dropped in the meantime.
+    fn save_power(&mut self, save: bool) {
+        if save == self.saving_power {
+            return;
+        }
+        debug!("Saving power: {}", save);
+        self.saving_power = save;
+        if let Some(settings) = gtk::Settings::default() {
+            if save {
+                settings.set_gtk_enable_animations(false);
+            } else {
+                settings.reset_property("gtk-enable-animations");
+            }
+        }
+        if save {
+            self.dictionary = None;
+            if !self.completing() {
+                self.suggestions.clear();
+            }
+        }
+    }
+
+    fn set_locked(&mut self, locked: bool, sender: &ComponentSender<Self>) {
+        match (locked, self.locked) {
+            (true, None) => {
+                let shown = self.window.0.is_visible();
+                self.locked = Some(shown);
+                match self.config.on_lock {
+                    LockBehavior::Hide => {
+                        if shown {
+                            self.hide_keyboard();
+                        }
+                    }
+                    LockBehavior::Restrict => {
+                        self.window.0.add_css_class("locked");
+                        self.window.1.add_css_class("locked");
+                        // Apps opened before locking can't be launched either.
+                        if matches!(self.panel, Some((Panel::Launcher, _))) {
+                            self.toggle_panel(Panel::Launcher, sender);
+                        }
+                        // Hide the clipboard's preview.
+                        self.render_keyboard(sender);
+                        self.toast.show("Locked: some keys are disabled");
+                    }
+                    LockBehavior::Ignore => {}
+                }
+            }
+            (false, Some(shown)) => {
+                self.locked = None;
+                self.window.0.remove_css_class("locked");
+                self.window.1.remove_css_class("locked");
+                let reshow = self.config.on_lock == LockBehavior::Hide && shown;
+                if reshow && self.fullscreen.is_some() {
+                    // Wait for the fullscreen window to go.
+                    self.fullscreen = Some(true);
+                } else if reshow {
+                    self.show_keyboard(sender);
+                } else {
+                    self.render_keyboard(sender);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// While locked, touches only go to the shields (and
+    /// the background gestures are detached, and the trigger
+    /// disabled), so that only the unlock gesture does anything.
+    fn set_touch_lock(&mut self, locked: bool, sender: &ComponentSender<Self>) {
+        if locked == self.touch_locked {
+            return;
+        }
+        self.touch_locked = locked;
+        self.touch_shields.0.set_visible(locked);
+        self.touch_shields.1.set_visible(locked);
+        self.trigger.set_sensitive(!locked);
+        if locked {
+            self.release_held_keys();
+            self.toast.show("Locked: hold both halves to unlock");
+        } else {
+            self.toast.show("Unlocked");
+        }
+        self.attach_gestures(sender);
+    }
+
+    fn set_fullscreen(&mut self, app_id: Option<String>, sender: &ComponentSender<Self>) {
+        let hide = self.config.hide_on_fullscreen
+            && app_id.is_some_and(|app_id| !self.config.fullscreen_allow.contains(&app_id));
+        match (hide, self.fullscreen) {
+            (true, None) => {
+                let shown = self.window.0.is_visible();
+                self.fullscreen = Some(shown);
+                if shown {
+                    self.hide_keyboard();
+                }
+                self.trigger.set_visible(false);
+            }
+            (false, Some(shown)) => {
+                self.fullscreen = None;
+                if shown {
+                    self.show_keyboard(sender);
+                } else {
+                    self.trigger.set_visible(true);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether URLs and paths are being completed, either because
+    /// it was turned on or because the text field is for a URL.
+    fn completing_urls(&self) -> bool {
+        self.complete || self.keyboard.content_purpose() == PURPOSE_URL
+    }
+
+    /// Whether words are being completed, either URLs and paths
+    /// or predictions from the active language's word list.
+    /// Never in password or PIN fields, as their text is
+    /// read for completing.
+    fn completing(&self) -> bool {
+        let predicting = !self.saving_power
+            && self
+                .config
+                .active_language()
+                .is_some_and(|lang| lang.dictionary.is_some());
+        (self.completing_urls() || predicting)
+            && self.keyboard.has_input_method()
+            && !self.keyboard.is_secret_field()
+            && !self.restricted()
+    }
+
+    /// Correct the word before the cursor, if it's misspelled,
+    /// except in password and PIN fields.
+    fn autocorrect(&mut self) {
+        let correcting = self.config.autocorrect
+            && self.keyboard.has_input_method()
+            && !self.keyboard.is_secret_field()
+            && !self.restricted()
+            && !self.completing_urls();
+        let path = self
+            .config
+            .active_language()
+            .and_then(|lang| lang.dictionary.as_deref())
+            .filter(|_| correcting);
+        let Some(path) = path else {
+            return;
+        };
+        self.keyboard.refresh();
+        let Some(word) = self
+            .keyboard
+            .surrounding_text()
+            .and_then(completion::current_word)
+        else {
+            return;
+        };
+        let dictionary = self
+            .dictionary
+            .get_or_insert_with(|| Dictionary::load(path));
+        let len = word.len();
+        if let Some(correction) = dictionary.correct(word) {
+            debug!("Autocorrecting {:?} to {:?}", word, correction);
+            self.keyboard
+                .handle(KeyMessage::ReplaceBefore(len as u32, correction));
+        }
+    }
+
+    /// Show completions for the word before the cursor.
+    fn update_completions(&mut self, sender: &ComponentSender<Self>) {
+        if !self.completing() {
+            return;
+        }
+        self.keyboard.refresh();
+        self.suggestions.clear();
+        let Some(word) = self
+            .keyboard
+            .surrounding_text()
+            .and_then(completion::current_word)
+        else {
+            return;
+        };
+        let completions = if self.completing_urls() && completion::is_path(word) {
+            // Reading the directory may be slow.
+            let path = word.to_string();
+            let sender = sender.input_sender().clone();
+            std::thread::spawn(move || {
+                let completions = completion::complete_path(&path, MAX_COMPLETIONS);
+                sender.emit(UIMessage::PathCompletions(path, completions));
+            });
+            return;
+        } else if self.completing_urls() {
+            let completer = self
+                .completer
+                .get_or_insert_with(|| Completer::load(&self.config.completions));
+            completer.complete(word, MAX_COMPLETIONS)
+        } else {
+            let Some(path) = self
+                .config
+                .active_language()
+                .and_then(|lang| lang.dictionary.as_deref())
+            else {
+                return;
+            };
+            let dictionary = self
+                .dictionary
+                .get_or_insert_with(|| Dictionary::load(path));
+            // Predicted words are followed by a space, as the word is done.
+            dictionary
+                .predict(word, MAX_COMPLETIONS)
+                .into_iter()
+                .map(|word| format!("{} ", word))
+                .collect()
+        };
+        self.offer_completions(word.len(), completions, sender);
+    }
+
+    /// Offer completions of the text before
+    /// the cursor (its length in bytes).
+    fn offer_completions(
+        &mut self,
+        len: usize,
+        completions: Vec<String>,
+        sender: &ComponentSender<Self>,
+    ) {
+        for completion in completions {
+            let label = truncate(completion.trim_end(), 32);
+            let sender = sender.clone();
+            self.suggestions.add(&label, move || {
+                sender.input(UIMessage::Complete(len, completion.clone()));
+            });
+        }
+    }
+
+    /// Show the Zhuyin syllable being composed,
+    /// and offer its characters.
+    fn update_zhuyin(&mut self, sender: &ComponentSender<Self>) {
+        self.keyboard
+            .handle(KeyMessage::Preedit(self.zhuyin.syllable()));
+        self.suggestions.clear();
+        let candidates = match &self.config.zhuyin_table {
+            Some(path) if self.zhuyin.is_composing() => {
+                let table = self.zhuyin_table.get_or_insert_with(|| Table::load(path));
+                self.zhuyin.candidates(table, MAX_COMPLETIONS)
+            }
+            _ => vec![],
+        };
+        self.show_candidates(&candidates);
+        for candidate in candidates {
+            let label = candidate.clone();
+            let sender = sender.clone();
+            self.suggestions.add(&label, move || {
+                sender.input(UIMessage::ZhuyinCommit(candidate.clone()));
+            });
+        }
+    }
+
+    /// Mirror candidates next to the text cursor, if enabled.
+    fn show_candidates(&mut self, candidates: &[String]) {
+        if self.config.candidate_popup {
+            self.keyboard.show_candidates(candidates);
+        }
+    }
+
+    /// Whether a key is taken for composition: any key
+    /// while composing, and the releases of those taken.
+    fn is_composing_key(&self, msg: &KeyMessage) -> bool {
+        match msg {
+            KeyMessage::ButtonPress(_) => self.zhuyin.is_composing(),
+            KeyMessage::ButtonRelease(code) => self.zhuyin_swallowed == Some(*code),
+            _ => false,
+        }
+    }
+
+    /// Backspace removes the last symbol, Space picks the
+    /// first character, and Enter types the symbols. Other
+    /// keys type the symbols too, then go through.
+    fn compose_key(&mut self, msg: KeyMessage, sender: &ComponentSender<Self>) {
+        let KeyMessage::ButtonPress(code) = msg else {
+            self.zhuyin_swallowed = None;
+            return;
+        };
+        let text = match evdev::Key::new(code) {
+            evdev::Key::KEY_BACKSPACE => {
+                self.zhuyin_swallowed = Some(code);
+                self.zhuyin.pop();
+                self.update_zhuyin(sender);
+                return;
+            }
+            evdev::Key::KEY_SPACE => {
+                self.zhuyin_swallowed = Some(code);
+                let first = self
+                    .zhuyin_table
+                    .as_ref()
+                    .and_then(|table| self.zhuyin.candidates(table, 1).into_iter().next());
+                first.unwrap_or_else(|| self.zhuyin.syllable())
+            }
+            evdev::Key::KEY_ENTER => {
+                self.zhuyin_swallowed = Some(code);
+                self.zhuyin.syllable()
+            }
+            _ => {
+                let text = self.zhuyin.syllable();
+                self.commit_zhuyin(text);
+                self.update(UIMessage::Keyboard(msg), sender.clone());
+                return;
+            }
+        };
+        self.commit_zhuyin(text);
+    }
+
+    /// Type text for the syllable, ending it.
+    fn commit_zhuyin(&mut self, text: String) {
+        self.zhuyin.clear();
+        self.suggestions.clear();
+        self.show_candidates(&[]);
+        self.keyboard.handle(KeyMessage::Commit(text));
+    }
+
+    /// Drop the syllable, e.g. when its text field is gone.
+    fn cancel_zhuyin(&mut self) {
+        if self.zhuyin.is_composing() {
+            self.zhuyin.clear();
+            self.suggestions.clear();
+            self.show_candidates(&[]);
+            self.keyboard.handle(KeyMessage::Preedit(String::new()));
+        }
+    }
+
+    /// Whether a key is taken for transliteration: keys the
+    /// table uses, and any key while a sequence is pending
+    /// (which finishes it first), and their releases.
+    fn is_transliterated_key(&self, msg: &KeyMessage) -> bool {
+        match msg {
+            KeyMessage::ButtonPress(code) => {
+                self.keyboard.has_input_method()
+                    && (self.transliterator.is_composing()
+                        || self.transliterated_char(evdev::Key::new(*code)).is_some())
+            }
+            KeyMessage::ButtonRelease(code) => self.transliteration_swallowed == Some(*code),
+            _ => false,
+        }
+    }
+
+    /// The character a key types, if the active language's
+    /// table transliterates it. Keys with modifiers other
+    /// than Shift are left alone, e.g. for shortcuts.
+    fn transliterated_char(&self, key: evdev::Key) -> Option<char> {
+        let table = self.transliteration.as_ref()?;
+        let shifted = self.keyboard.mod_active(&Modifier::Shift);
+        let plain = [Modifier::Alt, Modifier::Ctrl, Modifier::Meta]
+            .iter()
+            .all(|modifier| !self.keyboard.mod_active(modifier));
+        let glyph = if shifted {
+            keymap().shifted_glyph(&key)
+        } else {
+            keymap().glyph(&key)
+        };
+        let mut chars = glyph?.chars();
+        let c = chars.next()?;
+        (plain && chars.next().is_none() && table.handles(c)).then_some(c)
+    }
+
+    /// Transliterated keys are added to the pending sequence,
+    /// which is shown as preedit text, and Backspace removes
+    /// from it. Other keys finish the sequence, then go through.
+    fn transliterate_key(&mut self, msg: KeyMessage, sender: &ComponentSender<Self>) {
+        let KeyMessage::ButtonPress(code) = msg else {
+            self.transliteration_swallowed = None;
+            return;
+        };
+        let Some(table) = &self.transliteration else {
+            return;
+        };
+        let key = evdev::Key::new(code);
+        match self.transliterated_char(key) {
+            Some(c) => {
+                self.transliteration_swallowed = Some(code);
+                let text = self.transliterator.push(c, table);
+                if !text.is_empty() {
+                    self.keyboard.handle(KeyMessage::Commit(text));
+                }
+            }
+            None if key == evdev::Key::KEY_BACKSPACE => {
+                self.transliteration_swallowed = Some(code);
+                self.transliterator.pop();
+            }
+            None => {
+                let text = self.transliterator.take(table);
+                self.keyboard.handle(KeyMessage::Commit(text));
+                self.update(UIMessage::Keyboard(msg), sender.clone());
+                return;
+            }
+        }
+        let preview = self.transliterator.preview(table);
+        self.keyboard.handle(KeyMessage::Preedit(preview));
+    }
+
+    /// Type what the pending sequence would, if any.
+    fn finish_transliteration(&mut self) {
+        if let Some(table) = self
+            .transliteration
+            .as_ref()
+            .filter(|_| self.transliterator.is_composing())
+        {
+            let text = self.transliterator.take(table);
+            self.keyboard.handle(KeyMessage::Commit(text));
+        }
+    }
+
+    /// Drop the pending sequence, e.g. when its text field is gone.
+    fn cancel_transliteration(&mut self) {
+        if self.transliterator.is_composing() {
+            self.transliterator.clear();
+            self.keyboard.handle(KeyMessage::Preedit(String::new()));
+        }
+    }
+
+    /// Switch to the (already selected) language, along
+    /// with its word list and layout (if it has them).
+    fn use_language(&mut self, language: Language, sender: &ComponentSender<Self>) {
+        self.dictionary = None;
+        self.suggestions.clear();
+        self.suggestions.set_language(Some(&language.name));
+        self.finish_transliteration();
+        self.transliteration = language
+            .transliterate
+            .as_ref()
+            .map(transliterate::Table::load);
+        // Languages without their own layout go back to the usual one.
+        let path = language
+            .layout
+            .as_deref()
+            .or(self.config.profile_layout())
+            .map(Path::to_path_buf);
+        self.load_layout(path.as_deref(), sender);
+        self.clear_layers();
+        self.render_keyboard(sender);
+        self.save_state();
+        self.events
+            .send(Event::Language(&language.name), self.config.event_keys);
+    }
+
+    /// Whether typed expressions are being evaluated.
+    fn calculating(&self) -> bool {
+        self.keyboard.calculator_active() && self.keyboard.has_input_method()
+    }
+
+    /// Show the result of the expression before the cursor.
+    fn update_calculation(&mut self, sender: &ComponentSender<Self>) {
+        self.keyboard.refresh();
+        self.suggestions.clear();
+        let Some(text) = self.keyboard.surrounding_text() else {
+            return;
+        };
+        let before = text.text.get(..text.cursor as usize).unwrap_or_default();
+        let Some(expr) = calculator::trailing_expression(before) else {
+            return;
+        };
+        if let Some(value) = calculator::evaluate(expr) {
+            let result = calculator::format(value);
+            let len = expr.len();
+            let sender = sender.clone();
+            self.suggestions.add(&format!("= {}", result), move || {
+                sender.input(UIMessage::Complete(len, result.clone()));
+            });
+        }
+    }
+
+    /// Whether restricted actions are currently disabled.
+    fn restricted(&self) -> bool {
+        self.locked.is_some() && self.config.on_lock == LockBehavior::Restrict
+    }
+
+    /// Start a scan, showing that it's underway in the
+    /// suggestion bar, or cancel the one underway.
+    /// A cancelled scanner is stopped and its result ignored.
+    fn toggle_scan(&mut self, sender: &ComponentSender<Self>) {
+        if self.scanning.take().is_some() {
+            if let Some(pid) = self.scanner.take() {
+                commands::signal(pid, libc::SIGTERM);
+            }
+            self.suggestions.clear();
+            self.toast.show("Scan cancelled");
+            return;
+        }
+        if !self.keyboard.has_input_method() {
+            self.toast.show(NO_INPUT_METHOD);
+            return;
+        }
+        let Some(scanner) = &self.config.scanner else {
+            self.toast.show("No scanner is configured");
+            return;
+        };
+        self.scans += 1;
+        self.scanning = Some(self.scans);
+        self.scanner = commands::scan(scanner.command(), self.scans, sender.input_sender().clone());
+
+        self.suggestions.clear();
+        let sender = sender.clone();
+        self.suggestions
+            .add("▦ Scanning… ✕", move || sender.input(UIMessage::Scan));
+    }
+
+    /// Screenshots are taken with the keyboard (and trigger)
+    /// hidden, giving it a moment to disappear first, and
+    /// it's shown again once the screenshot is done.
+    fn capture(&mut self, action: CaptureAction, sender: &ComponentSender<Self>) {
+        let interactive = match action {
+            CaptureAction::Screenshot => false,
+            CaptureAction::Area => true,
+            CaptureAction::Record => {
+                if let Some(recording) = self.recording.take() {
+                    recording.stop();
+                    self.toast.show("Recording stopped");
+                    return;
+                }
+                let Some(recorder) = &self.config.recorder else {
+                    self.toast.show("No recorder is configured");
+                    return;
+                };
+                if let Err(err) = capture::record(recorder, sender.input_sender().clone()) {
+                    error!("Failed to start recording: {}", err);
+                    self.toast.show("Couldn't start recording");
+                }
+                return;
+            }
+        };
+
+        self.hide_keyboard();
+        self.trigger.set_visible(false);
+        let sender = sender.clone();
+        glib::timeout_add_local_once(SCREENSHOT_DELAY, move || {
+            let result = capture::screenshot(interactive, sender.input_sender().clone());
+            if let Err(err) = result {
+                error!("Failed to take a screenshot: {}", err);
+                sender.input(UIMessage::Captured(None));
+                sender.input(UIMessage::Toast("Couldn't take a screenshot".into()));
+            }
+        });
+    }
+
+    /// Show a panel in place of the right half,
+    /// or close it if it's already shown.
+    fn toggle_panel(&mut self, panel: Panel, sender: &ComponentSender<Self>) {
+        if let Some((_, widget)) = &self.panel {
+            self.slots.1.remove(widget);
+        }
+        self.canvas = None;
+        self.panel = match self.panel.take() {
+            Some((shown, _)) if shown == panel => None,
+            _ => {
+                // Cover the area of the right half.
+                let (width, height) = (self.slots.1.width(), self.slots.1.height());
+                let widget = match panel {
+                    Panel::Touchpad => touchpad::touchpad(width, height, sender),
+                    Panel::Launcher => launcher::launcher(width, height, self.key_size(), sender),
+                    Panel::CheatSheet => {
+                        cheatsheet::cheat_sheet(width, height, &self.keyboard, &self.config, sender)
+                    }
+                    Panel::Handwriting => {
+                        let (widget, canvas) = handwriting::handwriting(width, height, sender);
+                        self.canvas = Some(canvas);
+                        widget
+                    }
+                    Panel::PinPad => {
+                        pinpad::pin_pad(width, height, self.config.pin_pad.scramble, sender)
+                    }
+                    Panel::Switcher => switcher::switcher(width, height, &self.config, sender),
+                };
+                self.slots.1.add_named(&widget, Some(PANEL));
+                Some((panel, widget))
+            }
+        };
+        self.render_keyboard(sender);
+    }
+
+    /// Whether the pointer is being moved
+    /// from the mouse layer or touchpad.
+    fn driving_pointer(&self) -> bool {
+        self.keyboard.mouse_layer_active() || matches!(self.panel, Some((Panel::Touchpad, _)))
+    }
+
+    /// Pointer nudges are multiplied while
+    /// Shift (10x) or Ctrl (50x) is held.
+    fn nudge_scale(&self) -> i32 {
+        if self.keyboard.mod_active(&Modifier::Ctrl) {
+            50
+        } else if self.keyboard.mod_active(&Modifier::Shift) {
+            10
+        } else {
+            1
+        }
+    }
+
+    fn start_pointer_tick(&mut self, sender: &ComponentSender<Self>) {
+        let interval = Duration::from_millis(1000 / self.config.pointer_rate.max(1) as u64);
+        let sender = sender.clone();
+        self.pointer_tick.repeat(interval, move || {
+            sender.input(PointerMessage::Flush.into());
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn change_setting(&mut self, setting: Setting, sender: &ComponentSender<Self>) {
+        match setting {
+            Setting::Theme => {
+                self.config.theme = self.config.theme.next();
+                self.apply_theme();
+            }
+            Setting::ScaleUp => self.config.scale_by(1),
+            Setting::ScaleDown => self.config.scale_by(-1),
+            Setting::Stylus => self.config.stylus = !self.config.stylus,
+            Setting::Haptics => {
+                self.config.haptics = !self.config.haptics;
+                self.update_haptics();
+            }
+            Setting::Autocorrect => self.config.autocorrect = !self.config.autocorrect,
+            Setting::SwapHalves => {
+                self.config.swap_halves = !self.config.swap_halves;
+                anchor_window(&self.window.0, !self.config.swap_halves);
+                anchor_window(&self.window.1, self.config.swap_halves);
+            }
+            Setting::Mirror => self.config.mirror = !self.config.mirror,
+            Setting::Launcher => {
+                self.toggle_panel(Panel::Launcher, sender);
+                return;
+            }
+            Setting::Close => {
+                self.keyboard.handle(KeyMessage::SettingsLayer(false));
+                self.render_keyboard(sender);
+                return;
+            }
+        }
+        self.save_state();
+        self.clear_layers();
+        self.render_keyboard(sender);
+    }
+
+    fn use_profile(&mut self, name: &str, sender: &ComponentSender<Self>) {
+        let Some(profile) = self.config.use_profile(name) else {
+            error!("No profile named: {}", name);
+            self.toast.show(&format!("No profile named {}", name));
+            return;
+        };
+
+        let loaded = match &profile.layout {
+            Some(path) => self.load_layout(Some(path), sender),
+            None => true,
+        };
+        self.update_haptics();
+        self.apply_theme();
+        self.clear_layers();
+        self.render_keyboard(sender);
+        self.save_state();
+
+        // Otherwise leave the layout's error showing.
+        if loaded {
+            self.toast.show(&format!("Profile: {}", name));
+        }
+    }
+
+    /// Switch to the layout at the path, or the locale's default
+    /// layout without one, along with its trigger key and gestures.
+    /// Layers need re-rendering after.
+    /// Returns whether the layout was loaded.
+    fn load_layout(&mut self, path: Option<&Path>, sender: &ComponentSender<Self>) -> bool {
+        let layout = match path {
+            Some(path) => match Layout::try_from_path(path) {
+                Ok(layout) => layout,
+                Err(err) => {
+                    error!("Failed to load layout {:?}: {}", path, err);
+                    self.toast.show(&format!("Failed to load layout: {}", err));
+                    return false;
+                }
+            },
+            None => Layout::for_locale(locale::current().as_deref()),
+        };
+        self.keyboard.set_layout(layout);
+        self.layout_path = path.map(Path::to_path_buf);
+        let trigger = setup_trigger_key(self.keyboard.trigger_key(), &self.config, sender.clone());
+        self.trigger.set_child(Some(&trigger));
+        self.attach_gestures(sender);
+        self.watch_clipboard(sender);
+        true
+    }
+
+    /// Start watching the clipboard if the layout has paste keys.
+    /// It's never stopped, as another layout may be loaded.
+    fn watch_clipboard(&mut self, sender: &ComponentSender<Self>) {
+        if self.watching_clipboard {
+            return;
+        }
+        let pastes = self
+            .keyboard
+            .left_layers()
+            .chain(self.keyboard.right_layers())
+            .any(layout::Layer::has_paste);
+        if pastes {
+            clipboard::watch(sender.input_sender().clone());
+            self.watching_clipboard = true;
+        }
+    }
+
+    /// (Re-)attach the background gestures for the current layout.
+    fn attach_gestures(&mut self, sender: &ComponentSender<Self>) {
+        for controller in self.gestures.drain(..) {
+            if let Some(widget) = controller.widget() {
+                widget.remove_controller(&controller);
+            }
+        }
+        if self.touch_locked {
+            return;
+        }
+        let gestures = self.keyboard.gestures();
+        self.gestures = gestures::attach(&self.window.0, gestures, sender);
+        self.gestures
+            .extend(gestures::attach(&self.window.1, gestures, sender));
+    }
+
+    fn apply_theme(&self) {
+        let mut css = self.config.theme.css().to_string();
+        css.push_str(&fonts::css(&self.config, &self.keyboard));
+        css.push_str(&accents::css(&self.keyboard));
+        if let Some(opacity) = self.config.background_opacity {
+            // The window's own opacity applies on top,
+            // so the background is made up for it.
+            let window = self.window.0.opacity().max(0.1);
+            let (r, g, b) = self.config.theme.background();
+            css.push_str(&format!(
+                "window {{ background-color: rgba({}, {}, {}, {}); }}",
+                r,
+                g,
+                b,
+                (opacity as f64 / window).clamp(0., 1.)
+            ));
+        }
+        self.theme.load_from_string(&css);
+    }
+
+    fn render_keyboard(&mut self, sender: &ComponentSender<Self>) {
+        // Rendered layers are only valid for the state
+        // their keys' conditions were checked against.
+        let context = self.key_context();
+        let conditional = self
+            .keyboard
+            .left_layers()
+            .chain(self.keyboard.right_layers())
+            .any(layout::Layer::is_contextual);
+        if conditional && context != self.key_context {
+            self.clear_layers();
+        }
+        self.key_context = context;
+
         let (left, right) = self.keyboard.layer;
-        self.window.0.set_child(Some(&self.left[left]));
-        self.window.1.set_child(Some(&self.right[right]));
+        let (left_size, right_size) = (
+            self.side_key_size(Side::Left),
+            self.side_key_size(Side::Right),
+        );
+        let max_size = self.max_layer_size();
+        let visible = (
+            self.slots.0.visible_child_name(),
+            self.slots.1.visible_child_name(),
+        );
+        show_layer(&self.slots.0, left, || {
+            let layer = self.keyboard.left_layers().nth(left)?;
+            Some(layer.render(
+                left_size,
+                max_size,
+                &self.config,
+                &self.key_context,
+                sender.input_sender(),
+            ))
+        });
+        match &self.panel {
+            Some(_) => self.slots.1.set_visible_child_name(PANEL),
+            None => show_layer(&self.slots.1, right, || {
+                let layer = self.keyboard.right_layers().nth(right)?;
+                Some(layer.render(
+                    right_size,
+                    max_size,
+                    &self.config,
+                    &self.key_context,
+                    sender.input_sender(),
+                ))
+            }),
+        }
+
+        if self.slots.0.visible_child_name() != visible.0 {
+            fit_to_content(&self.window.0);
+        }
+        if self.slots.1.visible_child_name() != visible.1 {
+            fit_to_content(&self.window.1);
+        }
+
+        if self.shown != Some((left, right)) && self.events.has_subscribers() {
+            let left_name = self.keyboard.layer_name(Side::Left);
+            let right_name = self.keyboard.layer_name(Side::Right);
+            self.events.send(
+                Event::Layer(&left_name, &right_name),
+                self.config.event_keys,
+            );
+        }
+
+        // Announce layer changes for screen readers.
+        if let Some((prev_left, prev_right)) = self.shown {
+            let priority = gtk::AccessibleAnnouncementPriority::Medium;
+            if prev_left != left {
+                let name = self.keyboard.layer_name(Side::Left);
+                self.window.0.announce(&name, priority);
+            }
+            if prev_right != right {
+                let name = self.keyboard.layer_name(Side::Right);
+                self.window.1.announce(&name, priority);
+            }
+        }
+        self.shown = Some((left, right));
+        self.update_reservation();
+        self.update_mode_indicator();
+        self.update_hints();
+    }
+
+    /// Annotate keys with what they do with the
+    /// modifiers that are toggled on (see `hints`).
+    fn update_hints(&self) {
+        let modifiers = self.active_modifiers();
+        for slot in [&self.slots.0, &self.slots.1] {
+            if let Some(layer) = slot.visible_child() {
+                hints::update(&layer, &modifiers);
+            }
+        }
+    }
+
+    /// The state that keys' conditions are checked against.
+    fn key_context(&self) -> KeyContext {
+        let portrait = output::monitor(&self.window.0).is_some_and(|monitor| {
+            let area = monitor.geometry();
+            area.height() > area.width()
+        });
+        // Only the start is shown, on one line,
+        // and nothing while the session is locked.
+        let clipboard = self
+            .clipboard
+            .as_deref()
+            .filter(|_| !self.restricted())
+            .map(|text| truncate(&Newlines::Space.apply(text.trim()), CLIPBOARD_PREVIEW));
+        KeyContext {
+            app: self.focused_app.clone(),
+            locks: self.keyboard.active_locks(),
+            portrait,
+            clipboard,
+            selecting: self.keyboard.selecting(),
+        }
+    }
+
+    /// Show the vi mode while on a vim layer,
+    /// or that selection mode is on.
+    fn update_mode_indicator(&mut self) {
+        let active = self.keyboard.vim_active();
+        if !active {
+            self.vim.reset();
+        }
+        let selecting = self.keyboard.selecting();
+        let label = if active {
+            self.vim.indicator()
+        } else {
+            "SELECT".into()
+        };
+        self.mode_indicator.set_label(&label);
+        self.mode_indicator.set_visible(active || selecting);
+    }
+
+    /// Reserve the height of the taller half while the keyboard is shown.
+    fn update_reservation(&self) {
+        let Some(reservation) = &self.reservation else {
+            return;
+        };
+        let height = self.window.0.is_visible().then(|| {
+            [&self.window.0, &self.window.1]
+                .into_iter()
+                .filter_map(|window| window.child())
+                .map(|child| child.measure(gtk::Orientation::Vertical, -1).1)
+                .max()
+                .unwrap_or(0)
+        });
+        reservation.set_height(height);
     }
 
-    fn show_keyboard(&self) {
+    fn show_keyboard(&mut self, sender: &ComponentSender<Self>) {
+        self.keyboard.resume();
+        self.render_keyboard(sender);
+        self.shown_over = self.focused_window;
         self.trigger.set_visible(false);
         self.window.0.set_visible(true);
         self.window.1.set_visible(true);
+        self.update_reservation();
+        self.update_pin_pad(sender);
+        self.save_state();
+        self.events
+            .send(Event::Visible(true), self.config.event_keys);
     }
 
-    fn hide_keyboard(&self) {
+    /// Show the PIN pad while a PIN field is focused
+    /// (or a password field, if configured), and hide
+    /// it again after if it wasn't shown by hand.
+    fn update_pin_pad(&mut self, sender: &ComponentSender<Self>) {
+        let options = &self.config.pin_pad;
+        let purpose = self.keyboard.content_purpose();
+        let wanted = options.auto
+            && (purpose == PURPOSE_PIN || (options.passwords && purpose == PURPOSE_PASSWORD));
+        let shown = matches!(self.panel, Some((Panel::PinPad, _)));
+        if wanted && !shown && !self.pin_pad_dismissed {
+            debug!("PIN field focused, showing the PIN pad");
+            self.toggle_panel(Panel::PinPad, sender);
+            self.pin_pad_auto = true;
+        } else if !wanted && shown && self.pin_pad_auto {
+            self.toggle_panel(Panel::PinPad, sender);
+            self.pin_pad_auto = false;
+        }
+    }
+
+    /// Show (or hide) the keyboard for a newly focused text field.
+    fn text_field_focused(&mut self, sender: &ComponentSender<Self>) {
+        let hidden_by = self.fullscreen.is_some()
+            || (self.locked.is_some() && self.config.on_lock == LockBehavior::Hide);
+        let show = match self.config.show_on_focus {
+            ShowOnFocus::Never => return,
+            ShowOnFocus::Always => true,
+            ShowOnFocus::Touch => match self.last_interaction {
+                Some(Interaction::Touch) => true,
+                Some(Interaction::Pointer) => false,
+                None => return,
+            },
+        };
+        let shown = self.window.0.is_visible();
+        if show && !shown && !hidden_by {
+            debug!("Text field focused, showing the keyboard");
+            self.show_keyboard(sender);
+        } else if !show && shown {
+            debug!("Text field clicked, hiding the keyboard");
+            self.hide_keyboard();
+        }
+    }
+
+    fn hide_keyboard(&mut self) {
+        if self.config.release_on_hide {
+            self.release_held_keys();
+        }
+        self.cancel_zhuyin();
+        self.finish_transliteration();
         self.trigger.set_visible(true);
         self.window.0.set_visible(false);
         self.window.1.set_visible(false);
+        self.update_reservation();
+        self.suspend_keyboard();
+        self.save_state();
+        self.events
+            .send(Event::Visible(false), self.config.event_keys);
+    }
+
+    /// The keyboard's state, for status bars.
+    fn status(&self) -> Status {
+        Status {
+            visible: self.window.0.is_visible(),
+            left: self.keyboard.layer_name(Side::Left),
+            right: self.keyboard.layer_name(Side::Right),
+            modifiers: self
+                .active_modifiers()
+                .iter()
+                .map(|modifier| format!("{:?}", modifier))
+                .collect(),
+            language: self
+                .config
+                .active_language()
+                .map_or_else(|| keymap().language.clone(), |lang| lang.name.clone()),
+        }
+    }
+
+    /// The modifiers currently held or latched.
+    fn active_modifiers(&self) -> Vec<Modifier> {
+        [
+            Modifier::Alt,
+            Modifier::Ctrl,
+            Modifier::Shift,
+            Modifier::Meta,
+        ]
+        .into_iter()
+        .filter(|modifier| self.keyboard.mod_active(modifier))
+        .collect()
+    }
+
+    /// Send a message to the pointer device, reporting
+    /// errors rather than crashing so the keyboard stays usable.
+    fn handle_pointer(&mut self, msg: PointerMessage) {
+        let scrolling = Scrolling {
+            natural: self.config.natural_scroll,
+        };
+        match self.pointer.handle(msg, scrolling) {
+            Ok(()) => {}
+            // Already reported when the device failed.
+            Err(PointerError::BackingOff(_)) => {}
+            Err(err) => {
+                error!("{}", err);
+                self.toast.show(&err.to_string());
+            }
+        }
+    }
+
+    /// Release the keyboard's state while it's hidden,
+    /// and stop anything that would keep waking up.
+    fn suspend_keyboard(&mut self) {
+        if self.pointer_tick.stop() {
+            self.handle_pointer(PointerMessage::Flush);
+        }
+
+        // Re-render so that modifier keys aren't shown as held.
+        if self.keyboard.suspend() {
+            self.clear_layers();
+        }
     }
 
-    fn fade_keyboard(&self, change: i8) {
+    /// Let go of anything keys are holding down (e.g. keys,
+    /// modifiers, layers, or pointer buttons held by a swipe),
+    /// as keys don't see touches end once they're hidden.
+    fn release_held_keys(&self) {
+        for slot in [&self.slots.0, &self.slots.1] {
+            KeyButton::release_held_within(slot.upcast_ref());
+        }
+    }
+
+    /// Connect to feedbackd if haptics are on, or let it go.
+    fn update_haptics(&mut self) {
+        if !self.config.haptics {
+            self.haptics = None;
+        } else if self.haptics.is_none() {
+            self.haptics = haptics::connect()
+                .inspect_err(|err| error!("Failed to connect to feedbackd: {}", err))
+                .ok();
+        }
+    }
+
+    fn fade_keyboard(&mut self, change: i8) {
         let change = change as f64 * 0.1;
         let opacity = self.window.0.opacity() + change;
         let opacity = opacity.clamp(0.1, 0.8);
         self.window.0.set_opacity(opacity);
         self.window.1.set_opacity(opacity);
+        if self.config.background_opacity.is_some() {
+            self.apply_theme();
+        }
+        self.save_state();
+    }
+
+    /// Put things back as they were last left.
+    /// The saved profile, language, and layout
+    /// are already applied on launch.
+    fn restore_state(&mut self, sender: &ComponentSender<Self>) {
+        if let Some(opacity) = self.state.opacity {
+            let opacity = opacity.clamp(0.1, 0.8);
+            self.window.0.set_opacity(opacity);
+            self.window.1.set_opacity(opacity);
+        }
+
+        // The layout may have changed since.
+        let (left, right) = self.state.layers;
+        if left < self.keyboard.left_layers().count() {
+            self.keyboard.handle(KeyMessage::Layer(Side::Left, left));
+        }
+        if right < self.keyboard.right_layers().count() {
+            self.keyboard.handle(KeyMessage::Layer(Side::Right, right));
+        }
+
+        if self.state.visible {
+            sender.input(UIMessage::ShowKeyboard);
+        }
+    }
+
+    /// Save the state to restore on the next launch, if it's changed.
+    fn save_state(&mut self) {
+        // Anything waiting to be saved is saved now.
+        self.layer_save.stop();
+
+        // The mouse and settings layers are only ever passed through.
+        let (left, right) = self.keyboard.layer;
+        let left = if self.keyboard.mouse_layer_active() {
+            0
+        } else {
+            left
+        };
+        let right = if self.keyboard.settings_open() {
+            0
+        } else {
+            right
+        };
+        let state = State {
+            visible: self.window.0.is_visible(),
+            layout: self.layout_path.clone(),
+            profile: self.config.profile.clone(),
+            language: self.config.language.clone(),
+            layers: (left, right),
+            opacity: Some(self.window.0.opacity()),
+            settings: self.config.changed_settings(),
+        };
+        if state != self.state {
+            state.save();
+            self.state = state;
+        }
     }
 }
@@ -0,0 +1,49 @@
+//! Information about the output (monitor) the keyboard is shown on.
+//!
+//! GTK sizes are in logical pixels, which already account for
+//! the output's scale (including fractional scales via
+//! `wp_fractional_scale_v1`). But a logical pixel can still vary
+//! a lot in physical size between, say, a laptop and a tablet,
+//! so we use the output's physical dimensions to size keys consistently.
+
+use gdk4::{
+    Monitor,
+    prelude::{Cast, DisplayExt, ListModelExt, MonitorExt},
+};
+use relm4::gtk::{
+    self,
+    prelude::{NativeExt, WidgetExt},
+};
+use tracing::debug;
+
+/// The monitor the window is on, or the first monitor
+/// if the window hasn't been mapped yet.
+pub fn monitor(window: &gtk::Window) -> Option<Monitor> {
+    let display = window.display();
+    window
+        .surface()
+        .and_then(|surface| display.monitor_at_surface(&surface))
+        .or_else(|| {
+            display
+                .monitors()
+                .item(0)
+                .and_then(|obj| obj.downcast::<Monitor>().ok())
+        })
+}
+
+/// Logical pixels per millimeter for the monitor,
+/// if its physical size is known.
+pub fn px_per_mm(monitor: &Monitor) -> Option<f64> {
+    let width_mm = monitor.width_mm();
+    if width_mm <= 0 {
+        return None;
+    }
+    let width_px = monitor.geometry().width();
+    debug!(
+        "[Output] {}px / {}mm, scale={}",
+        width_px,
+        width_mm,
+        monitor.scale_factor()
+    );
+    Some(width_px as f64 / width_mm as f64)
+}
@@ -0,0 +1,95 @@
+//! A PIN pad with large digits, typed with key presses
+//! (rather than committed through the input method),
+//! since PIN and password fields may not accept that.
+
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{ButtonExt, GridExt},
+    },
+};
+use tracing::error;
+
+use super::{UIMessage, UIModel, close_button, keyboard::send_key};
+
+/// The digits' keys, by digit.
+const DIGITS: [evdev::Key; 10] = [
+    evdev::Key::KEY_0,
+    evdev::Key::KEY_1,
+    evdev::Key::KEY_2,
+    evdev::Key::KEY_3,
+    evdev::Key::KEY_4,
+    evdev::Key::KEY_5,
+    evdev::Key::KEY_6,
+    evdev::Key::KEY_7,
+    evdev::Key::KEY_8,
+    evdev::Key::KEY_9,
+];
+
+pub fn pin_pad(
+    width: i32,
+    height: i32,
+    scramble: bool,
+    sender: &ComponentSender<UIModel>,
+) -> gtk::Overlay {
+    let grid = gtk::Grid::builder()
+        .row_homogeneous(true)
+        .column_homogeneous(true)
+        .width_request(width)
+        .height_request(height)
+        .css_classes(["pin-pad"])
+        .build();
+
+    // Laid out like a phone's keypad.
+    let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9, 0];
+    if scramble {
+        shuffle(&mut digits);
+    }
+    for (i, digit) in digits.into_iter().enumerate() {
+        let (col, row) = match i {
+            9 => (1, 3),
+            i => (i % 3, i / 3),
+        };
+        let button = pin_key(&digit.to_string(), DIGITS[digit], sender);
+        grid.attach(&button, col as i32, row as i32, 1, 1);
+    }
+    grid.attach(&pin_key("⌫", evdev::Key::KEY_BACKSPACE, sender), 0, 3, 1, 1);
+    grid.attach(&pin_key("⏎", evdev::Key::KEY_ENTER, sender), 2, 3, 1, 1);
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&grid));
+
+    let sender = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender.input(UIMessage::TogglePinPad);
+    }));
+    overlay
+}
+
+fn pin_key(label: &str, key: evdev::Key, sender: &ComponentSender<UIModel>) -> gtk::Button {
+    let button = gtk::Button::builder()
+        .label(label)
+        .focus_on_click(false)
+        .build();
+    let sender = sender.clone();
+    button.connect_clicked(move |_| send_key(key.code(), sender.input_sender()));
+    button
+}
+
+/// Shuffle in place (Fisher-Yates), with the system's
+/// random source. Left as-is if that's unavailable.
+fn shuffle(digits: &mut [usize]) {
+    let mut random = [0u32; 10];
+    let len = std::mem::size_of_val(&random);
+    // SAFETY: The buffer is valid for writes of its length.
+    let filled = unsafe { libc::getrandom(random.as_mut_ptr().cast(), len, 0) };
+    if filled != len as isize {
+        error!("Failed to get random numbers to scramble the PIN pad");
+        return;
+    }
+    for i in (1..digits.len()).rev() {
+        let j = random[i] as usize % (i + 1);
+        digits.swap(i, j);
+    }
+}
@@ -0,0 +1,52 @@
+//! Reserve the keyboard's height along the whole bottom edge,
+//! so that tiling compositors resize windows to fit above it
+//! even though only the two halves are drawn.
+
+use gdk4::{cairo, prelude::SurfaceExt};
+use gtk4_layer_shell::{Edge, KeyboardMode, LayerShell};
+use relm4::gtk::{
+    self,
+    prelude::{NativeExt, WidgetExt},
+};
+
+use crate::config::Config;
+
+use super::setup_layer_shell;
+
+pub struct Reservation {
+    window: gtk::Window,
+}
+impl Reservation {
+    pub fn new(config: &Config) -> Self {
+        let window = gtk::Window::builder()
+            .css_classes(["reservation"])
+            .default_height(1)
+            .build();
+        setup_layer_shell(&window, "reserve", config);
+        // Nothing in it can be typed into.
+        window.set_keyboard_mode(KeyboardMode::None);
+        for edge in [Edge::Left, Edge::Right, Edge::Bottom] {
+            window.set_anchor(edge, true);
+        }
+
+        // It's only there to take up space,
+        // so let input through to what's below.
+        window.connect_realize(|window| {
+            if let Some(surface) = window.surface() {
+                surface.set_input_region(&cairo::Region::create());
+            }
+        });
+        Self { window }
+    }
+
+    /// Reserve this much height (px), or nothing if `None`.
+    pub fn set_height(&self, height: Option<i32>) {
+        match height {
+            Some(height) => {
+                self.window.set_exclusive_zone(height);
+                self.window.set_visible(true);
+            }
+            None => self.window.set_visible(false),
+        }
+    }
+}
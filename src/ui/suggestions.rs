@@ -0,0 +1,99 @@
+//! A bar above the keyboard for one-tap suggestions,
+//! e.g. undoing a delete swipe. It also shows the
+//! active language, if there are languages, which
+//! swiping along the bar switches.
+
+use relm4::{
+    Sender,
+    gtk::{
+        self,
+        prelude::{BoxExt, ButtonExt, EventControllerExt, GestureDragExt, GestureExt, WidgetExt},
+    },
+};
+
+use super::{
+    UIMessage,
+    swipe::{Direction, did_gesture},
+};
+
+pub struct Suggestions {
+    container: gtk::Box,
+    language: gtk::Label,
+    bar: gtk::Box,
+}
+impl Suggestions {
+    pub fn new(sender: Sender<UIMessage>) -> Self {
+        let language = gtk::Label::builder()
+            .css_classes(["language"])
+            .visible(false)
+            .build();
+        let bar = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .build();
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .css_classes(["suggestions"])
+            .visible(false)
+            .build();
+        container.append(&language);
+        container.append(&bar);
+        container.add_controller(language_swipe(sender));
+        Self {
+            container,
+            language,
+            bar,
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        &self.container
+    }
+
+    /// Show the active language, or nothing.
+    pub fn set_language(&self, name: Option<&str>) {
+        self.language.set_label(name.unwrap_or_default());
+        self.language.set_visible(name.is_some());
+        self.update_visibility();
+    }
+
+    /// Only take up space if there's something to show.
+    fn update_visibility(&self) {
+        let visible = self.language.is_visible() || self.bar.first_child().is_some();
+        self.container.set_visible(visible);
+    }
+
+    /// Add a suggestion, which calls `on_tap` when tapped.
+    pub fn add(&self, label: &str, on_tap: impl Fn() + 'static) {
+        let button = gtk::Button::with_label(label);
+        button.add_css_class("suggestion");
+        button.connect_clicked(move |_| on_tap());
+        self.bar.append(&button);
+        self.update_visibility();
+    }
+
+    pub fn clear(&self) {
+        while let Some(child) = self.bar.first_child() {
+            self.bar.remove(&child);
+        }
+        self.update_visibility();
+    }
+}
+
+/// Swiping left or right along the bar switches to the previous
+/// or next language. It sees touches before the suggestions do,
+/// so that a swipe starting on one doesn't also tap it.
+fn language_swipe(sender: Sender<UIMessage>) -> gtk::GestureDrag {
+    let drag = gtk::GestureDrag::new();
+    drag.set_propagation_phase(gtk::PropagationPhase::Capture);
+    drag.connect_drag_update(|drag, dx, dy| {
+        if did_gesture(dx, dy).is_some_and(|dir| !dir.is_vertical()) {
+            drag.set_state(gtk::EventSequenceState::Claimed);
+        }
+    });
+    drag.connect_drag_end(move |_, dx, dy| match did_gesture(dx, dy) {
+        Some(Direction::Right) => sender.emit(UIMessage::NextLanguage),
+        Some(Direction::Left) => sender.emit(UIMessage::PreviousLanguage),
+        _ => {}
+    });
+    drag
+}
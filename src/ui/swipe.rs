@@ -3,20 +3,46 @@ use gdk4::glib::{
     value::{FromValue, GenericValueTypeChecker},
 };
 
+use super::key::InputKind;
+
 /// Minimum distance to trigger a swipe.
 /// If this is too low, then taps may be
 /// interpreted as swipes.
 const SWIPE_MIN_DISTANCE: f64 = 3.;
 
+/// Fingers are less precise and tend to roll
+/// a bit when tapping, so touches need to move
+/// further to count as a swipe.
+const SWIPE_MIN_DISTANCE_TOUCH: f64 = 6.;
+
 /// Swipe angle must be w/in this number of degrees
 /// to trigger a directional swipe.
 const SWIPE_ANGLE_TOLERANCE: f64 = 25.;
 
-/// Minimum a swipe must increment to trigger repeat presses.
+/// A previewed swipe is taken back by returning within
+/// this many times the minimum swipe distance of where it
+/// started, once it's gone further than that.
+const PREVIEW_CENTER_FACTOR: f64 = 2.;
+
+/// Minimum a swipe must increment to trigger repeat presses,
+/// unless the key sets its own.
 const SWIPE_MIN_INCREMENT: f64 = 5.;
 
-pub fn did_swipe(dx: f64, dy: f64) -> (bool, Option<Direction>) {
-    let did_swipe = dx.abs() >= SWIPE_MIN_DISTANCE || dy.abs() >= SWIPE_MIN_DISTANCE;
+/// Minimum distance for a swipe on the keyboard background.
+/// This is larger than for keys as these gestures
+/// are meant to be deliberate.
+const GESTURE_MIN_DISTANCE: f64 = 40.;
+
+fn min_distance(input: InputKind) -> f64 {
+    match input {
+        InputKind::Touch => SWIPE_MIN_DISTANCE_TOUCH,
+        InputKind::Mouse | InputKind::Pen => SWIPE_MIN_DISTANCE,
+    }
+}
+
+pub fn did_swipe(dx: f64, dy: f64, input: InputKind) -> (bool, Option<Direction>) {
+    let min_distance = min_distance(input);
+    let did_swipe = dx.abs() >= min_distance || dy.abs() >= min_distance;
     if did_swipe {
         (true, direction(dx, dy))
     } else {
@@ -24,12 +50,31 @@ pub fn did_swipe(dx: f64, dy: f64) -> (bool, Option<Direction>) {
     }
 }
 
+/// Whether a swipe is (back) near where it started.
+pub fn near_center(dx: f64, dy: f64, input: InputKind) -> bool {
+    distance(0., 0., dx, dy) < min_distance(input) * PREVIEW_CENTER_FACTOR
+}
+
+pub fn did_gesture(dx: f64, dy: f64) -> Option<Direction> {
+    if distance(0., 0., dx, dy) >= GESTURE_MIN_DISTANCE {
+        direction(dx, dy)
+    } else {
+        None
+    }
+}
+
 pub fn did_swipe_increment(
     (x, y): (f64, f64),
     (last_x, last_y): (f64, f64),
+    min_increment: f64,
 ) -> (bool, Option<Direction>) {
+    let min_increment = if min_increment > 0. {
+        min_increment
+    } else {
+        SWIPE_MIN_INCREMENT
+    };
     let dist = distance(last_x, last_y, x, y);
-    let did_swipe = dist >= SWIPE_MIN_INCREMENT;
+    let did_swipe = dist >= min_increment;
     if did_swipe {
         let dx = x - last_x;
         let dy = y - last_y;
@@ -39,7 +84,7 @@ pub fn did_swipe_increment(
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Up,
     Left,
@@ -47,6 +92,36 @@ pub enum Direction {
     Down,
 }
 impl Direction {
+    pub const ALL: [Direction; 4] = [Self::Up, Self::Left, Self::Right, Self::Down];
+
+    pub fn css_class(&self) -> &'static str {
+        match self {
+            Self::Up => "swiping-up",
+            Self::Left => "swiping-left",
+            Self::Right => "swiping-right",
+            Self::Down => "swiping-down",
+        }
+    }
+
+    /// An arrow pointing this way.
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            Self::Up => "↑",
+            Self::Left => "←",
+            Self::Right => "→",
+            Self::Down => "↓",
+        }
+    }
+
+    /// A bit for this direction, for direction bitmasks.
+    pub fn bit(&self) -> u8 {
+        1 << self.as_value()
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Self::Up | Self::Down)
+    }
+
     pub fn as_value(&self) -> u8 {
         match self {
             Self::Up => 0,
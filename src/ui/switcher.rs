@@ -0,0 +1,105 @@
+//! Switch languages or profiles, e.g. by
+//! long-pressing the spacebar.
+
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{BoxExt, ButtonExt, WidgetExt},
+    },
+};
+
+use super::{UIMessage, UIModel, close_button};
+use crate::config::Config;
+
+pub fn switcher(
+    width: i32,
+    height: i32,
+    config: &Config,
+    sender: &ComponentSender<UIModel>,
+) -> gtk::Overlay {
+    let list = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .css_classes(["switcher"])
+        .build();
+
+    let language = config.active_language().map(|lang| lang.name.as_str());
+    let languages = config.languages.iter().map(|lang| lang.name.as_str());
+    section(
+        &list,
+        "Languages",
+        languages,
+        language,
+        sender,
+        UIMessage::Language,
+    );
+
+    let mut profiles: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+    profiles.sort();
+    section(
+        &list,
+        "Profiles",
+        profiles.into_iter(),
+        config.profile.as_deref(),
+        sender,
+        UIMessage::Profile,
+    );
+
+    let scroll = gtk::ScrolledWindow::builder()
+        .child(&list)
+        .width_request(width)
+        .height_request(height)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .build();
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&scroll));
+
+    let sender = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender.input(UIMessage::ToggleSwitcher);
+    }));
+    overlay
+}
+
+/// A heading and a button for each option, with the
+/// active one marked. Empty sections aren't shown.
+fn section<'a>(
+    list: &gtk::Box,
+    heading: &str,
+    names: impl Iterator<Item = &'a str>,
+    active: Option<&str>,
+    sender: &ComponentSender<UIModel>,
+    message: impl Fn(String) -> UIMessage + Clone + 'static,
+) {
+    let row = gtk::FlowBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    for name in names {
+        let button = gtk::Button::builder()
+            .label(name)
+            .css_classes(["switcher-option"])
+            .build();
+        if active == Some(name) {
+            button.add_css_class("active");
+        }
+        let sender = sender.clone();
+        let message = message.clone();
+        let name = name.to_string();
+        button.connect_clicked(move |_| {
+            sender.input(message(name.clone()));
+            sender.input(UIMessage::ToggleSwitcher);
+        });
+        row.insert(&button, -1);
+    }
+    if row.first_child().is_none() {
+        return;
+    }
+    let label = gtk::Label::builder()
+        .label(heading)
+        .halign(gtk::Align::Start)
+        .css_classes(["switcher-heading"])
+        .build();
+    list.append(&label);
+    list.append(&row);
+}
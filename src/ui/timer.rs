@@ -0,0 +1,58 @@
+//! A timeout that can be stopped, e.g. when the
+//! interaction it's waiting on ends early.
+
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use gdk4::glib;
+
+/// A timeout that runs at most once at a time:
+/// starting it again stops the pending run.
+/// Clones share the same timeout.
+#[derive(Clone, Default)]
+pub struct Timer(Rc<Cell<Option<glib::SourceId>>>);
+impl Timer {
+    /// Run `f` once, after `delay`.
+    pub fn once(&self, delay: Duration, f: impl FnOnce() + 'static) {
+        self.stop();
+        let id = self.0.clone();
+        let source = glib::timeout_add_local_once(delay, move || {
+            // The timeout is finished, so drop its id
+            // rather than trying to remove it again.
+            id.take();
+            f();
+        });
+        self.0.set(Some(source));
+    }
+
+    /// Run `f` every `interval`, until it breaks.
+    pub fn repeat(&self, interval: Duration, mut f: impl FnMut() -> glib::ControlFlow + 'static) {
+        self.stop();
+        let id = self.0.clone();
+        let source = glib::timeout_add_local(interval, move || {
+            let flow = f();
+            if flow == glib::ControlFlow::Break {
+                id.take();
+            }
+            flow
+        });
+        self.0.set(Some(source));
+    }
+
+    /// Stop the timeout, returning whether it was pending.
+    pub fn stop(&self) -> bool {
+        match self.0.take() {
+            Some(source) => {
+                source.remove();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        let source = self.0.take();
+        let pending = source.is_some();
+        self.0.set(source);
+        pending
+    }
+}
@@ -0,0 +1,46 @@
+//! Brief messages shown over the keyboard,
+//! e.g. when a command fails.
+
+use std::time::Duration;
+
+use relm4::gtk::{self, prelude::WidgetExt};
+
+use super::timer::Timer;
+
+/// How long a toast is shown for.
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+pub struct Toast {
+    label: gtk::Label,
+    hide_timer: Timer,
+}
+impl Toast {
+    pub fn new() -> Self {
+        let label = gtk::Label::builder()
+            .css_classes(["toast"])
+            .halign(gtk::Align::Center)
+            .valign(gtk::Align::Start)
+            .wrap(true)
+            .max_width_chars(48)
+            .visible(false)
+            .build();
+        Self {
+            label,
+            hide_timer: Timer::default(),
+        }
+    }
+
+    pub fn widget(&self) -> &gtk::Label {
+        &self.label
+    }
+
+    /// Show a message, replacing any current one.
+    pub fn show(&self, text: &str) {
+        self.label.set_label(text);
+        self.label.set_visible(true);
+
+        let label = self.label.clone();
+        self.hide_timer
+            .once(TOAST_DURATION, move || label.set_visible(false));
+    }
+}
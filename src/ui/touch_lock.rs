@@ -0,0 +1,74 @@
+//! Lock the keyboard against touches, e.g. while it's in a
+//! pocket or a child is holding the device. While locked the
+//! halves stay shown but are covered by a shield that takes
+//! all touches. Holding both halves at once (on the background,
+//! when unlocked) locks or unlocks it.
+
+use std::{cell::Cell, rc::Rc};
+
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{BoxExt, GestureExt, WidgetExt},
+    },
+};
+
+use super::{UIMessage, UIModel, gestures::started_on_key};
+
+/// How many times the usual long-press time
+/// (half a second by default) the halves are held for.
+const HOLD_FACTOR: f64 = 3.;
+
+/// Covers a half while it's locked.
+pub fn shield() -> gtk::Box {
+    let shield = gtk::Box::builder()
+        .css_classes(["touch-lock"])
+        .visible(false)
+        .build();
+    let label = gtk::Label::builder()
+        .label("🔒 Hold both halves to unlock")
+        .hexpand(true)
+        .halign(gtk::Align::Center)
+        .valign(gtk::Align::Center)
+        .build();
+    shield.append(&label);
+    shield
+}
+
+/// Toggle the lock when both halves are held at once.
+pub fn watch(windows: (&gtk::Window, &gtk::Window), sender: &ComponentSender<UIModel>) {
+    // Whether each half is being held.
+    let held: Rc<Cell<[bool; 2]>> = Rc::default();
+    for (side, window) in [windows.0, windows.1].into_iter().enumerate() {
+        let press = gtk::GestureLongPress::builder()
+            .delay_factor(HOLD_FACTOR)
+            .build();
+
+        let window_cb = window.clone();
+        let held_cb = held.clone();
+        let sender = sender.clone();
+        press.connect_pressed(move |_, x, y| {
+            // Holding a key does what it normally would.
+            if started_on_key(&window_cb, x, y) {
+                return;
+            }
+            let mut halves = held_cb.get();
+            halves[side] = true;
+            if halves == [true, true] {
+                // Both have to be held again to toggle it back.
+                halves = [false, false];
+                sender.input(UIMessage::ToggleTouchLock);
+            }
+            held_cb.set(halves);
+        });
+
+        let held_cb = held.clone();
+        press.connect_end(move |_, _| {
+            let mut halves = held_cb.get();
+            halves[side] = false;
+            held_cb.set(halves);
+        });
+        window.add_controller(press);
+    }
+}
@@ -0,0 +1,164 @@
+//! A full-window virtual touchpad, as an
+//! alternative to the small Pointer key.
+//!
+//! - One-finger drag: move the pointer
+//! - Two-finger drag: scroll
+//! - One-finger tap: left click
+//! - Two-finger tap: right click
+
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gdk4::glib;
+use relm4::{
+    ComponentSender,
+    gtk::{
+        self,
+        prelude::{GestureDragExt, WidgetExt},
+    },
+};
+
+use crate::pointer::{PointerButton, PointerMessage};
+
+use super::{UIMessage, UIModel, close_button};
+
+/// Pointer pixels moved per pixel dragged.
+const MOVE_SPEED: f64 = 1.5;
+
+/// High-resolution scroll units per pixel dragged.
+const SCROLL_SPEED: f64 = 4.;
+
+/// Drags shorter and quicker than this are taps.
+const TAP_MAX_DISTANCE: f64 = 6.;
+const TAP_MAX_DURATION: Duration = Duration::from_millis(250);
+
+/// Fingers rarely land or lift at exactly the same time,
+/// so one-finger taps this soon after two-finger
+/// activity are ignored.
+const TAP_SUPPRESS: Duration = Duration::from_millis(300);
+
+pub fn touchpad(width: i32, height: i32, sender: &ComponentSender<UIModel>) -> gtk::Overlay {
+    let surface = gtk::Box::builder()
+        .width_request(width)
+        .height_request(height)
+        .css_classes(["touchpad"])
+        .build();
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(&surface));
+
+    let sender_cb = sender.clone();
+    overlay.add_overlay(&close_button(move || {
+        sender_cb.input(UIMessage::ToggleTouchpad);
+    }));
+
+    // When there was last two-finger activity.
+    let multi: Rc<Cell<Option<Instant>>> = Rc::default();
+
+    let drag = gtk::GestureDrag::builder().n_points(1).build();
+    let tracker = DragTracker::default();
+    let tracker_cb = tracker.clone();
+    drag.connect_drag_begin(move |_, _, _| tracker_cb.begin());
+
+    let tracker_cb = tracker.clone();
+    let sender_cb = sender.clone();
+    drag.connect_drag_update(move |_, x, y| {
+        let (dx, dy) = tracker_cb.advance(x, y, MOVE_SPEED);
+        if (dx, dy) != (0, 0) {
+            // Pointer movement is positive upwards.
+            sender_cb.input(PointerMessage::Move(dx, -dy).into());
+        }
+    });
+
+    let multi_cb = multi.clone();
+    let sender_cb = sender.clone();
+    drag.connect_drag_end(move |_, x, y| {
+        if !tracker.is_tap(x, y) {
+            return;
+        }
+
+        // A second finger landing also ends this drag,
+        // so wait until that's been registered.
+        let multi = multi_cb.clone();
+        let sender = sender_cb.clone();
+        glib::idle_add_local_once(move || {
+            let recent_multi = multi.get().is_some_and(|at| at.elapsed() < TAP_SUPPRESS);
+            if !recent_multi {
+                click(PointerButton::Left, &sender);
+            }
+        });
+    });
+    surface.add_controller(drag);
+
+    let drag = gtk::GestureDrag::builder().n_points(2).build();
+    let tracker = DragTracker::default();
+    let tracker_cb = tracker.clone();
+    let multi_cb = multi.clone();
+    drag.connect_drag_begin(move |_, _, _| {
+        multi_cb.set(Some(Instant::now()));
+        tracker_cb.begin();
+    });
+
+    let tracker_cb = tracker.clone();
+    let multi_cb = multi.clone();
+    let sender_cb = sender.clone();
+    drag.connect_drag_update(move |_, x, y| {
+        multi_cb.set(Some(Instant::now()));
+        let (dx, dy) = tracker_cb.advance(x, y, SCROLL_SPEED);
+        if (dx, dy) != (0, 0) {
+            // Dragging upwards scrolls up.
+            sender_cb.input(PointerMessage::ScrollBy(dx, -dy).into());
+        }
+    });
+
+    let sender_cb = sender.clone();
+    drag.connect_drag_end(move |_, x, y| {
+        multi.set(Some(Instant::now()));
+        if tracker.is_tap(x, y) {
+            click(PointerButton::Right, &sender_cb);
+        }
+    });
+    surface.add_controller(drag);
+
+    overlay
+}
+
+fn click(button: PointerButton, sender: &ComponentSender<UIModel>) {
+    sender.input(PointerMessage::Press(button).into());
+    sender.input(PointerMessage::Release(button).into());
+}
+
+/// Tracks a drag's start time and how much of
+/// its offset has been sent so far.
+#[derive(Clone, Default)]
+struct DragTracker {
+    started: Rc<Cell<Option<Instant>>>,
+    sent: Rc<Cell<(f64, f64)>>,
+}
+impl DragTracker {
+    fn begin(&self) {
+        self.started.set(Some(Instant::now()));
+        self.sent.set((0., 0.));
+    }
+
+    /// Scale the unsent part of the offset into whole units,
+    /// keeping any remainder for the next update.
+    fn advance(&self, x: f64, y: f64, scale: f64) -> (i32, i32) {
+        let (sent_x, sent_y) = self.sent.get();
+        let dx = ((x - sent_x) * scale).trunc();
+        let dy = ((y - sent_y) * scale).trunc();
+        self.sent.set((sent_x + dx / scale, sent_y + dy / scale));
+        (dx as i32, dy as i32)
+    }
+
+    fn is_tap(&self, x: f64, y: f64) -> bool {
+        let quick = self
+            .started
+            .get()
+            .is_some_and(|at| at.elapsed() < TAP_MAX_DURATION);
+        quick && x.hypot(y) < TAP_MAX_DISTANCE
+    }
+}
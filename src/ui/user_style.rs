@@ -0,0 +1,75 @@
+//! The user's own CSS, which is re-applied whenever
+//! it changes so themes can be iterated on live.
+
+use std::path::{Path, PathBuf};
+
+use relm4::{
+    Sender,
+    gtk::{
+        self, gdk, gio,
+        prelude::{FileExt, FileMonitorExt},
+    },
+};
+use tracing::{debug, error};
+
+use super::UIMessage;
+
+pub struct UserStyle {
+    provider: gtk::CssProvider,
+    path: PathBuf,
+
+    /// Watches the file for changes.
+    _monitor: Option<gio::FileMonitor>,
+}
+impl UserStyle {
+    /// Apply the CSS at the path (if it exists) above all
+    /// other styles, sending [`UIMessage::ReloadStyle`]
+    /// whenever the file changes.
+    pub fn new(display: &gdk::Display, path: &Path, sender: Sender<UIMessage>) -> Self {
+        let provider = gtk::CssProvider::new();
+        provider.connect_parsing_error(|_, section, err| {
+            error!("Error in user CSS at {}: {}", section, err);
+        });
+        gtk::style_context_add_provider_for_display(
+            display,
+            &provider,
+            gtk::STYLE_PROVIDER_PRIORITY_USER + 2,
+        );
+
+        let monitor = gio::File::for_path(path)
+            .monitor_file(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            .inspect_err(|err| error!("Failed to watch user CSS: {}", err))
+            .ok();
+        if let Some(monitor) = &monitor {
+            monitor.connect_changed(move |_, _, _, event| {
+                use gio::FileMonitorEvent as Event;
+                if matches!(
+                    event,
+                    Event::ChangesDoneHint
+                        | Event::Created
+                        | Event::Deleted
+                        | Event::MovedIn
+                        | Event::Renamed
+                ) {
+                    sender.emit(UIMessage::ReloadStyle);
+                }
+            });
+        }
+
+        let style = Self {
+            provider,
+            path: path.to_path_buf(),
+            _monitor: monitor,
+        };
+        style.reload();
+        style
+    }
+
+    /// Re-read the CSS file. If it's gone,
+    /// the user styles are cleared.
+    pub fn reload(&self) {
+        let css = fs_err::read_to_string(&self.path).unwrap_or_default();
+        debug!("Loading user CSS from {:?}", self.path);
+        self.provider.load_from_string(&css);
+    }
+}
@@ -0,0 +1,316 @@
+//! Basic vi-style navigation for the vim layer, translated
+//! into the equivalent key chords (e.g. `w` is Ctrl+Right),
+//! so it works in any text field.
+
+use evdev::Key;
+
+use crate::layout::Modifier;
+
+/// A key pressed with modifiers.
+pub type Chord = (Vec<Modifier>, Key);
+
+/// Ctrl+R, which is redo in vi.
+pub const REDO: char = '\u{12}';
+
+/// What a vi command does.
+#[derive(Debug, Default, PartialEq)]
+pub struct Output {
+    /// The chords to send, in order.
+    pub chords: Vec<Chord>,
+
+    /// Text to type after the chords, e.g. for `r`.
+    pub text: String,
+
+    /// Whether to leave the vim layer, i.e. enter insert mode.
+    pub insert: bool,
+}
+
+/// The longest count that's accepted, so that
+/// a mistyped count doesn't send thousands of keys.
+const MAX_COUNT: usize = 99;
+
+#[derive(Debug, Default)]
+pub struct Vim {
+    /// A count typed before the command, e.g. the `3` in `3w`.
+    count: Option<usize>,
+
+    /// An operator waiting for its motion, e.g. the first `d` of `dd`,
+    /// or `r` waiting for the character to replace with.
+    operator: Option<char>,
+
+    /// In visual mode (`v`), motions select.
+    visual: bool,
+}
+impl Vim {
+    /// The mode, with any pending count and operator,
+    /// for the mode indicator.
+    pub fn indicator(&self) -> String {
+        let mode = if self.visual { "VISUAL" } else { "NORMAL" };
+        let count = self.count.map(|n| n.to_string()).unwrap_or_default();
+        let operator = self.operator.map(String::from).unwrap_or_default();
+        format!("{} {}{}", mode, count, operator)
+            .trim_end()
+            .to_string()
+    }
+
+    /// Forget any pending count or operator, and leave visual mode.
+    pub fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.visual = false;
+    }
+
+    pub fn handle(&mut self, c: char) -> Output {
+        // Counts, with a leading `0` being a motion instead,
+        // and any digit after `r` being the replacement.
+        let counting = self.count.is_some();
+        let replacing = self.operator == Some('r');
+        if let Some(digit) = c
+            .to_digit(10)
+            .filter(|digit| (*digit != 0 || counting) && !replacing)
+        {
+            let count = self.count.unwrap_or(0) * 10 + digit as usize;
+            self.count = Some(count.min(MAX_COUNT));
+            return Output::default();
+        }
+
+        let count = self.count.take().unwrap_or(1);
+        let chords = match self.operator.take() {
+            Some('r') => {
+                return Output {
+                    chords: vec![key(Key::KEY_DELETE); count],
+                    text: c.to_string().repeat(count),
+                    insert: false,
+                };
+            }
+            Some(operator) if self.visual => match (operator, c) {
+                ('g', 'g') => vec![(vec![Modifier::Ctrl, Modifier::Shift], Key::KEY_HOME)],
+                _ => vec![],
+            },
+            Some(operator) => operator_chords(operator, c),
+            None if self.visual => match c {
+                'v' => {
+                    self.visual = false;
+                    return Output::default();
+                }
+                'd' | 'x' => {
+                    self.visual = false;
+                    return chords(vec![ctrl(Key::KEY_X)]);
+                }
+                'y' => {
+                    self.visual = false;
+                    return chords(vec![ctrl(Key::KEY_C), key(Key::KEY_LEFT)]);
+                }
+                'g' => {
+                    self.operator = Some(c);
+                    return Output::default();
+                }
+                _ => motion_chords(c)
+                    .into_iter()
+                    .map(|(mut modifiers, key)| {
+                        modifiers.push(Modifier::Shift);
+                        (modifiers, key)
+                    })
+                    .collect(),
+            },
+            None => match c {
+                'd' | 'y' | 'g' | 'r' => {
+                    self.operator = Some(c);
+                    self.count = Some(count).filter(|n| *n > 1);
+                    return Output::default();
+                }
+                'v' => {
+                    self.visual = true;
+                    return Output::default();
+                }
+                'i' | 'a' | 'I' | 'A' | 'o' | 'O' => {
+                    return Output {
+                        chords: insert_chords(c),
+                        text: String::new(),
+                        insert: true,
+                    };
+                }
+                _ => command_chords(c),
+            },
+        };
+
+        let mut output = Output::default();
+        for _ in 0..count {
+            output.chords.extend(chords.iter().cloned());
+        }
+        output
+    }
+}
+
+fn chords(chords: Vec<Chord>) -> Output {
+    Output {
+        chords,
+        ..Output::default()
+    }
+}
+
+fn key(key: Key) -> Chord {
+    (vec![], key)
+}
+
+fn ctrl(key: Key) -> Chord {
+    (vec![Modifier::Ctrl], key)
+}
+
+fn shift(key: Key) -> Chord {
+    (vec![Modifier::Shift], key)
+}
+
+/// Motions, which move the cursor (or select, in visual mode).
+fn motion_chords(c: char) -> Vec<Chord> {
+    match c {
+        'h' => vec![key(Key::KEY_LEFT)],
+        'j' => vec![key(Key::KEY_DOWN)],
+        'k' => vec![key(Key::KEY_UP)],
+        'l' => vec![key(Key::KEY_RIGHT)],
+        'w' | 'e' => vec![ctrl(Key::KEY_RIGHT)],
+        'b' => vec![ctrl(Key::KEY_LEFT)],
+        '0' | '^' => vec![key(Key::KEY_HOME)],
+        '$' => vec![key(Key::KEY_END)],
+        'G' => vec![ctrl(Key::KEY_END)],
+        _ => vec![],
+    }
+}
+
+/// Commands that don't take a motion.
+fn command_chords(c: char) -> Vec<Chord> {
+    match c {
+        'x' => vec![key(Key::KEY_DELETE)],
+        'X' => vec![key(Key::KEY_BACKSPACE)],
+        'u' => vec![ctrl(Key::KEY_Z)],
+        REDO => vec![(vec![Modifier::Ctrl, Modifier::Shift], Key::KEY_Z)],
+        'p' => vec![ctrl(Key::KEY_V)],
+        _ => motion_chords(c),
+    }
+}
+
+/// An operator followed by a motion, e.g. `dw`.
+fn operator_chords(operator: char, motion: char) -> Vec<Chord> {
+    match (operator, motion) {
+        // Select the line, including its newline.
+        ('d', 'd') => vec![
+            key(Key::KEY_HOME),
+            shift(Key::KEY_DOWN),
+            key(Key::KEY_DELETE),
+        ],
+        ('d', 'w' | 'e') => vec![ctrl(Key::KEY_DELETE)],
+        ('d', 'b') => vec![ctrl(Key::KEY_BACKSPACE)],
+        ('d', '$') => vec![shift(Key::KEY_END), key(Key::KEY_DELETE)],
+        ('d', '0') => vec![shift(Key::KEY_HOME), key(Key::KEY_DELETE)],
+        ('y', 'y') => vec![
+            key(Key::KEY_HOME),
+            shift(Key::KEY_END),
+            ctrl(Key::KEY_C),
+            key(Key::KEY_HOME),
+        ],
+        ('g', 'g') => vec![ctrl(Key::KEY_HOME)],
+        _ => vec![],
+    }
+}
+
+/// Where to put the cursor before entering insert mode.
+fn insert_chords(c: char) -> Vec<Chord> {
+    match c {
+        'a' => vec![key(Key::KEY_RIGHT)],
+        'I' => vec![key(Key::KEY_HOME)],
+        'A' => vec![key(Key::KEY_END)],
+        'o' => vec![key(Key::KEY_END), key(Key::KEY_ENTER)],
+        'O' => vec![key(Key::KEY_HOME), key(Key::KEY_ENTER), key(Key::KEY_UP)],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(vim: &mut Vim, keys: &str) -> Output {
+        let mut output = Output::default();
+        for c in keys.chars() {
+            output = vim.handle(c);
+        }
+        output
+    }
+
+    #[test]
+    fn counts() {
+        let mut vim = Vim::default();
+        assert_eq!(run(&mut vim, "3l").chords, vec![key(Key::KEY_RIGHT); 3]);
+        assert_eq!(run(&mut vim, "12x").chords.len(), 12);
+        assert_eq!(vim.indicator(), "NORMAL");
+    }
+
+    #[test]
+    fn operators() {
+        let mut vim = Vim::default();
+        assert_eq!(vim.handle('d'), Output::default());
+        assert_eq!(vim.indicator(), "NORMAL d");
+        assert_eq!(vim.handle('w'), chords(vec![ctrl(Key::KEY_DELETE)]));
+        assert_eq!(
+            run(&mut vim, "2dd").chords.len(),
+            2 * operator_chords('d', 'd').len()
+        );
+        assert_eq!(run(&mut vim, "gg").chords, vec![ctrl(Key::KEY_HOME)]);
+        assert_eq!(vim.indicator(), "NORMAL");
+    }
+
+    #[test]
+    fn replace() {
+        let mut vim = Vim::default();
+        vim.handle('r');
+        assert_eq!(vim.indicator(), "NORMAL r");
+        let output = vim.handle('d');
+        assert_eq!(output.chords, vec![key(Key::KEY_DELETE)]);
+        assert_eq!(output.text, "d");
+
+        assert_eq!(run(&mut vim, "r5").text, "5");
+
+        let output = run(&mut vim, "3rx");
+        assert_eq!(output.chords, vec![key(Key::KEY_DELETE); 3]);
+        assert_eq!(output.text, "xxx");
+        assert!(!output.insert);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut vim = Vim::default();
+        assert_eq!(vim.handle('u').chords, vec![ctrl(Key::KEY_Z)]);
+        assert_eq!(
+            vim.handle(REDO).chords,
+            vec![(vec![Modifier::Ctrl, Modifier::Shift], Key::KEY_Z)]
+        );
+    }
+
+    #[test]
+    fn insert() {
+        let mut vim = Vim::default();
+        let output = vim.handle('A');
+        assert!(output.insert);
+        assert_eq!(output.chords, vec![key(Key::KEY_END)]);
+        assert!(!vim.handle('l').insert);
+    }
+
+    #[test]
+    fn visual() {
+        let mut vim = Vim::default();
+        vim.handle('v');
+        assert_eq!(vim.indicator(), "VISUAL");
+        assert_eq!(run(&mut vim, "2l").chords, vec![shift(Key::KEY_RIGHT); 2]);
+        assert_eq!(
+            run(&mut vim, "gg").chords,
+            vec![(vec![Modifier::Ctrl, Modifier::Shift], Key::KEY_HOME)]
+        );
+        assert_eq!(vim.handle('d').chords, vec![ctrl(Key::KEY_X)]);
+        assert_eq!(vim.indicator(), "NORMAL");
+        assert_eq!(vim.handle('l').chords, vec![key(Key::KEY_RIGHT)]);
+
+        vim.handle('v');
+        vim.handle('v');
+        assert_eq!(vim.indicator(), "NORMAL");
+    }
+}
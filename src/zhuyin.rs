@@ -0,0 +1,167 @@
+//! Zhuyin (Bopomofo) composition for Traditional Chinese.
+//!
+//! Symbols are composed into a syllable, shown as preedit text,
+//! and the syllable's characters are offered in the suggestion bar.
+//! A syllable has at most one initial, medial, final, and tone;
+//! typing another of the same kind replaces it, as is usual.
+//!
+//! Characters come from a table file, one syllable per line
+//! followed by its characters, most common first, e.g.:
+//!
+//! ```text
+//! ㄓㄨˋ 注 住 助 祝
+//! ㄋㄧˇ 你 妳 擬
+//! ```
+//!
+//! The first tone has no mark.
+
+use std::{collections::HashMap, path::Path};
+
+use tracing::error;
+
+/// Tone marks, for the second to fifth (neutral) tones.
+const TONES: [char; 4] = ['ˊ', 'ˇ', 'ˋ', '˙'];
+
+pub struct Table(HashMap<String, Vec<String>>);
+impl Table {
+    /// Load a table, logging and skipping it if it can't be read.
+    pub fn load(path: &Path) -> Self {
+        let text = fs_err::read_to_string(path).unwrap_or_else(|err| {
+            error!("Failed to load Zhuyin table: {}", err);
+            String::new()
+        });
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let syllable = parts.next()?.to_string();
+                Some((syllable, parts.map(str::to_string).collect()))
+            })
+            .collect();
+        Self(entries)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Composer {
+    initial: Option<char>,
+    medial: Option<char>,
+    final_: Option<char>,
+    tone: Option<char>,
+}
+impl Composer {
+    /// Whether a syllable is being composed.
+    pub fn is_composing(&self) -> bool {
+        !self.syllable().is_empty()
+    }
+
+    /// The syllable so far, in the usual order.
+    pub fn syllable(&self) -> String {
+        [self.initial, self.medial, self.final_, self.tone]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Add a symbol or tone mark, returning
+    /// whether it was one (otherwise it's ignored).
+    pub fn push(&mut self, c: char) -> bool {
+        let slot = match c {
+            'ㄅ'..='ㄙ' => &mut self.initial,
+            'ㄧ'..='ㄩ' => &mut self.medial,
+            'ㄚ'..='ㄦ' => &mut self.final_,
+            _ if TONES.contains(&c) => &mut self.tone,
+            _ => return false,
+        };
+        *slot = Some(c);
+        true
+    }
+
+    /// Remove the last symbol.
+    pub fn pop(&mut self) {
+        for slot in [
+            &mut self.tone,
+            &mut self.final_,
+            &mut self.medial,
+            &mut self.initial,
+        ] {
+            if slot.take().is_some() {
+                return;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Characters for the syllable, most common first. Without
+    /// a tone mark, characters of any tone are offered.
+    pub fn candidates(&self, table: &Table, limit: usize) -> Vec<String> {
+        let syllable = self.syllable();
+        let tones = if self.tone.is_some() {
+            vec![syllable]
+        } else {
+            std::iter::once(syllable.clone())
+                .chain(TONES.iter().map(|tone| format!("{}{}", syllable, tone)))
+                .collect()
+        };
+        tones
+            .iter()
+            .filter_map(|syllable| table.0.get(syllable))
+            .flatten()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compose(symbols: &str) -> Composer {
+        let mut composer = Composer::default();
+        for c in symbols.chars() {
+            composer.push(c);
+        }
+        composer
+    }
+
+    #[test]
+    fn syllable_order() {
+        assert_eq!(compose("ˇㄧㄋ").syllable(), "ㄋㄧˇ");
+        assert_eq!(compose("ㄓㄨㄔ").syllable(), "ㄔㄨ");
+
+        let mut composer = Composer::default();
+        assert!(!composer.push('a'));
+        assert!(!composer.is_composing());
+    }
+
+    #[test]
+    fn pop() {
+        let mut composer = compose("ㄓㄨˋ");
+        composer.pop();
+        assert_eq!(composer.syllable(), "ㄓㄨ");
+        composer.pop();
+        composer.pop();
+        assert!(!composer.is_composing());
+        composer.pop();
+        assert!(!composer.is_composing());
+    }
+
+    #[test]
+    fn candidates() {
+        let table = Table::parse("ㄓㄨˋ 注 住 助\nㄓㄨ 朱 豬\nㄋㄧˇ 你 妳\n");
+        assert_eq!(compose("ㄓㄨˋ").candidates(&table, 2), ["注", "住"]);
+        assert_eq!(
+            compose("ㄓㄨ").candidates(&table, 10),
+            ["朱", "豬", "注", "住", "助"]
+        );
+        assert!(compose("ㄓㄨˇ").candidates(&table, 10).is_empty());
+    }
+}